@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 use criterion::{Criterion, criterion_group, criterion_main};
 use std::hint::black_box;
 
@@ -11,11 +13,11 @@ mod test_helpers {
 
     pub fn create_camera() -> camera::Camera {
         let mut camera = camera::Camera::new(100 * SCALE, 50 * SCALE, std::f64::consts::PI / 3.0);
-        camera.transform = transformation::view_transform(
+        camera.set_transformation_matrix(transformation::view_transform(
             &tuple::Point::new(0.0, 1.5, -5.0),
             &tuple::Point::new(0.0, 1.0, 0.0),
             &tuple::Vector::new(0.0, 1.0, 0.0),
-        );
+        ));
         return camera;
     }
 
@@ -1,27 +1,48 @@
+#![allow(clippy::style)]
+
 use criterion::{Criterion, criterion_group, criterion_main};
+use ray_tracer::{obj_file, ray};
 use std::hint::black_box;
 
 mod test_helpers {
     use ray_tracer::transformation::Transform;
     use ray_tracer::{
-        camera, color, lights, material, matrix, obj_file, sequences, shape, transformation, tuple,
-        world,
+        camera, color, lights, material, matrix, obj_file, ray, sequences, shape, transformation,
+        tuple, world,
     };
 
     pub fn create_camera() -> camera::Camera {
         let mut camera = camera::Camera::new(100, 75, std::f64::consts::PI / 3.0);
-        camera.transform = transformation::view_transform(
+        camera.set_transformation_matrix(transformation::view_transform(
             &tuple::Point::new(0.0, 2.0, -4.0),
             &tuple::Point::new(0.0, 0.7, 0.0),
             &tuple::Vector::new(0.0, 1.0, 0.0),
-        );
+        ));
         return camera;
     }
 
+    // An OBJ model as a group subdivided by the midpoint split.
+    pub fn midpoint_model(model_path: &str) -> shape::Shape {
+        let source = std::fs::read_to_string(model_path).unwrap();
+        return obj_file::parse_obj(&source).into_group();
+    }
+
+    // An OBJ model as a group subdivided by the surface area heuristic.
+    pub fn sah_model(model_path: &str) -> shape::Shape {
+        let source = std::fs::read_to_string(model_path).unwrap();
+        return obj_file::parse_obj(&source).into_sah_group();
+    }
+
+    // An OBJ model as one mesh, with its own hierarchy over the faces.
+    pub fn mesh_model(model_path: &str) -> shape::Shape {
+        let source = std::fs::read_to_string(model_path).unwrap();
+        return obj_file::parse_obj(&source).into_mesh();
+    }
+
     // A teapot standing on a matte floor, the same scene as the OBJ model
     // render tests. The light is a parameter so the soft shadow bench can
     // swap the point light for an area light.
-    pub fn create_teapot_world(model_path: &str, light: lights::Light) -> world::World {
+    pub fn create_teapot_world(model: shape::Shape, light: lights::Light) -> world::World {
         let mut builder = world::WorldBuilder::new();
 
         builder.add_shape(
@@ -37,9 +58,8 @@ mod test_helpers {
 
         // The model is built z-up and roughly 32 units wide, so stand it up
         // on the y axis and scale it down to about three units across.
-        let source = std::fs::read_to_string(model_path).unwrap();
         builder.add_shape(
-            shape::ShapeBuilder::from(obj_file::parse_obj(&source).into_group())
+            shape::ShapeBuilder::from(model)
                 .set_transform(
                     matrix::Matrix4::IDENTITY
                         .rotation_x(-std::f64::consts::PI / 2.0)
@@ -71,13 +91,32 @@ mod test_helpers {
         light.set_jitter(sequences::Sequence::random(256, 0x5EED));
         return light;
     }
+
+    // Shadow rays from a grid of points on the floor around the teapot to
+    // the area light's samples, with the distance to each sample.
+    pub fn shadow_rays(light: &lights::Light) -> Vec<(ray::Ray, f64)> {
+        let mut rays = vec![];
+        for i in 0..20 {
+            for j in 0..20 {
+                let point = tuple::Point::new(-2.0 + i as f64 * 0.2, 0.0001, -2.0 + j as f64 * 0.2);
+                for v in 0..8 {
+                    for u in 0..8 {
+                        let to_light = lights::point_on_light(light, u, v) - point;
+                        let distance = tuple::magnitude(&to_light);
+                        rays.push((ray::ray(point, tuple::normalize(&to_light)), distance));
+                    }
+                }
+            }
+        }
+        return rays;
+    }
 }
 
 // The low-poly teapot (240 triangles): mostly a measure of BVH traversal
 // and smooth triangle intersection on a small mesh.
 fn low_poly_teapot_benchmark(c: &mut Criterion) {
     let world = test_helpers::create_teapot_world(
-        "object_files/teapot-low.obj",
+        test_helpers::midpoint_model("object_files/teapot-low.obj"),
         test_helpers::point_light(),
     );
     let camera = test_helpers::create_camera();
@@ -88,22 +127,32 @@ fn low_poly_teapot_benchmark(c: &mut Criterion) {
 }
 
 // The high-poly teapot (6,320 triangles): the same image, but the BVH is
-// several levels deeper.
+// several levels deeper. Rendered walking the shape tree, then again
+// through the hierarchy `World` compiles from it.
 fn high_poly_teapot_benchmark(c: &mut Criterion) {
-    let world =
-        test_helpers::create_teapot_world("object_files/teapot.obj", test_helpers::point_light());
+    let mut world = test_helpers::create_teapot_world(
+        test_helpers::midpoint_model("object_files/teapot.obj"),
+        test_helpers::point_light(),
+    );
     let camera = test_helpers::create_camera();
 
+    world.set_compiled(false);
     c.bench_function("render high poly teapot", |b| {
         b.iter(|| camera.render(black_box(&world)))
     });
+
+    world.set_compiled(true);
+    world.build_bvh();
+    c.bench_function("render high poly teapot, compiled BVH", |b| {
+        b.iter(|| camera.render(black_box(&world)))
+    });
 }
 
 // The low-poly teapot under an 8x8 area light: measures the cost of soft
 // shadows, which multiply the shadow rays per shaded point by 64.
 fn soft_shadow_teapot_benchmark(c: &mut Criterion) {
     let world = test_helpers::create_teapot_world(
-        "object_files/teapot-low.obj",
+        test_helpers::midpoint_model("object_files/teapot-low.obj"),
         test_helpers::area_light(),
     );
     let camera = test_helpers::create_camera();
@@ -113,11 +162,164 @@ fn soft_shadow_teapot_benchmark(c: &mut Criterion) {
     });
 }
 
+// The low-poly teapot anti-aliased adaptively: only silhouette and shadow
+// edge pixels are refined. Prints how many extra rays each threshold
+// spends, to weigh against uniform 4x supersampling's 3 extra rays per
+// pixel (22,500 for this 100x75 image).
+fn adaptive_teapot_benchmark(c: &mut Criterion) {
+    let world = test_helpers::create_teapot_world(
+        test_helpers::midpoint_model("object_files/teapot-low.obj"),
+        test_helpers::point_light(),
+    );
+    let camera = test_helpers::create_camera();
+
+    for threshold in [0.05, 0.1, 0.2, 0.4] {
+        let result = camera.render_adaptive(&world, threshold, 2);
+        println!(
+            "adaptive threshold {}: {} pixels refined, {} extra rays",
+            threshold, result.refined_pixels, result.extra_rays
+        );
+    }
+
+    c.bench_function("render adaptive teapot", |b| {
+        b.iter(|| camera.render_adaptive(black_box(&world), 0.1, 2))
+    });
+}
+
+// Just the soft shadow teapot's shadow rays, answered the old way, by
+// collecting and sorting every hit along each ray to find the nearest,
+// and by the occlusion query, which stops at the first hit and skips
+// groups whose bounding box the ray misses.
+fn shadow_ray_benchmark(c: &mut Criterion) {
+    let world = test_helpers::create_teapot_world(
+        test_helpers::midpoint_model("object_files/teapot-low.obj"),
+        test_helpers::area_light(),
+    );
+    let rays = test_helpers::shadow_rays(&world.lights[0]);
+
+    c.bench_function("shadow rays, nearest hit", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|(ray, distance)| {
+                    let intersections = ray.intersect_world(black_box(&world));
+                    ray::hit(&intersections).is_some_and(|hit| hit.t < *distance)
+                })
+                .count()
+        })
+    });
+    c.bench_function("shadow rays, any hit", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|(ray, distance)| black_box(&world).occluded(ray, *distance, &mut |_| true))
+                .count()
+        })
+    });
+}
+
+// The midpoint and surface area heuristic BVHs side by side. Prints the
+// shape of each tree for the larger models, then times the camera's rays
+// finding their closest hit on the high-poly teapot through each, and
+// through the flattened hierarchy `World` compiles.
+fn bvh_builder_benchmark(c: &mut Criterion) {
+    for path in [
+        "object_files/teapot.obj",
+        "object_files/teddy.obj",
+        "object_files/cow-nonormals.obj",
+    ] {
+        for (builder, model) in [
+            ("midpoint", test_helpers::midpoint_model(path)),
+            ("SAH", test_helpers::sah_model(path)),
+        ] {
+            let stats = model.bvh_stats();
+            println!(
+                "{} ({}): depth {}, {} leaves, {:.1} primitives per leaf, largest leaf {}",
+                path,
+                builder,
+                stats.depth,
+                stats.leaves,
+                stats.primitives as f64 / stats.leaves as f64,
+                stats.largest_leaf
+            );
+        }
+    }
+
+    let camera = test_helpers::create_camera();
+    let mut rays = vec![];
+    for y in 0..75 {
+        for x in 0..100 {
+            rays.extend(camera.ray_for_pixel(x, y));
+        }
+    }
+    for (name, model, compiled) in [
+        (
+            "closest hits, midpoint BVH",
+            test_helpers::midpoint_model("object_files/teapot.obj"),
+            false,
+        ),
+        (
+            "closest hits, SAH BVH",
+            test_helpers::sah_model("object_files/teapot.obj"),
+            false,
+        ),
+        (
+            "closest hits, compiled BVH",
+            test_helpers::sah_model("object_files/teapot.obj"),
+            true,
+        ),
+    ] {
+        let mut world = test_helpers::create_teapot_world(model, test_helpers::point_light());
+        world.set_compiled(compiled);
+        if compiled {
+            world.build_bvh();
+        }
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                rays.iter()
+                    .filter(|ray| black_box(&world).closest_hit(ray).is_some())
+                    .count()
+            })
+        });
+    }
+}
+
+// OBJ models loaded as a group of triangles organized by the surface area
+// heuristic and as a single mesh, then the high-poly teapot rendered as a
+// mesh, through the shape tree and through the compiled hierarchy.
+fn mesh_benchmark(c: &mut Criterion) {
+    for path in ["object_files/teapot.obj", "object_files/teddy.obj"] {
+        let source = std::fs::read_to_string(path).unwrap();
+        c.bench_function(&format!("load {} as a group", path), |b| {
+            b.iter(|| obj_file::parse_obj(black_box(&source)).into_sah_group())
+        });
+        c.bench_function(&format!("load {} as a mesh", path), |b| {
+            b.iter(|| obj_file::parse_obj(black_box(&source)).into_mesh())
+        });
+    }
+
+    let mut world = test_helpers::create_teapot_world(
+        test_helpers::mesh_model("object_files/teapot.obj"),
+        test_helpers::point_light(),
+    );
+    let camera = test_helpers::create_camera();
+
+    world.set_compiled(false);
+    c.bench_function("render high poly teapot mesh", |b| {
+        b.iter(|| camera.render(black_box(&world)))
+    });
+
+    world.set_compiled(true);
+    world.build_bvh();
+    c.bench_function("render high poly teapot mesh, compiled BVH", |b| {
+        b.iter(|| camera.render(black_box(&world)))
+    });
+}
+
 criterion_group! {
     name = benches;
     // Renders are slow (hundreds of milliseconds each), so take fewer
     // samples than criterion's default of 100.
     config = Criterion::default().sample_size(10);
-    targets = low_poly_teapot_benchmark, high_poly_teapot_benchmark, soft_shadow_teapot_benchmark
+    targets = low_poly_teapot_benchmark, high_poly_teapot_benchmark, soft_shadow_teapot_benchmark,
+        adaptive_teapot_benchmark, shadow_ray_benchmark, bvh_builder_benchmark, mesh_benchmark
 }
 criterion_main!(benches);
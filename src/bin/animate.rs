@@ -14,6 +14,8 @@
 //! `--scale` multiplies the scene's base resolution, so the same file can
 //! render small while iterating and large for a shareable asset.
 
+#![allow(clippy::style)]
+
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use ray_tracer::scene_file;
@@ -116,7 +118,9 @@ fn main() {
                     let world = animation
                         .build_world(index)
                         .unwrap_or_else(|error| fail(&error));
-                    let camera = animation.build_camera(index, arguments.scale);
+                    let camera = animation
+                        .build_camera(index, arguments.scale)
+                        .unwrap_or_else(|error| fail(&error));
                     let canvas = camera.render(&world);
 
                     let path = format!("{}/frame_{:03}.png", output_dir, index);
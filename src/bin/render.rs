@@ -7,21 +7,76 @@
 //!
 //! Usage:
 //!
-//!     cargo run --release --bin render -- <scene.toml> [--scale N] [--output PATH]
+//!     cargo run --release --bin render -- <scene.toml> [--scale N] [--output PATH] [--threads N]
+//!         [--stereo LAYOUT [--interocular D] [--toe-in DISTANCE]]
+//!         [--region X,Y,W,H [--full-frame]]
+//!         [--passes N] [--time DURATION] [--snapshot-every N] [--ambient-occlusion]
 //!
 //! `--scale` multiplies the scene's base resolution, so the same file can
 //! render small while iterating and large for a shareable asset.
+//! `--threads` sets how many worker threads shade the image; it defaults
+//! to the number of cores.
+//!
+//! `--stereo` renders a left and right eye pair from the scene's camera
+//! and packs them into one PNG: `side-by-side`, `top-bottom`, or
+//! `anaglyph` (red/cyan). The eyes sit `--interocular` apart (0.1 units by
+//! default) and look parallel unless `--toe-in` turns them to meet the
+//! given distance in front of the camera.
+//!
+//! A progress line shows how much of the image is done and roughly how
+//! long the rest will take.
+//!
+//! `--region` renders only the `W` by `H` pixels whose top left corner is
+//! at (`X`, `Y`) in the scaled image, and writes just those pixels. With
+//! `--full-frame` the PNG keeps the full image size and the rest of it is
+//! black. Either way the region's pixels match a full render exactly.
+//!
+//! `--passes` or `--time` render progressively instead: one sample per
+//! pixel per pass, averaged, until the given number of passes is done or
+//! the time (such as `30s` or `5m`) runs out at the end of a pass. The
+//! scene's `samples_per_pixel` is ignored. `--snapshot-every N` writes the
+//! image so far after every N passes, to watch it converge: next to the
+//! output, numbered by pass (`out.pass-0004.png` for `out.png`). The
+//! output itself is only written once the render is done. Progressive
+//! rendering can't be combined with `--stereo`.
+//!
+//! `--ambient-occlusion` renders a grayscale image of how occluded each
+//! visible point is instead of shading it, using the scene's
+//! `ambient_occlusion` settings if it has any.
+
+#![allow(clippy::style)]
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use ray_tracer::{camera, canvas, scene_file, stereo, world};
 
-use ray_tracer::scene_file;
+// Eye separation for `--stereo` when `--interocular` isn't given.
+const DEFAULT_INTEROCULAR: f64 = 0.1;
 
 struct Arguments {
     scene_path: String,
     scale: u32,
     output: Option<String>,
+    threads: usize,
+    stereo: Option<stereo::Layout>,
+    interocular: f64,
+    toe_in: Option<f64>,
+    region: Option<camera::Region>,
+    full_frame: bool,
+    passes: Option<u32>,
+    time: Option<Duration>,
+    snapshot_every: Option<u32>,
+    ambient_occlusion: bool,
 }
 
 fn usage() -> ! {
-    eprintln!("usage: render <scene.toml> [--scale N] [--output PATH]");
+    eprintln!(
+        "usage: render <scene.toml> [--scale N] [--output PATH] [--threads N]\n\
+         \x20             [--stereo side-by-side|top-bottom|anaglyph [--interocular D] [--toe-in DISTANCE]]\n\
+         \x20             [--region X,Y,W,H [--full-frame]]\n\
+         \x20             [--passes N] [--time DURATION] [--snapshot-every N] [--ambient-occlusion]"
+    );
     std::process::exit(2);
 }
 
@@ -30,10 +85,95 @@ fn fail(message: &str) -> ! {
     std::process::exit(1);
 }
 
+fn save(canvas: &canvas::Canvas, path: &str) {
+    canvas
+        .canvas_to_image()
+        .save(path)
+        .unwrap_or_else(|error| fail(&format!("could not write `{}`: {}", path, error)));
+}
+
+// Where the snapshot taken after `passes` passes goes: the output path
+// with the pass number before its extension.
+fn snapshot_path(output: &str, passes: u32) -> String {
+    let path = std::path::Path::new(output);
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("png");
+    return format!(
+        "{}.pass-{:04}.{}",
+        path.with_extension("").display(),
+        passes,
+        extension
+    );
+}
+
+// Parses a number of seconds, minutes, or hours: `90`, `30s`, `5m`, `1.5h`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = match value.char_indices().last()? {
+        (i, 's') => (&value[..i], 1.0),
+        (i, 'm') => (&value[..i], 60.0),
+        (i, 'h') => (&value[..i], 3600.0),
+        _ => (value, 1.0),
+    };
+    let seconds = number.parse::<f64>().ok()? * unit;
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return None;
+    }
+    return Some(Duration::from_secs_f64(seconds));
+}
+
+// Parses `X,Y,W,H`.
+fn parse_region(value: &str) -> Option<camera::Region> {
+    let numbers: Vec<u32> = value
+        .split(',')
+        .map(|number| number.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    return match numbers[..] {
+        [x, y, width, height] if width > 0 && height > 0 => Some(camera::Region {
+            x,
+            y,
+            width,
+            height,
+        }),
+        _ => None,
+    };
+}
+
+// Rewrites the progress line in place.
+fn print_progress(progress: &camera::Progress) -> camera::Control {
+    let eta = match progress.eta() {
+        Some(eta) => format!("{}s left", eta.as_secs()),
+        None => String::from("estimating time left"),
+    };
+    print!(
+        "\r{:5.1}% ({}/{} pixels), {}s elapsed, {}   ",
+        progress.fraction() * 100.0,
+        progress.completed,
+        progress.total,
+        progress.elapsed.as_secs(),
+        eta
+    );
+    std::io::stdout().flush().unwrap();
+    return camera::Control::Continue;
+}
+
 fn parse_arguments() -> Arguments {
     let mut scene_path = None;
     let mut scale = 1;
     let mut output = None;
+    let mut threads = std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1);
+    let mut stereo = None;
+    let mut interocular = DEFAULT_INTEROCULAR;
+    let mut toe_in = None;
+    let mut region = None;
+    let mut full_frame = false;
+    let mut passes = None;
+    let mut time = None;
+    let mut snapshot_every = None;
+    let mut ambient_occlusion = false;
 
     let mut arguments = std::env::args().skip(1);
     while let Some(argument) = arguments.next() {
@@ -47,6 +187,72 @@ fn parse_arguments() -> Arguments {
             "--output" => {
                 output = Some(arguments.next().unwrap_or_else(|| usage()));
             }
+            "--threads" => {
+                threads = arguments
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&count| count > 0)
+                    .unwrap_or_else(|| usage());
+            }
+            "--stereo" => {
+                stereo = match arguments.next().as_deref() {
+                    Some("side-by-side") => Some(stereo::Layout::SideBySide),
+                    Some("top-bottom") => Some(stereo::Layout::TopBottom),
+                    Some("anaglyph") => Some(stereo::Layout::Anaglyph),
+                    _ => usage(),
+                };
+            }
+            "--interocular" => {
+                interocular = arguments
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or_else(|| usage());
+            }
+            "--toe-in" => {
+                toe_in = Some(
+                    arguments
+                        .next()
+                        .and_then(|value| value.parse().ok())
+                        .filter(|&distance: &f64| distance > 0.0)
+                        .unwrap_or_else(|| usage()),
+                );
+            }
+            "--region" => {
+                region = Some(
+                    arguments
+                        .next()
+                        .and_then(|value| parse_region(&value))
+                        .unwrap_or_else(|| usage()),
+                );
+            }
+            "--full-frame" => full_frame = true,
+            "--passes" => {
+                passes = Some(
+                    arguments
+                        .next()
+                        .and_then(|value| value.parse().ok())
+                        .filter(|&count| count > 0)
+                        .unwrap_or_else(|| usage()),
+                );
+            }
+            "--time" => {
+                time = Some(
+                    arguments
+                        .next()
+                        .and_then(|value| parse_duration(&value))
+                        .unwrap_or_else(|| usage()),
+                );
+            }
+            "--snapshot-every" => {
+                snapshot_every = Some(
+                    arguments
+                        .next()
+                        .and_then(|value| value.parse().ok())
+                        .filter(|&count| count > 0)
+                        .unwrap_or_else(|| usage()),
+                );
+            }
+            "--ambient-occlusion" => ambient_occlusion = true,
             "--help" | "-h" => usage(),
             _ if scene_path.is_none() && !argument.starts_with('-') => {
                 scene_path = Some(argument);
@@ -59,6 +265,16 @@ fn parse_arguments() -> Arguments {
         scene_path: scene_path.unwrap_or_else(|| usage()),
         scale,
         output,
+        threads,
+        stereo,
+        interocular,
+        toe_in,
+        region,
+        full_frame,
+        passes,
+        time,
+        snapshot_every,
+        ambient_occlusion,
     };
 }
 
@@ -84,16 +300,98 @@ fn main() {
         scene.settings().width * arguments.scale,
         scene.settings().height * arguments.scale
     );
-    let world = scene.build_world().unwrap_or_else(|error| fail(&error));
-    let camera = scene.build_camera(arguments.scale);
-    let canvas = camera.render(&world);
-
+    let progressive = arguments.passes.is_some() || arguments.time.is_some();
+    if progressive && arguments.stereo.is_some() {
+        fail("progressive rendering (`--passes` or `--time`) can't be combined with `--stereo`");
+    }
     let output = arguments
         .output
+        .clone()
         .unwrap_or_else(|| format!("{}.png", scene.settings().name));
-    canvas
-        .canvas_to_image()
-        .save(&output)
-        .unwrap_or_else(|error| fail(&format!("could not write `{}`: {}", output, error)));
+
+    let world = scene.build_world().unwrap_or_else(|error| fail(&error));
+    let mut camera = scene
+        .build_camera(arguments.scale)
+        .unwrap_or_else(|error| fail(&error));
+    if arguments.ambient_occlusion {
+        let occlusion = scene.settings().ambient_occlusion.unwrap_or_default();
+        camera.set_integrator(world::Integrator::AmbientOcclusion(occlusion.build()));
+    }
+    if let Some(region) = arguments.region {
+        let width = scene.settings().width * arguments.scale;
+        let height = scene.settings().height * arguments.scale;
+        if region.x + region.width > width || region.y + region.height > height {
+            fail(&format!(
+                "region {},{},{},{} is not inside the {}x{} image",
+                region.x, region.y, region.width, region.height, width, height
+            ));
+        }
+        camera.set_region(Some(region));
+    }
+
+    // Cuts an image down to the region, unless the full frame was asked
+    // for.
+    let framed = |image: canvas::Canvas| {
+        return match arguments.region {
+            Some(region) if !arguments.full_frame => {
+                image.crop(region.x, region.y, region.width, region.height)
+            }
+            _ => image,
+        };
+    };
+    let render_view = |camera: &camera::Camera| {
+        let image = camera
+            .render_observed(&world, arguments.threads, print_progress)
+            .canvas;
+        println!();
+        return framed(image);
+    };
+    let canvas = match arguments.stereo {
+        Some(layout) => {
+            let convergence = match arguments.toe_in {
+                Some(distance) => stereo::Convergence::ToeIn { distance },
+                None => stereo::Convergence::Parallel,
+            };
+            let rig = stereo::StereoRig::new(camera, arguments.interocular, convergence);
+            stereo::compose(
+                &render_view(&rig.left_eye()),
+                &render_view(&rig.right_eye()),
+                layout,
+            )
+        }
+        None if progressive => {
+            let start = Instant::now();
+            let mut render = camera.progressive(&world);
+            loop {
+                render.pass(arguments.threads);
+                let passes = render.passes();
+                print!(
+                    "\rpass {}, {}s elapsed   ",
+                    passes,
+                    start.elapsed().as_secs()
+                );
+                std::io::stdout().flush().unwrap();
+
+                let passes_done = arguments.passes.is_some_and(|limit| passes >= limit);
+                let time_up = arguments
+                    .time
+                    .is_some_and(|budget| start.elapsed() >= budget);
+                if passes_done || time_up {
+                    break;
+                }
+                if arguments
+                    .snapshot_every
+                    .is_some_and(|every| passes % every == 0)
+                {
+                    save(&framed(render.snapshot()), &snapshot_path(&output, passes));
+                }
+            }
+            println!();
+            framed(render.snapshot())
+        }
+        None => render_view(&camera),
+    };
+
+    save(&canvas, &output);
     println!("Wrote `{}`", output);
 }
@@ -101,6 +101,36 @@ impl BoundingBox {
         );
     }
 
+    // The point halfway between the extents.
+    pub fn centroid(&self) -> tuple::Point {
+        return tuple::Point::new(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0,
+        );
+    }
+
+    // The area of the box's six faces. A ray passing at random through a
+    // box inside another hits the inner one with a chance in proportion to
+    // their surface areas, which is what the SAH builder weighs splits by.
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let dx = self.max.x - self.min.x;
+        let dy = self.max.y - self.min.y;
+        let dz = self.max.z - self.min.z;
+        return 2.0 * (dx * dy + dy * dz + dz * dx);
+    }
+
+    // Whether every extent is a real number: false for the infinite box
+    // around a plane, and for the NaNs a transform makes of it.
+    pub fn is_finite(&self) -> bool {
+        return [self.min, self.max]
+            .iter()
+            .all(|corner| corner.x.is_finite() && corner.y.is_finite() && corner.z.is_finite());
+    }
+
     // Whether the box encloses no space at all: extents stay inverted
     // until a first point is added.
     pub fn is_empty(&self) -> bool {
@@ -111,10 +141,17 @@ impl BoundingBox {
     // generalized to arbitrary extents, except only the yes/no answer is
     // needed, not the `t` values.
     pub fn intersects(&self, ray: &ray::Ray) -> bool {
+        return self.entry(ray).is_some();
+    }
+
+    // The `t` at which the ray enters the box, or `None` if it misses.
+    // A ray that starts inside the box entered it behind its origin, so
+    // the `t` is negative.
+    pub fn entry(&self, ray: &ray::Ray) -> Option<f64> {
         // An empty box's inverted extents would be swapped by `check_axis`
         // into an infinite box that accepts every ray.
         if self.is_empty() {
-            return false;
+            return None;
         }
 
         let (xtmin, xtmax) = check_axis(ray.origin.x, ray.direction.x, self.min.x, self.max.x);
@@ -124,7 +161,10 @@ impl BoundingBox {
         let tmin = xtmin.max(ytmin).max(ztmin);
         let tmax = xtmax.min(ytmax).min(ztmax);
 
-        return tmin <= tmax;
+        if tmin > tmax {
+            return None;
+        }
+        return Some(tmin);
     }
 }
 
@@ -411,6 +451,60 @@ mod bounds_tests {
         assert!(!bbox.intersects(&ray));
     }
 
+    #[test]
+    fn test_the_centroid_and_surface_area_of_a_bounding_box() {
+        let bbox = bounds::BoundingBox::new(
+            tuple::Point::new(-1.0, 0.0, 2.0),
+            tuple::Point::new(1.0, 3.0, 6.0),
+        );
+
+        assert_eq!(bbox.centroid(), tuple::Point::new(0.0, 1.5, 4.0));
+        assert_eq!(
+            bbox.surface_area(),
+            2.0 * (2.0 * 3.0 + 3.0 * 4.0 + 4.0 * 2.0)
+        );
+        assert_eq!(bounds::BoundingBox::empty().surface_area(), 0.0);
+    }
+
+    #[test]
+    fn test_only_a_box_with_real_extents_is_finite() {
+        let cube = bounds::BoundingBox::new(
+            tuple::Point::new(-1.0, -1.0, -1.0),
+            tuple::Point::new(1.0, 1.0, 1.0),
+        );
+        let plane = bounds::BoundingBox::new(
+            tuple::Point::new(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            tuple::Point::new(f64::INFINITY, 0.0, f64::INFINITY),
+        );
+        let tilted_plane = plane.transform(&matrix::Matrix4::IDENTITY.rotation_x(0.5));
+
+        assert!(cube.is_finite());
+        assert!(!plane.is_finite());
+        assert!(!tilted_plane.is_finite());
+    }
+
+    #[test]
+    fn test_the_entry_point_of_a_ray_into_a_bounding_box() {
+        let bbox = bounds::BoundingBox::new(
+            tuple::Point::new(-1.0, -1.0, -1.0),
+            tuple::Point::new(1.0, 1.0, 1.0),
+        );
+
+        let examples = [
+            ((0.0, 0.0, -5.0), (0.0, 0.0, 1.0), Some(4.0)),
+            ((0.0, 0.0, -5.0), (0.0, 0.0, 2.0), Some(2.0)),
+            ((0.0, 0.0, 0.0), (0.0, 0.0, 1.0), Some(-1.0)),
+            ((0.0, 2.0, -5.0), (0.0, 0.0, 1.0), None),
+        ];
+        for ((ox, oy, oz), (dx, dy, dz), expected) in examples {
+            let ray = ray::ray(
+                tuple::Point::new(ox, oy, oz),
+                tuple::Vector::new(dx, dy, dz),
+            );
+            assert_eq!(bbox.entry(&ray), expected);
+        }
+    }
+
     #[test]
     fn test_transforming_an_empty_box_leaves_it_empty() {
         let bbox = bounds::BoundingBox::empty();
@@ -0,0 +1,1120 @@
+// A bounding volume hierarchy compiled from a world's shapes, for tracing
+// rays through it faster than walking the `Shape` tree. The tree stays the
+// way scenes are put together; `World` compiles it into this before the
+// first ray is traced, and again after its shapes change.
+//
+// Every primitive is pulled out of the groups it sits in and carries the
+// composed transform of those with it. Triangles, a mesh's faces included,
+// go into world space and straight into the leaves, so testing one needs
+// neither its `Shape` nor a ray transform. Other primitives (CSG shapes
+// among them, whole) are tested through their `Shape`, reached by the path
+// of child indices that leads to it. Anything without finite bounds, like a
+// plane, is tested against every ray instead of going into the hierarchy.
+//
+// Instances make a second level. Each prototype they share is compiled once
+// into a hierarchy of its own, in the space of the instances placing it,
+// and each instance is a leaf carrying its composed transform: a ray that
+// reaches one is carried into that space and traced through the
+// prototype's hierarchy. However many instances there are, the
+// prototype's triangles are stored once.
+//
+// The nodes sit in one array in depth-first order: an interior node's first
+// child follows it directly and it records where its second starts, so a
+// traversal is a loop over indices with a small stack rather than a
+// recursion through nested groups. A mesh organizes its faces with the
+// same `build_hierarchy` and `traverse`.
+
+use crate::bounds;
+use crate::intersection;
+use crate::matrix;
+use crate::matrix::Transpose;
+use crate::ray;
+use crate::shape;
+use crate::tuple;
+
+// How many slices along each axis the SAH builder sorts a group's children
+// into by the centroids of their boxes; splits are only tried between
+// slices, rather than between every pair of neighbouring children.
+const SAH_BINS: usize = 12;
+
+// The cost of testing a ray against the two boxes a split adds, in units
+// of the cost of testing it against one child.
+const SAH_TRAVERSAL_COST: f64 = 1.0;
+
+// Leaves of the compiled hierarchy hold at most this many primitives
+// unless they can't be split.
+const MAX_LEAF_SIZE: usize = 4;
+
+// The deepest a compiled hierarchy goes, which bounds the traversal stack.
+// Anything still unsplit at this depth becomes a leaf, however large.
+const MAX_DEPTH: usize = 64;
+
+// Where the SAH builder splits a group: between bins `bin - 1` and `bin`
+// of `SAH_BINS` even slices along `axis` (0, 1 or 2 for x, y or z) of
+// the box around the children's centroids.
+pub(crate) struct SahSplit {
+    axis: usize,
+    min: f64,
+    extent: f64,
+    bin: usize,
+}
+
+impl SahSplit {
+    pub(crate) fn goes_left(&self, bbox: &bounds::BoundingBox) -> bool {
+        let centroid = bbox.centroid();
+        return sah_bin(coordinate(&centroid, self.axis), self.min, self.extent) < self.bin;
+    }
+}
+
+fn coordinate(point: &tuple::Point, axis: usize) -> f64 {
+    match axis {
+        0 => point.x,
+        1 => point.y,
+        _ => point.z,
+    }
+}
+
+fn sah_bin(value: f64, min: f64, extent: f64) -> usize {
+    let bin = ((value - min) / extent * SAH_BINS as f64) as usize;
+    return bin.min(SAH_BINS - 1);
+}
+
+// The cheapest split of the children with bounds `boxes` by the surface
+// area heuristic, trying each boundary between bins along each axis, or
+// `None` if keeping them together is cheaper (and allowed) or they can't
+// be told apart.
+pub(crate) fn sah_split(boxes: &[bounds::BoundingBox], max_leaf_size: usize) -> Option<SahSplit> {
+    if boxes.len() < 2 {
+        return None;
+    }
+    let mut whole = bounds::BoundingBox::empty();
+    let mut centroids = bounds::BoundingBox::empty();
+    for bbox in boxes.iter() {
+        whole.add_box(bbox);
+        centroids.add_point(bbox.centroid());
+    }
+
+    let mut best: Option<SahSplit> = None;
+    let mut best_cost = f64::INFINITY;
+    for axis in 0..3 {
+        let min = coordinate(&centroids.min, axis);
+        let extent = coordinate(&centroids.max, axis) - min;
+        // Every centroid is level along this axis, so no split separates
+        // them.
+        if !(extent > 0.0 && extent.is_finite()) {
+            continue;
+        }
+
+        let mut counts = [0; SAH_BINS];
+        let mut bin_boxes = [bounds::BoundingBox::empty(); SAH_BINS];
+        for bbox in boxes.iter() {
+            let bin = sah_bin(coordinate(&bbox.centroid(), axis), min, extent);
+            counts[bin] += 1;
+            bin_boxes[bin].add_box(bbox);
+        }
+
+        for bin in 1..SAH_BINS {
+            let (mut left, mut right) =
+                (bounds::BoundingBox::empty(), bounds::BoundingBox::empty());
+            for (other, (bbox, count)) in bin_boxes.iter().zip(counts).enumerate() {
+                // An empty bin's box is inside out, and merging it in
+                // would stretch the side's box to infinity.
+                if count == 0 {
+                    continue;
+                }
+                if other < bin {
+                    left.add_box(bbox);
+                } else {
+                    right.add_box(bbox);
+                }
+            }
+            let left_count: usize = counts[..bin].iter().sum();
+            let right_count: usize = counts[bin..].iter().sum();
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+
+            let cost = SAH_TRAVERSAL_COST
+                + (left.surface_area() * left_count as f64
+                    + right.surface_area() * right_count as f64)
+                    / whole.surface_area();
+            // Infinite boxes (planes) make for NaN costs, which never win.
+            if cost < best_cost {
+                best_cost = cost;
+                best = Some(SahSplit {
+                    axis,
+                    min,
+                    extent,
+                    bin,
+                });
+            }
+        }
+    }
+
+    // Testing every child costs one unit each.
+    if best_cost >= boxes.len() as f64 && boxes.len() <= max_leaf_size {
+        return None;
+    }
+    return best;
+}
+
+// A node of a flattened hierarchy, as `build_hierarchy` lays them out.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Node {
+    pub(crate) bounds: bounds::BoundingBox,
+    pub(crate) kind: NodeKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum NodeKind {
+    // The first child follows this node; the second is at `second_child`.
+    // The children were split along `axis`, so a ray heading down it
+    // reaches the second child's side first.
+    Interior { second_child: u32, axis: usize },
+    // `count` items from `first` on, in the order `build_hierarchy` puts
+    // them in.
+    Leaf { first: u32, count: u32 },
+}
+
+// Builds a flattened hierarchy over items with bounds `boxes`, split by
+// the surface area heuristic into leaves of at most `max_leaf_size` items
+// wherever that pays. Returns the nodes, depth first, and the items'
+// indices in the order the leaves hold them.
+pub(crate) fn build_hierarchy(
+    boxes: &[bounds::BoundingBox],
+    max_leaf_size: usize,
+) -> (Vec<Node>, Vec<usize>) {
+    let mut nodes = vec![];
+    let mut order = Vec::with_capacity(boxes.len());
+    if !boxes.is_empty() {
+        let items = (0..boxes.len()).collect();
+        build_node(boxes, items, max_leaf_size, 1, &mut nodes, &mut order);
+    }
+    return (nodes, order);
+}
+
+// Appends the subtree over `items` to `nodes`, depth first.
+fn build_node(
+    boxes: &[bounds::BoundingBox],
+    items: Vec<usize>,
+    max_leaf_size: usize,
+    depth: usize,
+    nodes: &mut Vec<Node>,
+    order: &mut Vec<usize>,
+) {
+    let item_boxes: Vec<bounds::BoundingBox> = items.iter().map(|&item| boxes[item]).collect();
+    let mut bounds = bounds::BoundingBox::empty();
+    for bbox in item_boxes.iter() {
+        bounds.add_box(bbox);
+    }
+    let split = if depth < MAX_DEPTH {
+        sah_split(&item_boxes, max_leaf_size)
+    } else {
+        None
+    };
+
+    let Some(split) = split else {
+        nodes.push(Node {
+            bounds,
+            kind: NodeKind::Leaf {
+                first: order.len() as u32,
+                count: items.len() as u32,
+            },
+        });
+        order.extend(items);
+        return;
+    };
+
+    let index = nodes.len();
+    nodes.push(Node {
+        bounds,
+        kind: NodeKind::Interior {
+            second_child: 0,
+            axis: split.axis,
+        },
+    });
+    let (left, right): (Vec<usize>, Vec<usize>) = items
+        .into_iter()
+        .partition(|&item| split.goes_left(&boxes[item]));
+    build_node(boxes, left, max_leaf_size, depth + 1, nodes, order);
+    let second_child = nodes.len() as u32;
+    build_node(boxes, right, max_leaf_size, depth + 1, nodes, order);
+    nodes[index].kind = NodeKind::Interior {
+        second_child,
+        axis: split.axis,
+    };
+}
+
+// Walks a hierarchy depth first, calling `visit` on each node it reaches
+// and descending into an interior node's children only if `visit` says
+// to. The child on the side `ray` comes from goes first, so the nearest
+// hits tend to turn up early and prune the rest.
+pub(crate) fn traverse(nodes: &[Node], ray: &ray::Ray, mut visit: impl FnMut(&Node) -> bool) {
+    if nodes.is_empty() {
+        return;
+    }
+    // Each interior node above the current one defers at most one child,
+    // so the stack never holds more than the tree is deep.
+    let mut stack = [0u32; MAX_DEPTH];
+    let mut pending = 0;
+    let mut index = 0;
+    loop {
+        let node = &nodes[index];
+        if visit(node)
+            && let NodeKind::Interior { second_child, axis } = node.kind
+        {
+            let heading_back = match axis {
+                0 => ray.direction.x < 0.0,
+                1 => ray.direction.y < 0.0,
+                _ => ray.direction.z < 0.0,
+            };
+            let (near, far) = if heading_back {
+                (second_child as usize, index + 1)
+            } else {
+                (index + 1, second_child as usize)
+            };
+            stack[pending] = far as u32;
+            pending += 1;
+            index = near;
+            continue;
+        }
+        if pending == 0 {
+            return;
+        }
+        pending -= 1;
+        index = stack[pending] as usize;
+    }
+}
+
+// A primitive pulled out of the shape tree.
+struct Primitive {
+    // Indices from `World::shapes` down through group children (and
+    // instance prototypes) to the primitive.
+    path: Vec<usize>,
+    // The composed transform of the groups enclosing the primitive, its
+    // inverse and the inverse's transpose: the identity for a shape at the
+    // top of the world. An instance in the hierarchy carries its own
+    // transform in these too, taking its prototype's hits to world space.
+    parent_transform: matrix::Matrix4,
+    parent_inverse: matrix::Matrix4,
+    parent_inverse_transpose: matrix::Matrix4,
+    // How far along `path` the outermost instance that overrides the
+    // primitive's material is, if any is.
+    material_from: Option<usize>,
+}
+
+impl Primitive {
+    fn shape<'a>(&self, shapes: &'a [shape::Shape]) -> &'a shape::Shape {
+        return self.ancestor(shapes, self.path.len() - 1);
+    }
+
+    // An instance primitive's prototype, as the one-shape list its
+    // hierarchy was compiled from.
+    fn prototypes<'a>(&self, shapes: &'a [shape::Shape]) -> &'a [shape::Shape] {
+        return self
+            .shape(shapes)
+            .children()
+            .expect("an instance primitive is an instance");
+    }
+
+    // The shape `depth` steps down the path, the primitive's outermost
+    // group at 0.
+    fn ancestor<'a>(&self, shapes: &'a [shape::Shape], depth: usize) -> &'a shape::Shape {
+        let mut shape = &shapes[self.path[0]];
+        for &index in self.path[1..=depth].iter() {
+            shape = &shape
+                .children()
+                .expect("a primitive's path runs through groups")[index];
+        }
+        return shape;
+    }
+
+    // Carries a hit from the primitive's parent space to world space, as
+    // the groups and instances above it would on its way up the tree.
+    fn to_world<'a>(
+        &self,
+        shapes: &'a [shape::Shape],
+        hit: &intersection::Intersection<'a>,
+    ) -> intersection::Intersection<'a> {
+        let material = match self.material_from {
+            Some(depth) => self
+                .ancestor(shapes, depth)
+                .material_override()
+                .expect("the path leads through an instance with a material"),
+            None => hit.material,
+        };
+        return intersection::Intersection {
+            material,
+            world_transform: self.parent_transform * hit.world_transform,
+            world_inverse: hit.world_inverse * self.parent_inverse,
+            world_inverse_transpose: self.parent_inverse_transpose * hit.world_inverse_transpose,
+            ..*hit
+        };
+    }
+}
+
+// A flat or smooth triangle, or one face of a mesh, in world space.
+struct Triangle {
+    p1: tuple::Point,
+    e1: tuple::Vector,
+    e2: tuple::Vector,
+    // How much the triangle's transforms scale volumes by, which keeps its
+    // test for parallel rays the same as in object space.
+    det_scale: f64,
+    primitive: u32,
+    // Which of a mesh's faces this is; zero for a lone triangle.
+    face: u32,
+}
+
+// What a leaf of the compiled hierarchy holds.
+enum Entry {
+    Triangle(Triangle),
+    // A bounded primitive other than a triangle, by index into
+    // `Bvh::primitives`.
+    Shape(u32),
+    // A bounded instance, by index into `Bvh::primitives`, and the
+    // hierarchy compiled for its prototype, by index into
+    // `Bvh::prototypes`.
+    Instance { primitive: u32, prototype: u32 },
+}
+
+// The nearest hit found so far by a traversal. Triangle hits only become
+// intersections once the search is over, as finding their `Shape` and
+// composing their transforms is wasted on the hits later ones beat.
+// There's only ever one of these, on the stack, so the size of the
+// `Shape` variant costs nothing worth boxing it to save.
+#[allow(clippy::large_enum_variant)]
+enum Hit<'a> {
+    Triangle {
+        entry: usize,
+        t: f64,
+        u: f64,
+        v: f64,
+    },
+    Shape(intersection::Intersection<'a>),
+}
+
+impl Hit<'_> {
+    fn t(&self) -> f64 {
+        match self {
+            Hit::Triangle { t, .. } => *t,
+            Hit::Shape(intersection) => intersection.t,
+        }
+    }
+}
+
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // What the leaves hold, in their order.
+    entries: Vec<Entry>,
+    // Primitives without finite bounds, by index into `primitives`.
+    unbounded: Vec<u32>,
+    primitives: Vec<Primitive>,
+    // A hierarchy for each prototype the instances share, with the
+    // prototype's address, which tells instances of the same one apart
+    // from instances of an equal copy.
+    prototypes: Vec<(usize, Bvh)>,
+}
+
+impl Bvh {
+    /// Compiles `shapes`, splitting the primitives in them by the surface
+    /// area heuristic regardless of how the groups they came in were
+    /// organized. A mesh's faces are split up like any other triangles.
+    pub fn build(shapes: &[shape::Shape]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: vec![],
+            entries: vec![],
+            unbounded: vec![],
+            primitives: vec![],
+            prototypes: vec![],
+        };
+        let mut items = vec![];
+        for (index, shape) in shapes.iter().enumerate() {
+            bvh.flatten(
+                shape,
+                vec![index],
+                matrix::Matrix4::IDENTITY,
+                matrix::Matrix4::IDENTITY,
+                None,
+                &mut items,
+            );
+        }
+
+        let boxes: Vec<bounds::BoundingBox> = items.iter().map(|(bbox, _)| *bbox).collect();
+        let (nodes, order) = build_hierarchy(&boxes, MAX_LEAF_SIZE);
+        let mut entries: Vec<Option<Entry>> =
+            items.into_iter().map(|(_, entry)| Some(entry)).collect();
+        bvh.nodes = nodes;
+        bvh.entries = order
+            .into_iter()
+            .map(|item| entries[item].take().unwrap())
+            .collect();
+        return bvh;
+    }
+
+    /// The number of nodes in the hierarchy, leaves included, not counting
+    /// the hierarchies of instances' prototypes.
+    pub fn node_count(&self) -> usize {
+        return self.nodes.len();
+    }
+
+    /// The number of triangles stored, in this hierarchy and its
+    /// prototypes': each prototype's once, however many instances place it.
+    pub fn triangle_count(&self) -> usize {
+        let own = self
+            .entries
+            .iter()
+            .filter(|entry| matches!(entry, Entry::Triangle(_)))
+            .count();
+        return own
+            + self
+                .prototypes
+                .iter()
+                .map(|(_, prototype)| prototype.triangle_count())
+                .sum::<usize>();
+    }
+
+    fn flatten(
+        &mut self,
+        shape: &shape::Shape,
+        path: Vec<usize>,
+        parent_transform: matrix::Matrix4,
+        parent_inverse: matrix::Matrix4,
+        material_from: Option<usize>,
+        items: &mut Vec<(bounds::BoundingBox, Entry)>,
+    ) {
+        let material_from = material_from.or(shape.material_override().map(|_| path.len() - 1));
+        if let Some(prototype) = shape.prototype() {
+            let bounds = shape.parent_space_bounds().transform(&parent_transform);
+            // A bounded instance becomes a leaf tracing into its prototype's
+            // hierarchy; an unbounded one is tested through its `Shape`
+            // below, as any other unbounded primitive is.
+            if bounds.is_finite() {
+                let primitive = self.primitives.len() as u32;
+                let transform = parent_transform * *shape.transformation_matrix();
+                let inverse = *shape.inverse_transformation_matrix() * parent_inverse;
+                self.primitives.push(Primitive {
+                    path,
+                    parent_transform: transform,
+                    parent_inverse: inverse,
+                    parent_inverse_transpose: inverse.transpose(),
+                    material_from,
+                });
+                let prototype = self.prototype_index(prototype);
+                items.push((
+                    bounds,
+                    Entry::Instance {
+                        primitive,
+                        prototype,
+                    },
+                ));
+                return;
+            }
+        } else if let Some(children) = shape.children() {
+            let transform = parent_transform * *shape.transformation_matrix();
+            let inverse = *shape.inverse_transformation_matrix() * parent_inverse;
+            for (index, child) in children.iter().enumerate() {
+                let mut child_path = path.clone();
+                child_path.push(index);
+                self.flatten(child, child_path, transform, inverse, material_from, items);
+            }
+            return;
+        }
+
+        let primitive = self.primitives.len() as u32;
+        self.primitives.push(Primitive {
+            path,
+            parent_transform,
+            parent_inverse,
+            parent_inverse_transpose: parent_inverse.transpose(),
+            material_from,
+        });
+
+        let bounds = shape.parent_space_bounds().transform(&parent_transform);
+        if !bounds.is_finite() {
+            self.unbounded.push(primitive);
+            return;
+        }
+        let to_world = parent_transform * *shape.transformation_matrix();
+        let triangle = |(p1, e1, e2): (tuple::Point, tuple::Vector, tuple::Vector), face| {
+            let triangle = Triangle {
+                p1: to_world * p1,
+                e1: to_world * e1,
+                e2: to_world * e2,
+                det_scale: volume_scale(&to_world),
+                primitive,
+                face,
+            };
+            let mut bounds = bounds::BoundingBox::empty();
+            bounds.add_point(triangle.p1);
+            bounds.add_point(triangle.p1 + triangle.e1);
+            bounds.add_point(triangle.p1 + triangle.e2);
+            return (bounds, Entry::Triangle(triangle));
+        };
+        if let Some(mesh) = shape.as_mesh() {
+            for face in 0..mesh.face_count() {
+                items.push(triangle(mesh.edges(face), face as u32));
+            }
+        } else if let Some(edges) = shape.triangle_edges() {
+            items.push(triangle(edges, 0));
+        } else {
+            items.push((bounds, Entry::Shape(primitive)));
+        }
+    }
+
+    // Where the hierarchy for `prototype` is in `prototypes`, compiling it
+    // the first time an instance of it turns up.
+    fn prototype_index(&mut self, prototype: &shape::Shape) -> u32 {
+        let address = prototype as *const shape::Shape as usize;
+        if let Some(index) = self.prototypes.iter().position(|(a, _)| *a == address) {
+            return index as u32;
+        }
+        self.prototypes
+            .push((address, Bvh::build(std::slice::from_ref(prototype))));
+        return (self.prototypes.len() - 1) as u32;
+    }
+
+    /// The first thing `ray` hits in front of its origin among `shapes`,
+    /// which must be the shapes this was built from, unchanged since.
+    pub fn closest_hit<'a>(
+        &self,
+        shapes: &'a [shape::Shape],
+        ray: &ray::Ray,
+    ) -> Option<intersection::Intersection<'a>> {
+        let mut closest: Option<Hit<'a>> = None;
+        for &primitive in self.unbounded.iter() {
+            self.intersect_shape(shapes, primitive, ray, &mut closest);
+        }
+
+        traverse(&self.nodes, ray, |node| {
+            // A ray only enters the box beyond the closest hit so far, so
+            // nothing inside can beat it.
+            match node.bounds.entry(ray) {
+                Some(entry) if closest.as_ref().is_none_or(|hit| entry < hit.t()) => {}
+                _ => return false,
+            }
+            let NodeKind::Leaf { first, count } = node.kind else {
+                return true;
+            };
+            for entry in first as usize..(first + count) as usize {
+                match &self.entries[entry] {
+                    Entry::Triangle(triangle) => {
+                        let Some((t, u, v)) = intersect(triangle, ray) else {
+                            continue;
+                        };
+                        let nearer = closest.as_ref().is_none_or(|hit| t < hit.t());
+                        if t.is_sign_positive() && nearer {
+                            closest = Some(Hit::Triangle { entry, t, u, v });
+                        }
+                    }
+                    Entry::Shape(primitive) => {
+                        self.intersect_shape(shapes, *primitive, ray, &mut closest)
+                    }
+                    Entry::Instance {
+                        primitive,
+                        prototype,
+                    } => self.intersect_instance(shapes, *primitive, *prototype, ray, &mut closest),
+                }
+            }
+            return true;
+        });
+
+        return closest.map(|hit| match hit {
+            Hit::Triangle { entry, t, u, v } => match &self.entries[entry] {
+                Entry::Triangle(triangle) => self.triangle_hit(shapes, triangle, t, u, v),
+                _ => unreachable!("triangle hits come from triangle entries"),
+            },
+            Hit::Shape(intersection) => intersection,
+        });
+    }
+
+    /// Whether `ray` hits anything among `shapes` in front of its origin,
+    /// closer than `max_t`, that `blocks` says stops it, as
+    /// `Shape::occludes` answers it for the tree.
+    pub fn occluded<'a>(
+        &self,
+        shapes: &'a [shape::Shape],
+        ray: &ray::Ray,
+        max_t: f64,
+        blocks: &mut dyn FnMut(&intersection::Intersection<'a>) -> bool,
+    ) -> bool {
+        let mut blocked = self
+            .unbounded
+            .iter()
+            .any(|&primitive| self.shape_occludes(shapes, primitive, ray, max_t, blocks));
+
+        traverse(&self.nodes, ray, |node| {
+            if blocked || !node.bounds.intersects(ray) {
+                return false;
+            }
+            let NodeKind::Leaf { first, count } = node.kind else {
+                return true;
+            };
+            let entries = &self.entries[first as usize..(first + count) as usize];
+            blocked = entries.iter().any(|entry| match entry {
+                Entry::Triangle(triangle) => intersect(triangle, ray).is_some_and(|(t, u, v)| {
+                    t.is_sign_positive()
+                        && t < max_t
+                        && blocks(&self.triangle_hit(shapes, triangle, t, u, v))
+                }),
+                Entry::Shape(primitive) => {
+                    self.shape_occludes(shapes, *primitive, ray, max_t, blocks)
+                }
+                Entry::Instance {
+                    primitive,
+                    prototype,
+                } => self.instance_occludes(shapes, *primitive, *prototype, ray, max_t, blocks),
+            });
+            return !blocked;
+        });
+        return blocked;
+    }
+
+    fn triangle_hit<'a>(
+        &self,
+        shapes: &'a [shape::Shape],
+        triangle: &Triangle,
+        t: f64,
+        u: f64,
+        v: f64,
+    ) -> intersection::Intersection<'a> {
+        let primitive = &self.primitives[triangle.primitive as usize];
+        let object = primitive.shape(shapes);
+        let hit = intersection::face_intersection(t, object, triangle.face as usize, u, v);
+        return primitive.to_world(shapes, &hit);
+    }
+
+    fn intersect_shape<'a>(
+        &self,
+        shapes: &'a [shape::Shape],
+        primitive: u32,
+        ray: &ray::Ray,
+        closest: &mut Option<Hit<'a>>,
+    ) {
+        let primitive = &self.primitives[primitive as usize];
+        let parent_ray = ray.transform(&primitive.parent_inverse);
+        for intersection in primitive.shape(shapes).intersect(&parent_ray) {
+            let nearer = closest.as_ref().is_none_or(|hit| intersection.t < hit.t());
+            if intersection.t.is_sign_positive() && nearer {
+                *closest = Some(Hit::Shape(primitive.to_world(shapes, &intersection)));
+            }
+        }
+    }
+
+    // The closest hit on an instance's prototype, traced through the
+    // prototype's hierarchy in the instance's space and carried back out.
+    fn intersect_instance<'a>(
+        &self,
+        shapes: &'a [shape::Shape],
+        primitive: u32,
+        prototype: u32,
+        ray: &ray::Ray,
+        closest: &mut Option<Hit<'a>>,
+    ) {
+        let primitive = &self.primitives[primitive as usize];
+        let instance_ray = ray.transform(&primitive.parent_inverse);
+        let prototypes = primitive.prototypes(shapes);
+        let (_, hierarchy) = &self.prototypes[prototype as usize];
+        if let Some(intersection) = hierarchy.closest_hit(prototypes, &instance_ray) {
+            if closest.as_ref().is_none_or(|hit| intersection.t < hit.t()) {
+                *closest = Some(Hit::Shape(primitive.to_world(shapes, &intersection)));
+            }
+        }
+    }
+
+    fn instance_occludes<'a>(
+        &self,
+        shapes: &'a [shape::Shape],
+        primitive: u32,
+        prototype: u32,
+        ray: &ray::Ray,
+        max_t: f64,
+        blocks: &mut dyn FnMut(&intersection::Intersection<'a>) -> bool,
+    ) -> bool {
+        let primitive = &self.primitives[primitive as usize];
+        let instance_ray = ray.transform(&primitive.parent_inverse);
+        let prototypes = primitive.prototypes(shapes);
+        let (_, hierarchy) = &self.prototypes[prototype as usize];
+        return hierarchy.occluded(prototypes, &instance_ray, max_t, &mut |intersection| {
+            return blocks(&primitive.to_world(shapes, intersection));
+        });
+    }
+
+    fn shape_occludes<'a>(
+        &self,
+        shapes: &'a [shape::Shape],
+        primitive: u32,
+        ray: &ray::Ray,
+        max_t: f64,
+        blocks: &mut dyn FnMut(&intersection::Intersection<'a>) -> bool,
+    ) -> bool {
+        let primitive = &self.primitives[primitive as usize];
+        let parent_ray = ray.transform(&primitive.parent_inverse);
+        return primitive
+            .shape(shapes)
+            .occludes(&parent_ray, max_t, &mut |intersection| {
+                return blocks(&primitive.to_world(shapes, intersection));
+            });
+    }
+}
+
+fn intersect(triangle: &Triangle, ray: &ray::Ray) -> Option<(f64, f64, f64)> {
+    return shape::intersect_triangle(
+        ray,
+        &triangle.p1,
+        &triangle.e1,
+        &triangle.e2,
+        triangle.det_scale,
+    );
+}
+
+// How much `transform` scales volumes by: the determinant of its linear
+// part, as the triple product of where it takes the three axes.
+fn volume_scale(transform: &matrix::Matrix4) -> f64 {
+    let x = *transform * tuple::Vector::new(1.0, 0.0, 0.0);
+    let y = *transform * tuple::Vector::new(0.0, 1.0, 0.0);
+    let z = *transform * tuple::Vector::new(0.0, 0.0, 1.0);
+    return tuple::dot(&x, &tuple::cross(&y, &z)).abs();
+}
+
+#[cfg(test)]
+mod bvh_tests {
+    use std::sync::Arc;
+
+    use crate::bvh;
+    use crate::color;
+    use crate::intersection;
+    use crate::material;
+    use crate::matrix;
+    use crate::mesh;
+    use crate::ray;
+    use crate::shape;
+    use crate::transformation::Transform;
+    use crate::tuple;
+    use assert_approx_eq::assert_approx_eq;
+
+    // A tilted pyramid mesh whose sides alternate between smooth and flat
+    // faces.
+    fn pyramid() -> shape::Shape {
+        let apex = 4;
+        let faces = (0..4)
+            .map(|side| mesh::Face {
+                vertices: [side, (side + 1) % 4, apex],
+                normals: (side % 2 == 0).then_some([side, (side + 1) % 4, apex]),
+                uvs: None,
+            })
+            .collect();
+        let mut pyramid = shape::Shape::mesh(mesh::Mesh::new(
+            vec![
+                tuple::Point::new(-1.0, 0.0, -1.0),
+                tuple::Point::new(1.0, 0.0, -1.0),
+                tuple::Point::new(1.0, 0.0, 1.0),
+                tuple::Point::new(-1.0, 0.0, 1.0),
+                tuple::Point::new(0.0, 1.5, 0.0),
+            ],
+            vec![
+                tuple::Vector::new(-1.0, 0.5, -1.0),
+                tuple::Vector::new(1.0, 0.5, -1.0),
+                tuple::Vector::new(1.0, 0.5, 1.0),
+                tuple::Vector::new(-1.0, 0.5, 1.0),
+                tuple::Vector::new(0.0, 1.0, 0.0),
+            ],
+            vec![],
+            faces,
+        ));
+        pyramid.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .rotation_x(-0.4)
+                .scaling(1.0, 1.5, 1.0)
+                .translation(3.0, -1.0, 1.0),
+        );
+        return pyramid;
+    }
+
+    // A floor, a CSG lens, a transformed group of triangles, spheres and a
+    // nested rotated group, and a tilted mesh placed twice as instances,
+    // the second time with a material of its own, and once more inside a
+    // group that is itself instanced with another: everything the compiler
+    // has to flatten.
+    fn scene() -> Vec<shape::Shape> {
+        let mut floor = shape::Shape::default_plane();
+        floor.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, -2.0, 0.0));
+
+        let mut right = shape::Shape::default_sphere();
+        right.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.5, 0.0, 0.0));
+        let mut lens = shape::Shape::csg(
+            shape::CsgOperation::Intersection,
+            shape::Shape::default_sphere(),
+            right,
+        );
+        lens.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(-3.0, 0.0, 0.0));
+
+        let mut inner = shape::Shape::default_group();
+        inner.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .rotation_y(0.7)
+                .translation(1.0, 1.0, 0.0),
+        );
+        inner.add_child(shape::Shape::triangle(
+            tuple::Point::new(0.0, 1.0, 0.0),
+            tuple::Point::new(-1.0, 0.0, 0.0),
+            tuple::Point::new(1.0, 0.0, 0.0),
+        ));
+        inner.add_child(shape::Shape::smooth_triangle(
+            tuple::Point::new(0.0, -1.0, 0.0),
+            tuple::Point::new(1.0, 0.0, 0.0),
+            tuple::Point::new(-1.0, 0.0, 0.0),
+            tuple::Vector::new(0.0, 1.0, 0.0),
+            tuple::Vector::new(-1.0, 0.0, 0.0),
+            tuple::Vector::new(1.0, 0.0, 0.0),
+        ));
+
+        let mut outer = shape::Shape::default_group();
+        outer.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .scaling(0.5, 0.5, 0.5)
+                .translation(1.0, 0.0, 2.0),
+        );
+        for x in -2..=2 {
+            let mut sphere = shape::Shape::default_sphere();
+            sphere.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(
+                x as f64 * 2.5,
+                -2.0,
+                0.0,
+            ));
+            outer.add_child(sphere);
+        }
+        outer.add_child(inner);
+
+        let pyramid = Arc::new(pyramid());
+        let mut glass = material::glass();
+        glass.color = color::color(0.2, 0.4, 0.6);
+        let mut copy = shape::Shape::instance(pyramid.clone(), Some(glass));
+        copy.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .rotation_y(1.0)
+                .translation(-4.0, 0.5, 1.0),
+        );
+
+        let mut holder = shape::Shape::default_group();
+        let mut held = shape::Shape::instance(pyramid.clone(), None);
+        held.set_transformation_matrix(matrix::Matrix4::IDENTITY.rotation_z(0.3));
+        holder.add_child(held);
+        let mut red = material::material();
+        red.color = color::color(0.8, 0.1, 0.1);
+        let mut nested = shape::Shape::instance(Arc::new(holder), Some(red));
+        nested.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .scaling(0.6, 0.6, 0.6)
+                .translation(-1.5, 1.2, 1.0),
+        );
+
+        return vec![
+            floor,
+            lens,
+            outer,
+            shape::Shape::instance(pyramid, None),
+            copy,
+            nested,
+        ];
+    }
+
+    // A fan of rays from in front of the scene, some hitting each shape and
+    // some missing everything.
+    fn rays() -> Vec<ray::Ray> {
+        let mut rays = vec![];
+        for i in 0..30 {
+            for j in 0..30 {
+                let target = tuple::Point::new(-5.0 + i as f64 / 3.0, -3.0 + j as f64 / 5.0, 2.0);
+                let origin = tuple::Point::new(0.0, 0.5, -6.0);
+                rays.push(ray::ray(origin, tuple::normalize(&(target - origin))));
+            }
+        }
+        return rays;
+    }
+
+    fn tree_closest_hit<'a>(
+        shapes: &'a [shape::Shape],
+        ray: &ray::Ray,
+    ) -> Option<intersection::Intersection<'a>> {
+        let mut closest = None;
+        for shape in shapes.iter() {
+            shape.intersect_closest(ray, &mut closest);
+        }
+        return closest;
+    }
+
+    #[test]
+    fn test_the_compiled_hierarchy_finds_the_hits_the_shape_tree_does() {
+        let shapes = scene();
+        let bvh = bvh::Bvh::build(&shapes);
+
+        let (mut hits, mut overridden, mut nested) = (0, 0, 0);
+        for ray in rays().iter() {
+            let expected = tree_closest_hit(&shapes, ray);
+            let actual = bvh.closest_hit(&shapes, ray);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(expected), Some(actual)) => {
+                    hits += 1;
+                    if actual.material.transparency > 0.0 {
+                        overridden += 1;
+                    }
+                    if actual.material.color == color::color(0.8, 0.1, 0.1) {
+                        nested += 1;
+                    }
+                    assert!(std::ptr::eq(expected.object, actual.object));
+                    assert_eq!(expected.face, actual.face);
+                    assert!(std::ptr::eq(expected.material, actual.material));
+                    assert_approx_eq!(expected.t, actual.t, 1e-9);
+                    assert_approx_eq!(expected.u, actual.u, 1e-9);
+                    assert_approx_eq!(expected.v, actual.v, 1e-9);
+                    crate::assert_matrix_approx_eq!(
+                        expected.world_transform,
+                        actual.world_transform
+                    );
+                    crate::assert_matrix_approx_eq!(expected.world_inverse, actual.world_inverse);
+                    crate::assert_matrix_approx_eq!(
+                        expected.world_inverse_transpose,
+                        actual.world_inverse_transpose
+                    );
+                }
+                (expected, actual) => panic!("expected {:?}, found {:?}", expected, actual),
+            }
+        }
+        assert!(hits > 0);
+        assert!(overridden > 0);
+        assert!(nested > 0);
+    }
+
+    #[test]
+    fn test_the_compiled_hierarchy_finds_the_occluders_the_shape_tree_does() {
+        let shapes = scene();
+        let bvh = bvh::Bvh::build(&shapes);
+
+        for ray in rays().iter() {
+            for max_t in [2.0, 6.0, 10.0, f64::INFINITY] {
+                let expected = shapes
+                    .iter()
+                    .any(|shape| shape.occludes(ray, max_t, &mut |_| true));
+                assert_eq!(bvh.occluded(&shapes, ray, max_t, &mut |_| true), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_occlusion_asks_about_every_hit_until_one_blocks() {
+        let shapes = scene();
+        let bvh = bvh::Bvh::build(&shapes);
+        let ray = ray::ray(
+            tuple::Point::new(1.0, 10.0, 2.0),
+            tuple::Vector::new(0.0, -1.0, 0.0),
+        );
+
+        // The ray passes down through the middle sphere to the floor.
+        let mut asked = vec![];
+        let blocked = bvh.occluded(&shapes, &ray, f64::INFINITY, &mut |intersection| {
+            asked.push(intersection.t);
+            return false;
+        });
+
+        assert!(!blocked);
+        asked.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(asked.len(), 3);
+        assert_approx_eq!(asked[0], 10.5, 1e-9);
+        assert_approx_eq!(asked[1], 11.5, 1e-9);
+        assert_approx_eq!(asked[2], 12.0, 1e-9);
+    }
+
+    #[test]
+    fn test_a_plane_is_tested_outside_the_hierarchy() {
+        let shapes = vec![shape::Shape::default_plane()];
+        let bvh = bvh::Bvh::build(&shapes);
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 1.0, 0.0),
+            tuple::Vector::new(0.0, -1.0, 0.0),
+        );
+
+        assert_eq!(bvh.node_count(), 0);
+        assert_eq!(bvh.closest_hit(&shapes, &ray).unwrap().t, 1.0);
+    }
+
+    #[test]
+    fn test_nothing_is_hit_in_an_empty_world() {
+        let bvh = bvh::Bvh::build(&[]);
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+
+        assert!(bvh.closest_hit(&[], &ray).is_none());
+        assert!(!bvh.occluded(&[], &ray, f64::INFINITY, &mut |_| true));
+    }
+
+    #[test]
+    fn test_a_tiny_triangle_is_not_mistaken_for_a_parallel_one() {
+        // In world space this triangle's determinant is far below the
+        // threshold for a ray parallel to it, but in object space it's not.
+        let mut group = shape::Shape::default_group();
+        group.set_transformation_matrix(matrix::Matrix4::IDENTITY.scaling(0.001, 0.001, 0.001));
+        group.add_child(shape::Shape::triangle(
+            tuple::Point::new(0.0, 1.0, 0.0),
+            tuple::Point::new(-1.0, 0.0, 0.0),
+            tuple::Point::new(1.0, 0.0, 0.0),
+        ));
+        let shapes = vec![group];
+        let bvh = bvh::Bvh::build(&shapes);
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0005, -1.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+
+        assert!(tree_closest_hit(&shapes, &ray).is_some());
+        assert_approx_eq!(bvh.closest_hit(&shapes, &ray).unwrap().t, 1.0, 1e-9);
+    }
+
+    #[test]
+    fn test_instances_share_their_prototype_triangles() {
+        let pyramid = Arc::new(pyramid());
+        let instances = |count: usize| {
+            return (0..count)
+                .map(|i| {
+                    let mut instance = shape::Shape::instance(pyramid.clone(), None);
+                    instance.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(
+                        i as f64 * 3.0,
+                        0.0,
+                        0.0,
+                    ));
+                    return instance;
+                })
+                .collect::<Vec<_>>();
+        };
+
+        let one = bvh::Bvh::build(&instances(1));
+        let many = bvh::Bvh::build(&instances(50));
+
+        assert_eq!(one.triangle_count(), 4);
+        assert_eq!(many.triangle_count(), 4);
+    }
+
+    #[test]
+    fn test_primitives_are_split_across_leaves() {
+        let mut group = shape::Shape::default_group();
+        for x in 0..16 {
+            let mut sphere = shape::Shape::default_sphere();
+            sphere.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(
+                x as f64 * 3.0,
+                0.0,
+                0.0,
+            ));
+            group.add_child(sphere);
+        }
+        let shapes = vec![group];
+
+        let bvh = bvh::Bvh::build(&shapes);
+
+        // 16 spheres in leaves of at most 4 need at least 4 leaves under 3
+        // interior nodes.
+        assert!(bvh.node_count() >= 7);
+    }
+}
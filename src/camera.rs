@@ -1,17 +1,134 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
 use crate::canvas;
+use crate::color;
 use crate::matrix;
 use crate::matrix::Inverse;
 use crate::ray;
+use crate::sequences;
 use crate::tuple;
 use crate::world;
 
+// `render_parallel` hands out the image in square tiles this many pixels
+// across. Small enough that threads finish at about the same time, large
+// enough that claiming a tile costs nothing next to shading it.
+const TILE_SIZE: u32 = 16;
+
+/// Seed for the default lens sampling, so depth of field renders are
+/// reproducible.
+const LENS_JITTER_SEED: u64 = 0x1E45;
+
+/// An adaptively anti-aliased image, and what refining it cost.
+pub struct AdaptiveRender {
+    pub canvas: canvas::Canvas,
+    /// Pixels that differed enough from a neighbour to be refined.
+    pub refined_pixels: usize,
+    /// Rays fired beyond the first one through each pixel's center.
+    pub extra_rays: usize,
+}
+
+/// A rectangle of pixels, `width` by `height` with its top left corner at
+/// (`x`, `y`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How far along a render is, as reported to the observer passed to
+/// `render_observed`.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    /// Pixels shaded so far.
+    pub completed: usize,
+    /// Pixels the render will shade in all.
+    pub total: usize,
+    /// Time since the render started.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        return self.completed as f64 / self.total as f64;
+    }
+
+    /// How much longer the render should take, assuming the remaining
+    /// pixels shade as fast as the ones so far. `None` until some pixels
+    /// are done.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.completed == 0 {
+            return None;
+        }
+        let remaining = (self.total - self.completed) as f64 / self.completed as f64;
+        return Some(self.elapsed.mul_f64(remaining));
+    }
+}
+
+/// What an observer wants a render to do next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    Continue,
+    Cancel,
+}
+
+/// The result of `render_observed`.
+pub struct ObservedRender {
+    /// The image, black wherever a cancelled render didn't get to.
+    pub canvas: canvas::Canvas,
+    pub cancelled: bool,
+}
+
+// How pixels map to rays in camera space.
+#[derive(Clone, Copy)]
+enum Projection {
+    // Rays fan out from the camera's position.
+    Perspective,
+    // Rays run parallel, each from its own point on the image plane, so
+    // objects keep their size however far away they are.
+    Orthographic,
+    // Longitude across the image and latitude down it: every direction
+    // around the camera.
+    Equirectangular,
+    // Distance from the image center is proportional to the angle from
+    // the view direction, up to half the field of view at the edge of the
+    // image circle.
+    Fisheye { half_field_of_view: f64 },
+}
+
+#[derive(Clone)]
 pub struct Camera {
     hsize: u32,
     vsize: u32,
     half_width: f64,
     half_height: f64,
     pixel_size: f64,
-    pub transform: matrix::Matrix4,
+    projection: Projection,
+    transform: matrix::Matrix4,
+    // Kept in step with `transform` by `set_transformation_matrix`, so
+    // firing a ray doesn't invert it.
+    inverse: matrix::Matrix4,
+    // Supersampling fires `samples_per_side` squared rays per pixel, one
+    // through each cell of an even grid over the pixel, offset within its
+    // cell by the next two `jitter` values.
+    samples_per_side: u32,
+    jitter: sequences::Sequence,
+    // A thin lens of radius `aperture` (zero for a pinhole) focused on the
+    // plane `focal_distance` in front of the camera. Each ray leaves from
+    // a point on the lens picked by the next two `lens_jitter` values.
+    aperture: f64,
+    focal_distance: f64,
+    lens_jitter: sequences::Sequence,
+    // Only these pixels are shaded, if set; the rest of the image stays
+    // black.
+    region: Option<Region>,
+    integrator: world::Integrator,
 }
 
 impl Camera {
@@ -29,6 +146,71 @@ impl Camera {
             half_height = half_view;
         }
 
+        return Camera::with_projection(
+            hsize,
+            vsize,
+            half_width,
+            half_height,
+            Projection::Perspective,
+        );
+    }
+
+    /// A camera whose rays all run parallel to its view direction, for
+    /// diagrams free of perspective distortion. `view_width` is how wide
+    /// a slice of the world the image spans, in world units.
+    pub fn orthographic(hsize: u32, vsize: u32, view_width: f64) -> Camera {
+        let half_width = view_width / 2.0;
+        let half_height = half_width * f64::from(vsize) / f64::from(hsize);
+
+        return Camera::with_projection(
+            hsize,
+            vsize,
+            half_width,
+            half_height,
+            Projection::Orthographic,
+        );
+    }
+
+    /// A 360 degree panorama, for environment maps and VR previews. Columns
+    /// sweep all the way around the camera, with the view direction in the
+    /// middle, and rows run from straight up to straight down; a 2:1 image
+    /// has square pixels.
+    pub fn equirectangular(hsize: u32, vsize: u32) -> Camera {
+        let pi = std::f64::consts::PI;
+        return Camera::with_projection(hsize, vsize, pi, pi / 2.0, Projection::Equirectangular);
+    }
+
+    /// An equidistant fisheye with an image circle filling the shorter side
+    /// of the image: a pixel's distance from the center is proportional to
+    /// its ray's angle from the view direction. `field_of_view` may be up
+    /// to 2π, which sees every direction. Pixels outside the circle see
+    /// nothing and render black.
+    pub fn fisheye(hsize: u32, vsize: u32, field_of_view: f64) -> Camera {
+        assert!(
+            field_of_view > 0.0 && field_of_view <= 2.0 * std::f64::consts::PI,
+            "a fisheye's field of view must be more than 0 and at most 2π, got {}",
+            field_of_view
+        );
+        // Measure the image in radii of the image circle.
+        let aspect = f64::from(hsize) / f64::from(vsize);
+        let (half_width, half_height) = if aspect >= 1.0 {
+            (aspect, 1.0)
+        } else {
+            (1.0, 1.0 / aspect)
+        };
+        let projection = Projection::Fisheye {
+            half_field_of_view: field_of_view / 2.0,
+        };
+        return Camera::with_projection(hsize, vsize, half_width, half_height, projection);
+    }
+
+    fn with_projection(
+        hsize: u32,
+        vsize: u32,
+        half_width: f64,
+        half_height: f64,
+        projection: Projection,
+    ) -> Camera {
         let pixel_size = (half_width * 2.0) / f64::from(hsize);
 
         Camera {
@@ -37,46 +219,636 @@ impl Camera {
             half_width,
             half_height,
             pixel_size,
+            projection,
             transform: matrix::Matrix4::IDENTITY,
+            inverse: matrix::Matrix4::IDENTITY,
+            samples_per_side: 1,
+            jitter: sequences::Sequence::constant(0.5),
+            aperture: 0.0,
+            focal_distance: 1.0,
+            lens_jitter: sequences::Sequence::random(256, LENS_JITTER_SEED),
+            region: None,
+            integrator: world::Integrator::Whitted,
+        }
+    }
+
+    pub fn transformation_matrix(&self) -> &matrix::Matrix4 {
+        &self.transform
+    }
+
+    /// Points the camera, usually with a `view_transform`. Panics if
+    /// `new_transform` can't be inverted, as a `view_transform` looking
+    /// straight along `up` can't.
+    pub fn set_transformation_matrix(&mut self, new_transform: matrix::Matrix4) {
+        if let Err(error) = self.try_set_transformation_matrix(new_transform) {
+            panic!("{}", error);
         }
     }
 
-    pub fn ray_for_pixel(&self, x: u32, y: u32) -> ray::Ray {
-        // offset from the edge of the canvas to the pixel's center
-        let xoffset = ((x as f64) + 0.5) * self.pixel_size;
-        let yoffset = ((y as f64) + 0.5) * self.pixel_size;
+    /// `set_transformation_matrix` for transforms that come from outside
+    /// the program, like a scene file's: one that can't be inverted is an
+    /// error, and the camera keeps the transform it had.
+    pub fn try_set_transformation_matrix(
+        &mut self,
+        new_transform: matrix::Matrix4,
+    ) -> Result<(), String> {
+        self.inverse = new_transform.inverse().ok_or_else(|| {
+            String::from("a camera's transform must be invertible: is it looking along `up`?")
+        })?;
+        self.transform = new_transform;
+        return Ok(());
+    }
+
+    /// Averages `samples` rays per pixel instead of firing one through its
+    /// center. `samples` must be a perfect square (1, 4, 9, 16, ...) so the
+    /// rays can be spread over an even grid.
+    pub fn set_samples_per_pixel(&mut self, samples: u32) {
+        let samples_per_side = samples.isqrt();
+        assert!(
+            samples > 0 && samples_per_side * samples_per_side == samples,
+            "samples per pixel must be a perfect square, got {}",
+            samples
+        );
+        self.samples_per_side = samples_per_side;
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        return self.samples_per_side * self.samples_per_side;
+    }
+
+    /// Sets where within its grid cell each supersampling ray passes. The
+    /// default constant 0.5 samples cell centers (stratified sampling); a
+    /// `Sequence::random` jitters them. A single sample always goes through
+    /// the pixel center.
+    pub fn set_jitter(&mut self, jitter: sequences::Sequence) {
+        self.jitter = jitter;
+    }
+
+    /// Swaps the pinhole for a thin lens of radius `aperture`, focused on
+    /// the plane `focal_distance` in front of the camera: anything nearer
+    /// or farther blurs. Each ray samples one point on the lens, so raise
+    /// the samples per pixel to smooth the blur. An aperture of zero is a
+    /// pinhole again.
+    pub fn set_focus(&mut self, aperture: f64, focal_distance: f64) {
+        self.aperture = aperture;
+        self.focal_distance = focal_distance;
+    }
+
+    pub fn aperture(&self) -> f64 {
+        return self.aperture;
+    }
+
+    pub fn focal_distance(&self) -> f64 {
+        return self.focal_distance;
+    }
+
+    /// Sets how points on the lens are picked: two values per ray, for the
+    /// distance from the lens center and the angle around it.
+    pub fn set_lens_jitter(&mut self, jitter: sequences::Sequence) {
+        self.lens_jitter = jitter;
+    }
+
+    /// Sets how rays are turned into colors. `Integrator::Whitted`, the
+    /// default, is fast and noise free; `Integrator::path_traced()` adds
+    /// indirect light but needs many samples per pixel to smooth its noise.
+    pub fn set_integrator(&mut self, integrator: world::Integrator) {
+        self.integrator = integrator;
+    }
+
+    /// Restricts `render` and `render_parallel` to the pixels in `region`,
+    /// leaving the rest of the image black. Each pixel in the region gets
+    /// exactly the color a full render gives it, so cropping the result
+    /// to the region (see `Canvas::crop`) is a cheap way to look closely
+    /// at one part of a scene. `None` renders the whole image again.
+    pub fn set_region(&mut self, region: Option<Region>) {
+        if let Some(region) = region {
+            assert!(
+                region.width > 0
+                    && region.height > 0
+                    && region.x + region.width <= self.hsize
+                    && region.y + region.height <= self.vsize,
+                "region {:?} is not inside the {}x{} image",
+                region,
+                self.hsize,
+                self.vsize
+            );
+        }
+        self.region = region;
+    }
+
+    pub fn region(&self) -> Option<Region> {
+        return self.region;
+    }
+
+    /// The ray through the center of pixel (x, y), or `None` for a pixel
+    /// outside a fisheye's image circle.
+    pub fn ray_for_pixel(&self, x: u32, y: u32) -> Option<ray::Ray> {
+        return self.ray_through(x, y, 0.5, 0.5);
+    }
+
+    // The ray through a point inside pixel (x, y), given as fractions of
+    // the pixel's width and height from its top left corner.
+    fn ray_through(&self, x: u32, y: u32, xfraction: f64, yfraction: f64) -> Option<ray::Ray> {
+        // offset from the edge of the canvas to the point in the pixel
+        let xoffset = ((x as f64) + xfraction) * self.pixel_size;
+        let yoffset = ((y as f64) + yfraction) * self.pixel_size;
 
         // untransformed coordinates of the pixel in world space
         // (camera looks towards -z, so +x is the left)
         let world_x = self.half_width - xoffset;
         let world_y = self.half_height - yoffset;
 
-        let inverse_transform = self.transform.inverse().unwrap();
-        let pixel = inverse_transform * tuple::Point::new(world_x, world_y, -1.0);
-        let origin = inverse_transform * tuple::Point::new(0.0, 0.0, 0.0);
+        // In camera space, the ray starts at `origin` and passes through
+        // `pixel` on the image plane one unit in front of it.
+        let (origin, pixel) = match self.projection {
+            Projection::Perspective => (
+                tuple::Point::new(0.0, 0.0, 0.0),
+                tuple::Point::new(world_x, world_y, -1.0),
+            ),
+            Projection::Orthographic => (
+                tuple::Point::new(world_x, world_y, 0.0),
+                tuple::Point::new(world_x, world_y, -1.0),
+            ),
+            // The panoramic projections have no image plane: `pixel` is
+            // one unit along the ray's direction instead.
+            Projection::Equirectangular => {
+                // world_x is the longitude, measured to the left of the
+                // view direction. Rows split the latitudes evenly whatever
+                // the image's shape.
+                let longitude = world_x;
+                let latitude = std::f64::consts::FRAC_PI_2
+                    - std::f64::consts::PI * (f64::from(y) + yfraction) / f64::from(self.vsize);
+                let direction = tuple::Vector::new(
+                    longitude.sin() * latitude.cos(),
+                    latitude.sin(),
+                    -longitude.cos() * latitude.cos(),
+                );
+                let origin = tuple::Point::new(0.0, 0.0, 0.0);
+                (origin, origin + direction)
+            }
+            Projection::Fisheye { half_field_of_view } => {
+                let radius = world_x.hypot(world_y);
+                if radius > 1.0 {
+                    return None;
+                }
+                let angle = radius * half_field_of_view;
+                let direction = if radius == 0.0 {
+                    tuple::Vector::new(0.0, 0.0, -1.0)
+                } else {
+                    let spread = angle.sin() / radius;
+                    tuple::Vector::new(world_x * spread, world_y * spread, -angle.cos())
+                };
+                let origin = tuple::Point::new(0.0, 0.0, 0.0);
+                (origin, origin + direction)
+            }
+        };
+        let (origin, pixel) = if self.aperture > 0.0 {
+            self.through_lens(origin, pixel)
+        } else {
+            (origin, pixel)
+        };
+
+        let pixel = self.inverse * pixel;
+        let origin = self.inverse * origin;
         let direction = tuple::normalize(&(pixel - origin));
 
-        return ray::ray(origin, direction);
+        return Some(ray::ray(origin, direction));
+    }
+
+    // Moves a pinhole ray's start to a point on the lens and aims it at
+    // where the pinhole ray crosses the focal plane. Every ray through the
+    // pixel meets at that point, so only the focal plane is sharp.
+    fn through_lens(
+        &self,
+        origin: tuple::Point,
+        pixel: tuple::Point,
+    ) -> (tuple::Point, tuple::Point) {
+        // Square root of the radius fraction, so points spread evenly
+        // over the disk's area instead of bunching at its center.
+        let radius = self.aperture * self.lens_jitter.next().sqrt();
+        let angle = 2.0 * std::f64::consts::PI * self.lens_jitter.next();
+        let lens_offset = tuple::Vector::new(radius * angle.cos(), radius * angle.sin(), 0.0);
+        let focal_point = origin + (pixel - origin) * self.focal_distance;
+
+        return (origin + lens_offset, focal_point);
     }
 
     pub fn render(&self, world: &world::World) -> canvas::Canvas {
+        let region = self.region_or_image();
+        let mut image = canvas::canvas(self.hsize, self.vsize);
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                image.write_pixel(x, y, self.render_pixel(world, x, y));
+            }
+        }
+        return image;
+    }
+
+    /// Renders the same image as `render`, bit for bit, but shades it on
+    /// `threads` worker threads. Each worker claims the next unrendered
+    /// tile until none are left.
+    pub fn render_parallel(&self, world: &world::World, threads: usize) -> canvas::Canvas {
+        return self
+            .render_observed(world, threads, |_| Control::Continue)
+            .canvas;
+    }
+
+    /// Renders like `render_parallel`, calling `observer` on this thread
+    /// each time a tile is finished. If the observer returns
+    /// `Control::Cancel`, the workers stop claiming tiles and the image is
+    /// returned with only the tiles finished so far filled in.
+    pub fn render_observed(
+        &self,
+        world: &world::World,
+        threads: usize,
+        observer: impl FnMut(&Progress) -> Control,
+    ) -> ObservedRender {
+        return self.shade_tiles(threads, |x, y| self.render_pixel(world, x, y), observer);
+    }
+
+    /// Starts a progressive render of `world`: an image that is refined
+    /// one sample per pixel per pass, for previewing a heavy scene early
+    /// and letting it converge for as long as there is time.
+    pub fn progressive<'a>(&'a self, world: &'a world::World) -> ProgressiveRender<'a> {
+        return ProgressiveRender {
+            camera: self,
+            world,
+            sums: canvas::canvas(self.hsize, self.vsize),
+            passes: 0,
+        };
+    }
+
+    // Colors the region's pixels with `shade` on `threads` worker threads,
+    // a tile at a time, reporting each finished tile to `observer`.
+    fn shade_tiles(
+        &self,
+        threads: usize,
+        shade: impl Fn(u32, u32) -> color::Color + Sync,
+        mut observer: impl FnMut(&Progress) -> Control,
+    ) -> ObservedRender {
+        let region = self.region_or_image();
+        let (right, bottom) = (region.x + region.width, region.y + region.height);
+        let columns = region.width.div_ceil(TILE_SIZE);
+        let rows = region.height.div_ceil(TILE_SIZE);
+        let total = (columns * rows) as usize;
+        let next_tile = AtomicUsize::new(0);
+        let cancelled = AtomicBool::new(false);
+        let start = Instant::now();
+
+        let mut image = canvas::canvas(self.hsize, self.vsize);
+        std::thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..threads.max(1) {
+                let sender = sender.clone();
+                let (next_tile, cancelled, shade) = (&next_tile, &cancelled, &shade);
+                scope.spawn(move || {
+                    while !cancelled.load(Ordering::Relaxed) {
+                        let tile = next_tile.fetch_add(1, Ordering::Relaxed) as u32;
+                        if tile as usize >= total {
+                            break;
+                        }
+                        let left = region.x + (tile % columns) * TILE_SIZE;
+                        let top = region.y + (tile / columns) * TILE_SIZE;
+                        let mut pixels = Vec::new();
+                        for y in top..(top + TILE_SIZE).min(bottom) {
+                            for x in left..(left + TILE_SIZE).min(right) {
+                                pixels.push((x, y, shade(x, y)));
+                            }
+                        }
+                        if sender.send(pixels).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            // Only the workers' senders are left, so the loop ends once
+            // they have all finished.
+            drop(sender);
+
+            let mut progress = Progress {
+                completed: 0,
+                total: (region.width * region.height) as usize,
+                elapsed: Duration::ZERO,
+            };
+            for pixels in receiver {
+                progress.completed += pixels.len();
+                for (x, y, color) in pixels {
+                    image.write_pixel(x, y, color);
+                }
+                progress.elapsed = start.elapsed();
+                if observer(&progress) == Control::Cancel {
+                    cancelled.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        });
+        return ObservedRender {
+            canvas: image,
+            cancelled: cancelled.into_inner(),
+        };
+    }
+
+    /// Anti-aliases only the pixels that need it. Renders one ray through
+    /// each pixel's center, then refines every pixel whose color differs
+    /// from a horizontal or vertical neighbour's by more than `threshold`
+    /// in any channel. A refined pixel is split into quadrants, each
+    /// sampled through its center; a quadrant is split again while its
+    /// parent's samples still differ by more than `threshold`, up to
+    /// `max_depth` levels of splitting. `samples_per_pixel` and the
+    /// region are ignored.
+    pub fn render_adaptive(
+        &self,
+        world: &world::World,
+        threshold: f64,
+        max_depth: u32,
+    ) -> AdaptiveRender {
+        let mut first_pass = canvas::canvas(self.hsize, self.vsize);
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                self.rewind_sequences(world, x, y, 0);
+                first_pass.write_pixel(x, y, self.color_through(world, x, y, 0.5, 0.5));
+            }
+        }
+
         let mut image = canvas::canvas(self.hsize, self.vsize);
+        let mut refined_pixels = 0;
+        let mut extra_rays = 0;
         for y in 0..self.vsize {
             for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(x, y);
-                let color = world.color_at(&ray, 10);
-                image.write_pixel(x, y, color);
+                let center = *first_pass.pixel_at(x, y);
+                let neighbours = [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ];
+                let high_contrast = neighbours.iter().any(|&(nx, ny)| {
+                    nx < self.hsize
+                        && ny < self.vsize
+                        && contrast(center, *first_pass.pixel_at(nx, ny)) > threshold
+                });
+
+                if high_contrast && max_depth > 0 {
+                    self.rewind_sequences(world, x, y, 0);
+                    let color = self.refine(
+                        world,
+                        x,
+                        y,
+                        (0.0, 0.0, 1.0),
+                        threshold,
+                        max_depth,
+                        &mut extra_rays,
+                    );
+                    image.write_pixel(x, y, color);
+                    refined_pixels += 1;
+                } else {
+                    image.write_pixel(x, y, center);
+                }
+            }
+        }
+
+        return AdaptiveRender {
+            canvas: image,
+            refined_pixels,
+            extra_rays,
+        };
+    }
+
+    // The average color of a square region of pixel (x, y), given as
+    // (left, top, size) in fractions of the pixel, from one ray through
+    // the center of each of its quadrants. Quadrants are refined in turn
+    // while the samples disagree and `depth` levels remain.
+    #[allow(clippy::too_many_arguments)]
+    fn refine(
+        &self,
+        world: &world::World,
+        x: u32,
+        y: u32,
+        (left, top, size): (f64, f64, f64),
+        threshold: f64,
+        depth: u32,
+        rays: &mut usize,
+    ) -> color::Color {
+        let half = size / 2.0;
+        let quadrants = [
+            (left, top),
+            (left + half, top),
+            (left, top + half),
+            (left + half, top + half),
+        ];
+        let samples = quadrants.map(|(qleft, qtop)| {
+            self.color_through(world, x, y, qleft + half / 2.0, qtop + half / 2.0)
+        });
+        *rays += samples.len();
+
+        let disagree = samples
+            .iter()
+            .any(|&sample| contrast(sample, samples[0]) > threshold);
+        let mut total = color::black();
+        for (quadrant, sample) in quadrants.iter().zip(samples) {
+            if disagree && depth > 1 {
+                let region = (quadrant.0, quadrant.1, half);
+                total = total + self.refine(world, x, y, region, threshold, depth - 1, rays);
+            } else {
+                total = total + sample;
+            }
+        }
+        return total * 0.25;
+    }
+
+    // The pixels to render: the region if one is set, otherwise all of
+    // them.
+    fn region_or_image(&self) -> Region {
+        return self.region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: self.hsize,
+            height: self.vsize,
+        });
+    }
+
+    // Rewinds the jitter sequences for pass `pass` over pixel (x, y). A
+    // render that scatters rays or samples the pixel or the lens draws so
+    // many numbers that neighbours starting from the same place would
+    // repeat each other's pattern, so each pixel starts from a place of its
+    // own. Otherwise every pixel starts from the first number, as a
+    // single-threaded render of the same scene always has. Each pass starts
+    // past the numbers the passes before it drew.
+    fn rewind_sequences(&self, world: &world::World, x: u32, y: u32, pass: u32) {
+        let scattered = match self.integrator {
+            world::Integrator::Whitted => world.ambient_occlusion.is_some(),
+            world::Integrator::PathTraced { .. } | world::Integrator::AmbientOcclusion(_) => true,
+        };
+        let sampled = scattered || self.samples_per_side > 1 || self.aperture > 0.0;
+        let start = if sampled {
+            let hash = ((u64::from(x) << 32) | u64::from(y)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            (hash >> 32) as usize
+        } else {
+            0
+        };
+        sequences::rewind_to(start + pass as usize * self.draws_per_sample(world));
+    }
+
+    // The most numbers one ray through a pixel draws from any one sequence
+    // where it first meets a surface: two per lens point, per light sample,
+    // per occlusion ray and per bounce direction, and a third for the
+    // bounce's Russian roulette.
+    fn draws_per_sample(&self, world: &world::World) -> usize {
+        let lens = if self.aperture > 0.0 { 2 } else { 0 };
+        let light = world
+            .lights
+            .iter()
+            .map(|light| 2 * light.samples())
+            .max()
+            .unwrap_or(0);
+        let occlusion = match (&self.integrator, &world.ambient_occlusion) {
+            (world::Integrator::AmbientOcclusion(occlusion), _) | (_, Some(occlusion)) => {
+                2 * occlusion.samples
+            }
+            _ => 0,
+        };
+        let bounce = match self.integrator {
+            world::Integrator::PathTraced { .. } => 3,
+            _ => 0,
+        };
+        return lens.max(light).max(occlusion).max(bounce).max(1);
+    }
+
+    // Every pixel starts from rewound jitter sequences, so its color
+    // depends only on its position: not on the thread that shades it, nor
+    // on the pixels that thread shaded before.
+    fn render_pixel(&self, world: &world::World, x: u32, y: u32) -> color::Color {
+        self.rewind_sequences(world, x, y, 0);
+        if self.samples_per_side == 1 {
+            return self.color_through(world, x, y, 0.5, 0.5);
+        }
+
+        let side = f64::from(self.samples_per_side);
+        let mut total = color::black();
+        for row in 0..self.samples_per_side {
+            for column in 0..self.samples_per_side {
+                let xfraction = (f64::from(column) + self.jitter.next()) / side;
+                let yfraction = (f64::from(row) + self.jitter.next()) / side;
+                total = total + self.color_through(world, x, y, xfraction, yfraction);
+            }
+        }
+        return total * (1.0 / (side * side));
+    }
+
+    // Pass `pass` of a progressive render: one ray through pixel (x, y),
+    // through its center on the first pass and then through points the
+    // Halton sequence spreads evenly over it. The jitter sequences start
+    // past the numbers earlier passes drew, so lenses and area lights are
+    // sampled afresh too.
+    fn progressive_sample(&self, world: &world::World, x: u32, y: u32, pass: u32) -> color::Color {
+        self.rewind_sequences(world, x, y, pass);
+        if pass == 0 {
+            return self.color_through(world, x, y, 0.5, 0.5);
+        }
+        return self.color_through(world, x, y, halton(pass, 2), halton(pass, 3));
+    }
+
+    // The color seen along `ray_through`, or black where there is no ray.
+    fn color_through(
+        &self,
+        world: &world::World,
+        x: u32,
+        y: u32,
+        xfraction: f64,
+        yfraction: f64,
+    ) -> color::Color {
+        let ray = match self.ray_through(x, y, xfraction, yfraction) {
+            Some(ray) => ray,
+            None => return color::black(),
+        };
+        return match &self.integrator {
+            world::Integrator::Whitted => world.color_at(&ray, 10),
+            world::Integrator::PathTraced { jitter } => world.path_traced_color(&ray, jitter, 0),
+            world::Integrator::AmbientOcclusion(occlusion) => {
+                world.occlusion_color(&ray, occlusion)
+            }
+        };
+    }
+}
+
+/// An image being refined pass by pass; see `Camera::progressive`.
+pub struct ProgressiveRender<'a> {
+    camera: &'a Camera,
+    world: &'a world::World,
+    // Every pass's samples added up, per pixel.
+    sums: canvas::Canvas,
+    passes: u32,
+}
+
+impl ProgressiveRender<'_> {
+    /// Adds one more sample to every pixel (in the camera's region, if it
+    /// has one), shading on `threads` worker threads. The first pass is
+    /// the same image as a one sample per pixel `render`; the camera's
+    /// `samples_per_pixel` is otherwise ignored.
+    pub fn pass(&mut self, threads: usize) {
+        let camera = self.camera;
+        let (world, pass) = (self.world, self.passes);
+        let samples = camera.shade_tiles(
+            threads,
+            |x, y| camera.progressive_sample(world, x, y, pass),
+            |_| Control::Continue,
+        );
+        for y in 0..camera.vsize {
+            for x in 0..camera.hsize {
+                let sum = *self.sums.pixel_at(x, y) + *samples.canvas.pixel_at(x, y);
+                self.sums.write_pixel(x, y, sum);
+            }
+        }
+        self.passes += 1;
+    }
+
+    pub fn passes(&self) -> u32 {
+        return self.passes;
+    }
+
+    /// The image so far: each pixel's samples averaged. Black before the
+    /// first pass.
+    pub fn snapshot(&self) -> canvas::Canvas {
+        let scale = 1.0 / f64::from(self.passes.max(1));
+        let mut image = canvas::canvas(self.sums.width, self.sums.height);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                image.write_pixel(x, y, *self.sums.pixel_at(x, y) * scale);
             }
         }
         return image;
     }
 }
 
+// The `index`th number of the Halton sequence in `base`: `index` written in
+// `base`, with its digits mirrored about the point. Successive numbers
+// keep filling the gaps between the ones before.
+fn halton(index: u32, base: u32) -> f64 {
+    let mut index = index;
+    let mut result = 0.0;
+    let mut digit_value = 1.0;
+    while index > 0 {
+        digit_value /= f64::from(base);
+        result += digit_value * f64::from(index % base);
+        index /= base;
+    }
+    return result;
+}
+
+// The largest difference between two colors in any one channel.
+fn contrast(a: color::Color, b: color::Color) -> f64 {
+    return (a.r - b.r)
+        .abs()
+        .max((a.g - b.g).abs())
+        .max((a.b - b.b).abs());
+}
+
 #[cfg(test)]
 mod camera_tests {
     use crate::camera;
     use crate::color;
     use crate::matrix;
+    use crate::matrix::Inverse;
     use crate::transformation;
     use crate::transformation::Transform;
     use crate::tuple;
@@ -94,7 +866,7 @@ mod camera_tests {
 
         assert_eq!(camera.hsize, hsize);
         assert_eq!(camera.vsize, vsize);
-        assert_eq!(camera.transform, matrix::Matrix4::IDENTITY);
+        assert_eq!(*camera.transformation_matrix(), matrix::Matrix4::IDENTITY);
     }
 
     #[test]
@@ -113,7 +885,7 @@ mod camera_tests {
     fn test_a_ray_through_the_center_of_the_canvas() {
         let camera = camera::Camera::new(201, 101, std::f64::consts::PI / 2.0);
 
-        let ray = camera.ray_for_pixel(100, 50);
+        let ray = camera.ray_for_pixel(100, 50).unwrap();
 
         assert_tuple_approx_eq!(ray.origin, tuple::Point::new(0.0, 0.0, 0.0));
         assert_tuple_approx_eq!(ray.direction, tuple::Vector::new(0.0, 0.0, -1.0));
@@ -123,7 +895,7 @@ mod camera_tests {
     fn test_a_ray_through_the_near_corner_of_the_canvas() {
         let camera = camera::Camera::new(201, 101, std::f64::consts::PI / 2.0);
 
-        let ray = camera.ray_for_pixel(0, 0);
+        let ray = camera.ray_for_pixel(0, 0).unwrap();
 
         assert_tuple_approx_eq!(ray.origin, tuple::Point::new(0.0, 0.0, 0.0));
         assert_tuple_approx_eq!(
@@ -136,7 +908,7 @@ mod camera_tests {
     fn test_a_ray_through_the_far_corner_of_the_canvas() {
         let camera = camera::Camera::new(201, 101, std::f64::consts::PI / 2.0);
 
-        let ray = camera.ray_for_pixel(200, 100);
+        let ray = camera.ray_for_pixel(200, 100).unwrap();
 
         assert_tuple_approx_eq!(ray.origin, tuple::Point::new(0.0, 0.0, 0.0));
         assert_tuple_approx_eq!(
@@ -151,9 +923,9 @@ mod camera_tests {
         let transform = matrix::Matrix4::IDENTITY
             .translation(0.0, -2.0, 5.0)
             .rotation_y(std::f64::consts::PI / 4.0);
-        camera.transform = transform;
+        camera.set_transformation_matrix(transform);
 
-        let ray = camera.ray_for_pixel(100, 50);
+        let ray = camera.ray_for_pixel(100, 50).unwrap();
 
         assert_tuple_approx_eq!(ray.origin, tuple::Point::new(0.0, 2.0, -5.0));
         assert_tuple_approx_eq!(
@@ -162,6 +934,194 @@ mod camera_tests {
         );
     }
 
+    #[test]
+    fn test_trying_a_view_along_up_keeps_the_old_transform() {
+        let mut camera = camera::Camera::new(201, 101, std::f64::consts::PI / 2.0);
+        let transform = matrix::Matrix4::IDENTITY.translation(0.0, -2.0, 5.0);
+        camera.set_transformation_matrix(transform);
+
+        let result = camera.try_set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 5.0, 0.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+
+        assert!(result.unwrap_err().contains("must be invertible"));
+        assert_eq!(*camera.transformation_matrix(), transform);
+        assert_eq!(camera.inverse, transform.inverse().unwrap());
+    }
+
+    #[test]
+    fn test_pixel_size_for_an_orthographic_camera() {
+        let camera = camera::Camera::orthographic(200, 100, 4.0);
+
+        assert_approx_eq!(camera.pixel_size, 0.02);
+        assert_approx_eq!(camera.half_height, 1.0);
+    }
+
+    #[test]
+    fn test_orthographic_rays_start_on_the_image_plane_and_run_parallel() {
+        let camera = camera::Camera::orthographic(200, 100, 4.0);
+
+        let center = camera.ray_for_pixel(100, 50).unwrap();
+        let corner = camera.ray_for_pixel(0, 0).unwrap();
+
+        assert_tuple_approx_eq!(center.origin, tuple::Point::new(-0.01, -0.01, 0.0));
+        assert_tuple_approx_eq!(center.direction, tuple::Vector::new(0.0, 0.0, -1.0));
+        assert_tuple_approx_eq!(corner.origin, tuple::Point::new(1.99, 0.99, 0.0));
+        assert_tuple_approx_eq!(corner.direction, tuple::Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_orthographic_rays_when_the_camera_is_transformed() {
+        let mut camera = camera::Camera::orthographic(200, 100, 4.0);
+        camera.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .translation(0.0, -2.0, 5.0)
+                .rotation_y(std::f64::consts::PI / 4.0),
+        );
+
+        let center = camera.ray_for_pixel(100, 50).unwrap();
+        let corner = camera.ray_for_pixel(0, 0).unwrap();
+
+        let forward = tuple::Vector::new(2.0_f64.sqrt() / 2.0, 0.0, -2.0_f64.sqrt() / 2.0);
+        assert_tuple_approx_eq!(center.direction, forward);
+        assert_tuple_approx_eq!(corner.direction, forward);
+        assert_ne!(corner.origin, center.origin);
+    }
+
+    #[test]
+    fn test_equirectangular_rays_cover_every_direction() {
+        let camera = camera::Camera::equirectangular(200, 100);
+
+        // (x, y, xfraction, yfraction) of points on the image, and the
+        // directions they look in.
+        let cases = [
+            // the center of the image looks ahead
+            (99, 49, 1.0, 1.0, tuple::Vector::new(0.0, 0.0, -1.0)),
+            // a quarter of the way across looks left
+            (49, 49, 1.0, 1.0, tuple::Vector::new(1.0, 0.0, 0.0)),
+            // three quarters of the way across looks right
+            (149, 49, 1.0, 1.0, tuple::Vector::new(-1.0, 0.0, 0.0)),
+            // the left edge looks behind
+            (0, 49, 0.0, 1.0, tuple::Vector::new(0.0, 0.0, 1.0)),
+            // the top edge looks straight up
+            (99, 0, 1.0, 0.0, tuple::Vector::new(0.0, 1.0, 0.0)),
+            // the bottom edge looks straight down
+            (99, 99, 1.0, 1.0, tuple::Vector::new(0.0, -1.0, 0.0)),
+        ];
+        for (x, y, xfraction, yfraction, direction) in cases {
+            let ray = camera.ray_through(x, y, xfraction, yfraction).unwrap();
+
+            assert_tuple_approx_eq!(ray.origin, tuple::Point::new(0.0, 0.0, 0.0));
+            assert_tuple_approx_eq!(ray.direction, direction);
+        }
+    }
+
+    #[test]
+    fn test_fisheye_rays_spread_with_distance_from_the_center() {
+        let camera = camera::Camera::fisheye(100, 100, std::f64::consts::PI);
+
+        let center = camera.ray_through(49, 49, 1.0, 1.0).unwrap();
+        let left_edge = camera.ray_through(0, 49, 0.0, 1.0).unwrap();
+        let halfway_up = camera.ray_through(49, 24, 1.0, 1.0).unwrap();
+
+        assert_tuple_approx_eq!(center.direction, tuple::Vector::new(0.0, 0.0, -1.0));
+        // 180 degrees across, so the edge of the circle looks sideways...
+        assert_tuple_approx_eq!(left_edge.direction, tuple::Vector::new(1.0, 0.0, 0.0));
+        // ...and halfway to it looks 45 degrees off the view direction.
+        let diagonal = 2.0_f64.sqrt() / 2.0;
+        assert_tuple_approx_eq!(
+            halfway_up.direction,
+            tuple::Vector::new(0.0, diagonal, -diagonal)
+        );
+    }
+
+    #[test]
+    fn test_a_full_circle_fisheye_sees_behind_the_camera_at_its_edge() {
+        let camera = camera::Camera::fisheye(100, 100, 2.0 * std::f64::consts::PI);
+
+        let edge = camera.ray_through(49, 0, 1.0, 0.0).unwrap();
+
+        assert_tuple_approx_eq!(edge.direction, tuple::Vector::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_a_fisheye_has_no_rays_outside_its_image_circle() {
+        let camera = camera::Camera::fisheye(200, 100, std::f64::consts::PI);
+
+        assert!(camera.ray_for_pixel(0, 0).is_none());
+        assert!(camera.ray_for_pixel(10, 50).is_none());
+        assert!(camera.ray_for_pixel(100, 50).is_some());
+    }
+
+    #[test]
+    fn test_rendering_leaves_pixels_outside_a_fisheye_circle_black() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::fisheye(20, 10, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+
+        let image = camera.render(&world);
+
+        assert_eq!(*image.pixel_at(0, 0), color::black());
+        assert_ne!(*image.pixel_at(10, 5), color::black());
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 2π")]
+    fn test_a_fisheye_field_of_view_is_at_most_a_full_circle() {
+        camera::Camera::fisheye(100, 100, 7.0);
+    }
+
+    #[test]
+    fn test_a_lens_ray_leaves_from_the_sampled_point_on_the_lens() {
+        use crate::sequences;
+
+        let mut camera = camera::Camera::new(201, 101, std::f64::consts::PI / 2.0);
+        camera.set_focus(0.5, 5.0);
+        // Half the radius straight along +x, then the full radius along +y.
+        camera.set_lens_jitter(sequences::sequence(&[0.25, 0.0, 1.0, 0.25]));
+
+        let first = camera.ray_for_pixel(100, 50).unwrap();
+        let second = camera.ray_for_pixel(100, 50).unwrap();
+
+        assert_tuple_approx_eq!(first.origin, tuple::Point::new(0.25, 0.0, 0.0));
+        assert_tuple_approx_eq!(second.origin, tuple::Point::new(0.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_lens_rays_converge_where_the_pinhole_ray_meets_the_focal_plane() {
+        use crate::sequences;
+
+        let mut camera = camera::Camera::new(201, 101, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .translation(0.0, -2.0, 5.0)
+                .rotation_y(std::f64::consts::PI / 4.0),
+        );
+        let pinhole = camera.ray_for_pixel(0, 0).unwrap();
+        // The corner pixel's center is at (200/201, 100/201, -1) in camera
+        // space, so the pinhole ray meets the plane z = -5 five times as
+        // far away.
+        let in_focus = pinhole.position(
+            5.0 * ((200.0_f64 / 201.0).powi(2) + (100.0_f64 / 201.0).powi(2) + 1.0).sqrt(),
+        );
+
+        camera.set_focus(0.5, 5.0);
+        camera.set_lens_jitter(sequences::sequence(&[0.3, 0.1, 0.8, 0.6, 0.5, 0.9]));
+        for _ in 0..3 {
+            let ray = camera.ray_for_pixel(0, 0).unwrap();
+            let distance = tuple::magnitude(&(in_focus - ray.origin));
+
+            assert!(ray.origin != pinhole.origin);
+            assert_tuple_approx_eq!(ray.position(distance), in_focus);
+        }
+    }
+
     #[test]
     fn test_rendering_a_world_with_a_camera() {
         let world = world::default_world();
@@ -169,7 +1129,7 @@ mod camera_tests {
         let from = tuple::Point::new(0.0, 0.0, -5.0);
         let to = tuple::Point::new(0.0, 0.0, 0.0);
         let up = tuple::Vector::new(0.0, 1.0, 0.0);
-        camera.transform = transformation::view_transform(&from, &to, &up);
+        camera.set_transformation_matrix(transformation::view_transform(&from, &to, &up));
 
         let image = camera.render(&world);
 
@@ -205,7 +1165,7 @@ mod camera_tests {
         let from = tuple::Point::new(0.0, 0.0, -5.0);
         let to = tuple::Point::new(0.0, 0.0, 0.0);
         let up = tuple::Vector::new(0.0, 1.0, 0.0);
-        camera.transform = transformation::view_transform(&from, &to, &up);
+        camera.set_transformation_matrix(transformation::view_transform(&from, &to, &up));
 
         let image = camera.render(&builder.world);
 
@@ -219,4 +1179,469 @@ mod camera_tests {
             color::color(0.166675, 0.33334, 0.166675)
         );
     }
+
+    #[test]
+    fn test_supersampling_averages_a_grid_of_rays_over_the_pixel() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+        camera.set_samples_per_pixel(4);
+
+        // The edge of the sphere crosses pixel (4, 5), so its sub-pixel
+        // rays disagree.
+        let image = camera.render(&world);
+
+        let expected = [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+            .iter()
+            .map(|&(xfraction, yfraction)| {
+                world.color_at(&camera.ray_through(4, 5, xfraction, yfraction).unwrap(), 10)
+            })
+            .fold(color::black(), |total, color| total + color)
+            * 0.25;
+        assert_color_approx_eq!(image.pixel_at(4, 5), expected);
+    }
+
+    #[test]
+    fn test_supersampling_jitters_rays_within_their_cells() {
+        use crate::sequences;
+
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+        camera.set_samples_per_pixel(4);
+        // Each pixel starts the jitter somewhere of its own, so a constant
+        // keeps the rays' places predictable.
+        camera.set_jitter(sequences::Sequence::constant(0.1));
+
+        let image = camera.render(&world);
+
+        let expected = [(0.05, 0.05), (0.55, 0.05), (0.05, 0.55), (0.55, 0.55)]
+            .iter()
+            .map(|&(xfraction, yfraction)| {
+                world.color_at(&camera.ray_through(4, 5, xfraction, yfraction).unwrap(), 10)
+            })
+            .fold(color::black(), |total, color| total + color)
+            * 0.25;
+        assert_color_approx_eq!(image.pixel_at(4, 5), expected);
+    }
+
+    #[test]
+    fn test_a_single_sample_per_pixel_ignores_the_jitter() {
+        use crate::sequences;
+
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+        let expected = camera.render(&world);
+
+        camera.set_jitter(sequences::sequence(&[0.1, 0.9]));
+        let image = camera.render(&world);
+
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "perfect square")]
+    fn test_samples_per_pixel_must_be_a_perfect_square() {
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_samples_per_pixel(3);
+    }
+
+    // The first number each pixel draws from `sequence` after the camera
+    // rewinds for it, over a row of pixels.
+    fn first_draws(
+        camera: &camera::Camera,
+        world: &world::World,
+        sequence: &crate::sequences::Sequence,
+    ) -> Vec<f64> {
+        return (0..8)
+            .map(|x| {
+                camera.rewind_sequences(world, x, 0, 0);
+                sequence.next()
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_pixels_start_area_light_samples_from_the_first_number() {
+        use crate::lights;
+        use crate::sequences;
+
+        let mut world = world::default_world();
+        let camera = camera::Camera::new(8, 1, std::f64::consts::PI / 2.0);
+        let sequence = sequences::Sequence::random(64, 7);
+        world.lights = vec![lights::area_light(
+            tuple::Point::new(-1.0, 2.0, -1.0),
+            tuple::Vector::new(2.0, 0.0, 0.0),
+            4,
+            tuple::Vector::new(0.0, 0.0, 2.0),
+            4,
+            color::white(),
+        )];
+        let draws = first_draws(&camera, &world, &sequence);
+
+        let first = sequences::Sequence::random(64, 7).next();
+        assert!(draws.iter().all(|&n| n == first));
+    }
+
+    #[test]
+    fn test_pixels_start_supersampling_jitter_from_their_own_place() {
+        use crate::sequences;
+
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(8, 1, std::f64::consts::PI / 2.0);
+        camera.set_samples_per_pixel(4);
+        let sequence = sequences::Sequence::random(64, 7);
+
+        let draws = first_draws(&camera, &world, &sequence);
+        assert!(draws.iter().any(|&n| n != draws[0]));
+    }
+
+    #[test]
+    fn test_pixels_start_lens_samples_from_their_own_place() {
+        use crate::sequences;
+
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(8, 1, std::f64::consts::PI / 2.0);
+        camera.set_focus(0.1, 5.0);
+        let sequence = sequences::Sequence::random(64, 7);
+
+        let draws = first_draws(&camera, &world, &sequence);
+        assert!(draws.iter().any(|&n| n != draws[0]));
+    }
+
+    #[test]
+    fn test_adaptive_rendering_refines_only_high_contrast_pixels() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+        let single_sample = camera.render(&world);
+
+        let result = camera.render_adaptive(&world, 0.1, 1);
+
+        // A background pixel far from the sphere is left alone, while one
+        // on its edge becomes the average of its four quadrants, as with
+        // 2x2 supersampling.
+        assert_eq!(result.canvas.pixel_at(0, 0), single_sample.pixel_at(0, 0));
+        camera.set_samples_per_pixel(4);
+        let supersampled = camera.render(&world);
+        assert_color_approx_eq!(result.canvas.pixel_at(4, 5), supersampled.pixel_at(4, 5));
+
+        assert!(result.refined_pixels > 0);
+        assert!(result.refined_pixels < 11 * 11);
+        assert_eq!(result.extra_rays, 4 * result.refined_pixels);
+    }
+
+    #[test]
+    fn test_adaptive_rendering_subdivides_again_up_to_the_max_depth() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+
+        let shallow = camera.render_adaptive(&world, 0.1, 1);
+        let deep = camera.render_adaptive(&world, 0.1, 3);
+
+        assert_eq!(deep.refined_pixels, shallow.refined_pixels);
+        assert!(deep.extra_rays > shallow.extra_rays);
+        assert!(deep.extra_rays <= shallow.refined_pixels * (4 + 16 + 64));
+    }
+
+    #[test]
+    fn test_adaptive_rendering_with_a_high_threshold_matches_a_plain_render() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+
+        let result = camera.render_adaptive(&world, 2.0, 3);
+
+        assert_eq!(result.canvas, camera.render(&world));
+        assert_eq!(result.refined_pixels, 0);
+        assert_eq!(result.extra_rays, 0);
+    }
+
+    #[test]
+    fn test_rendering_in_parallel_matches_rendering_on_one_thread() {
+        use crate::lights;
+        use crate::sequences;
+
+        // An area light, so every pixel draws on a shared jitter sequence,
+        // and a size that leaves partial tiles along the right and bottom.
+        let mut world = world::default_world();
+        let mut light = lights::area_light(
+            tuple::Point::new(-10.0, 10.0, -10.0),
+            tuple::Vector::new(2.0, 0.0, 0.0),
+            4,
+            tuple::Vector::new(0.0, 2.0, 0.0),
+            4,
+            color::white(),
+        );
+        light.set_jitter(sequences::Sequence::random(100, 3));
+        world.lights = vec![light];
+        let mut camera = camera::Camera::new(37, 21, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+
+        let expected = camera.render(&world);
+        let image = camera.render_parallel(&world, 3);
+
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn test_rendering_a_region_matches_the_same_pixels_of_a_full_render() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(37, 21, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+        camera.set_samples_per_pixel(4);
+        camera.set_jitter(crate::sequences::Sequence::random(50, 7));
+        let full = camera.render(&world);
+
+        let region = camera::Region {
+            x: 9,
+            y: 4,
+            width: 19,
+            height: 12,
+        };
+        camera.set_region(Some(region));
+        let image = camera.render(&world);
+        let parallel = camera.render_parallel(&world, 2);
+
+        assert_eq!(parallel, image);
+        for y in 0..21 {
+            for x in 0..37 {
+                let inside = (9..28).contains(&x) && (4..16).contains(&y);
+                let expected = if inside {
+                    *full.pixel_at(x, y)
+                } else {
+                    color::black()
+                };
+                assert_eq!(*image.pixel_at(x, y), expected);
+            }
+        }
+        assert_eq!(image.crop(9, 4, 19, 12), full.crop(9, 4, 19, 12));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not inside the 10x10 image")]
+    fn test_a_region_must_fit_inside_the_image() {
+        let mut camera = camera::Camera::new(10, 10, std::f64::consts::PI / 2.0);
+
+        camera.set_region(Some(camera::Region {
+            x: 5,
+            y: 0,
+            width: 6,
+            height: 10,
+        }));
+    }
+
+    #[test]
+    fn test_an_observed_render_reports_every_pixel_and_matches_a_plain_render() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(37, 21, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+
+        let mut reports = Vec::new();
+        let result = camera.render_observed(&world, 2, |progress| {
+            reports.push(*progress);
+            camera::Control::Continue
+        });
+
+        assert!(!result.cancelled);
+        assert_eq!(result.canvas, camera.render(&world));
+        // 3 columns by 2 rows of tiles
+        assert_eq!(reports.len(), 6);
+        assert!(reports.iter().all(|progress| progress.total == 37 * 21));
+        assert!(
+            reports
+                .windows(2)
+                .all(|pair| pair[0].completed < pair[1].completed)
+        );
+        let last = reports.last().unwrap();
+        assert_eq!(last.completed, 37 * 21);
+        assert_eq!(last.eta(), Some(std::time::Duration::ZERO));
+    }
+
+    #[test]
+    fn test_cancelling_a_render_returns_the_tiles_finished_so_far() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(64, 64, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+
+        let mut calls = 0;
+        let result = camera.render_observed(&world, 1, |_| {
+            calls += 1;
+            camera::Control::Cancel
+        });
+
+        assert!(result.cancelled);
+        assert_eq!(calls, 1);
+        // On one thread, the first tile is the top left one.
+        let full = camera.render(&world);
+        assert_eq!(result.canvas.crop(0, 0, 16, 16), full.crop(0, 0, 16, 16));
+        assert_eq!(*result.canvas.pixel_at(32, 32), color::black());
+        assert_ne!(*full.pixel_at(32, 32), color::black());
+    }
+
+    #[test]
+    fn test_progress_estimates_the_time_left_from_the_rate_so_far() {
+        let progress = camera::Progress {
+            completed: 25,
+            total: 100,
+            elapsed: std::time::Duration::from_secs(10),
+        };
+
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(progress.eta(), Some(std::time::Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_the_first_progressive_pass_matches_a_plain_render() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+        let mut progressive = camera.progressive(&world);
+
+        progressive.pass(2);
+
+        assert_eq!(progressive.passes(), 1);
+        assert_eq!(progressive.snapshot(), camera.render(&world));
+    }
+
+    #[test]
+    fn test_progressive_snapshots_average_a_new_sample_per_pass() {
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+        let mut progressive = camera.progressive(&world);
+
+        progressive.pass(1);
+        progressive.pass(1);
+        let image = progressive.snapshot();
+
+        // The second pass samples each pixel at (1/2, 1/3) instead of its
+        // center, which lands on something else along the sphere's edge.
+        let mut edge_pixels = 0;
+        for y in 0..11 {
+            for x in 0..11 {
+                let center = camera.color_through(&world, x, y, 0.5, 0.5);
+                let second = camera.color_through(&world, x, y, 0.5, 1.0 / 3.0);
+                if center != second {
+                    edge_pixels += 1;
+                }
+                assert_color_approx_eq!(image.pixel_at(x, y), (center + second) * 0.5);
+            }
+        }
+        assert!(edge_pixels > 0);
+    }
+
+    // The first `count` numbers pixel (3, 2) draws from `sequence` on
+    // progressive pass `pass`.
+    fn pass_draws(
+        camera: &camera::Camera,
+        world: &world::World,
+        sequence: &crate::sequences::Sequence,
+        pass: u32,
+        count: usize,
+    ) -> Vec<f64> {
+        camera.rewind_sequences(world, 3, 2, pass);
+        return (0..count).map(|_| sequence.next()).collect();
+    }
+
+    #[test]
+    fn test_consecutive_progressive_passes_draw_different_lens_samples() {
+        use crate::sequences;
+
+        let world = world::default_world();
+        let mut camera = camera::Camera::new(8, 8, std::f64::consts::PI / 2.0);
+        camera.set_focus(0.1, 5.0);
+        let sequence = sequences::Sequence::random(256, 7);
+
+        let first = pass_draws(&camera, &world, &sequence, 1, 2);
+        let second = pass_draws(&camera, &world, &sequence, 2, 2);
+
+        assert!(first.iter().all(|n| !second.contains(n)));
+    }
+
+    #[test]
+    fn test_consecutive_progressive_passes_draw_different_light_samples() {
+        use crate::lights;
+        use crate::sequences;
+
+        let mut world = world::default_world();
+        world.lights = vec![lights::area_light(
+            tuple::Point::new(-1.0, 2.0, -1.0),
+            tuple::Vector::new(2.0, 0.0, 0.0),
+            4,
+            tuple::Vector::new(0.0, 0.0, 2.0),
+            4,
+            color::white(),
+        )];
+        let camera = camera::Camera::new(8, 8, std::f64::consts::PI / 2.0);
+        let sequence = sequences::Sequence::random(256, 7);
+
+        // Sixteen light samples, two numbers each.
+        let first = pass_draws(&camera, &world, &sequence, 0, 32);
+        let second = pass_draws(&camera, &world, &sequence, 1, 32);
+
+        assert!(first.iter().all(|n| !second.contains(n)));
+    }
+
+    #[test]
+    fn test_the_halton_sequence_fills_the_gaps_between_earlier_numbers() {
+        let base2: Vec<f64> = (1..5).map(|i| camera::halton(i, 2)).collect();
+        let base3: Vec<f64> = (1..5).map(|i| camera::halton(i, 3)).collect();
+
+        assert_eq!(base2, [0.5, 0.25, 0.75, 0.125]);
+        assert_approx_eq!(base3[0], 1.0 / 3.0);
+        assert_approx_eq!(base3[1], 2.0 / 3.0);
+        assert_approx_eq!(base3[2], 1.0 / 9.0);
+        assert_approx_eq!(base3[3], 4.0 / 9.0);
+    }
 }
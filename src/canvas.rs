@@ -35,6 +35,18 @@ impl Canvas {
         &self.grid[y as usize][x as usize]
     }
 
+    /// A copy of the `width` by `height` rectangle of pixels whose top
+    /// left corner is at (`x`, `y`).
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Canvas {
+        let mut cropped = canvas(width, height);
+        for row in 0..height {
+            for column in 0..width {
+                cropped.write_pixel(column, row, *self.pixel_at(x + column, y + row));
+            }
+        }
+        return cropped;
+    }
+
     pub fn canvas_to_image(&self) -> RgbImage {
         let mut img: RgbImage = ImageBuffer::new(self.width, self.height);
 
@@ -169,6 +181,23 @@ mod canvas_tests {
         assert_eq!(expected_image, result);
     }
 
+    #[test]
+    fn test_cropping_a_canvas_copies_the_rectangle() {
+        let mut canvas1 = canvas::canvas(5, 4);
+        for y in 0..canvas1.height {
+            for x in 0..canvas1.width {
+                canvas1.write_pixel(x, y, color::color(x as f64, y as f64, 0.0));
+            }
+        }
+
+        let cropped = canvas1.crop(1, 2, 3, 2);
+
+        assert_eq!(cropped.width, 3);
+        assert_eq!(cropped.height, 2);
+        assert_eq!(&color::color(1.0, 2.0, 0.0), cropped.pixel_at(0, 0));
+        assert_eq!(&color::color(3.0, 3.0, 0.0), cropped.pixel_at(2, 1));
+    }
+
     // Scenario: Reading a file with the wrong magic number
     #[test]
     fn test_reading_a_file_with_the_wrong_magic_number() {
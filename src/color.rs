@@ -1,4 +1,4 @@
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, Div, Mul, Sub};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Color {
@@ -54,6 +54,18 @@ impl Mul<f64> for Color {
     }
 }
 
+impl Div<f64> for Color {
+    type Output = Self;
+
+    fn div(self, other: f64) -> Self {
+        Color {
+            r: self.r / other,
+            g: self.g / other,
+            b: self.b / other,
+        }
+    }
+}
+
 impl Mul for Color {
     type Output = Self;
 
@@ -105,6 +117,14 @@ mod color_tests {
         assert_eq!(color1 * 2.0, expected_color);
     }
 
+    #[test]
+    fn test_colors_can_be_divided_by_a_scalar() {
+        let color1 = color::color(0.4, 0.6, 0.8);
+
+        let expected_color = color::color(0.2, 0.3, 0.4);
+        assert_eq!(color1 / 2.0, expected_color);
+    }
+
     #[test]
     fn test_colors_can_be_muliplied_with_each_other() {
         let color1 = color::color(1.0, 0.2, 0.4);
@@ -1,8 +1,8 @@
 use crate::color;
 use crate::lighting;
 use crate::lights;
+use crate::material;
 use crate::matrix;
-use crate::matrix::{Inverse, Transpose};
 use crate::ray;
 use crate::shape;
 use crate::tuple;
@@ -14,16 +14,28 @@ const EPSILON: f64 = 1e-5;
 pub struct Intersection<'a> {
     pub t: f64,
     pub object: &'a shape::Shape,
+    // The material to shade the hit with: the object's own, unless an
+    // instance it was reached through overrides it.
+    pub material: &'a material::Material,
 
     // The object-to-world transform. This starts as the object's own
     // transform, and each enclosing group prepends its transform as the
     // intersections bubble back up the tree.
     pub world_transform: matrix::Matrix4,
+    // Its inverse, the world-to-object transform, built up the same way
+    // from each shape's cached inverse so that it never needs inverting.
+    pub world_inverse: matrix::Matrix4,
+    // And the inverse's transpose, which carries normals to world space,
+    // built up from each shape's cached inverse transpose.
+    pub world_inverse_transpose: matrix::Matrix4,
 
     // Barycentric coordinates of the hit, relative to a triangle's
     // corners. They stay zero for every other shape.
     pub u: f64,
     pub v: f64,
+    // Which of a mesh's faces was hit, and so which corners `u` and `v`
+    // are relative to. It stays zero for every other shape.
+    pub face: usize,
 }
 
 pub fn intersection(t: f64, object: &shape::Shape) -> Intersection<'_> {
@@ -31,32 +43,51 @@ pub fn intersection(t: f64, object: &shape::Shape) -> Intersection<'_> {
 }
 
 pub fn intersection_with_uv(t: f64, object: &shape::Shape, u: f64, v: f64) -> Intersection<'_> {
+    face_intersection(t, object, 0, u, v)
+}
+
+pub fn face_intersection(
+    t: f64,
+    object: &shape::Shape,
+    face: usize,
+    u: f64,
+    v: f64,
+) -> Intersection<'_> {
     Intersection {
         t,
         object,
-        world_transform: object.transform,
+        material: &object.material,
+        world_transform: *object.transformation_matrix(),
+        world_inverse: *object.inverse_transformation_matrix(),
+        world_inverse_transpose: *object.inverse_transpose_matrix(),
         u,
         v,
+        face,
     }
 }
 
 impl<'a> Intersection<'a> {
     pub fn world_to_object(&self, world_point: tuple::Point) -> tuple::Point {
-        return self.world_transform.inverse().unwrap() * world_point;
+        return self.world_inverse * world_point;
     }
 
     pub fn normal_to_world(&self, object_normal: tuple::Vector) -> tuple::Vector {
-        let mut world_normal = self.world_transform.inverse().unwrap().transpose() * object_normal;
+        let mut world_normal = self.world_inverse_transpose * object_normal;
         // This is sorta a cheat to skip finding the submatrix.
         world_normal.w = 0.0;
         return tuple::normalize(&world_normal);
     }
 
+    /// The texture coordinates the surface carries at the hit, if any.
+    pub fn surface_uv(&self) -> Option<(f64, f64)> {
+        return self.object.surface_uv(self.face, self.u, self.v);
+    }
+
     pub fn normal_at(&self, world_point: tuple::Point) -> tuple::Vector {
         let object_point = self.world_to_object(world_point);
-        let object_normal = self
-            .object
-            .local_normal_at_with_uv(object_point, self.u, self.v);
+        let object_normal =
+            self.object
+                .local_normal_at_hit(object_point, self.face, self.u, self.v);
         return self.normal_to_world(object_normal);
     }
 }
@@ -65,7 +96,9 @@ impl<'a> Intersection<'a> {
 pub struct Computation<'a> {
     pub t: f64,
     pub object: &'a shape::Shape,
+    pub material: &'a material::Material,
     pub world_transform: matrix::Matrix4,
+    pub world_inverse: matrix::Matrix4,
 
     pub point: tuple::Point,
     pub eyev: tuple::Vector,
@@ -74,6 +107,8 @@ pub struct Computation<'a> {
     pub inside: bool,
     pub over_point: tuple::Point,
     pub under_point: tuple::Point,
+    // The surface's own texture coordinates at the hit, if it has any.
+    pub uv: Option<(f64, f64)>,
 
     // refactive indices of either side of the ray-object intersection
     pub n1: f64,
@@ -94,7 +129,9 @@ pub fn prepare_computations<'a>(
     let maybe_inverted_normalv = if inside { -normalv } else { normalv };
     let reflectv = ray.direction.reflect(&maybe_inverted_normalv);
 
-    let mut containers: Vec<&shape::Shape> = vec![];
+    // The objects the ray is inside, with the materials they were hit
+    // with.
+    let mut containers: Vec<(&shape::Shape, &material::Material)> = vec![];
     let mut n1 = 1.0_f64;
     let mut n2 = 1.0_f64;
     for i in intersections.iter() {
@@ -102,22 +139,22 @@ pub fn prepare_computations<'a>(
             if containers.is_empty() {
                 n1 = 1.0;
             } else {
-                n1 = containers.last().unwrap().material.refractive_index;
+                n1 = containers.last().unwrap().1.refractive_index;
             }
         }
 
-        let index_of_hit_object = containers.iter().position(|&x| x == i.object);
-        if index_of_hit_object.is_some() {
-            containers.remove(index_of_hit_object.unwrap());
+        let index_of_hit_object = containers.iter().position(|&(x, _)| x == i.object);
+        if let Some(index) = index_of_hit_object {
+            containers.remove(index);
         } else {
-            containers.push(i.object);
+            containers.push((i.object, i.material));
         }
 
         if i == &hit {
             if containers.is_empty() {
                 n2 = 1.0;
             } else {
-                n2 = containers.last().unwrap().material.refractive_index;
+                n2 = containers.last().unwrap().1.refractive_index;
             }
         }
     }
@@ -125,7 +162,9 @@ pub fn prepare_computations<'a>(
     Computation {
         t,
         object,
+        material: hit.material,
         world_transform: hit.world_transform,
+        world_inverse: hit.world_inverse,
         point,
         eyev,
         normalv: maybe_inverted_normalv,
@@ -133,6 +172,7 @@ pub fn prepare_computations<'a>(
         inside,
         over_point: point + maybe_inverted_normalv * EPSILON,
         under_point: point - maybe_inverted_normalv * EPSILON,
+        uv: hit.surface_uv(),
         n1,
         n2,
     }
@@ -140,27 +180,33 @@ pub fn prepare_computations<'a>(
 
 impl<'a> Computation<'a> {
     pub fn shade_hit(&self, world: &world::World, remaining: usize) -> color::Color {
-        if world.lights.is_empty() {
-            return color::black();
-        }
         let mut surface = color::black();
+        let material = &self.material;
+        let color = lighting::surface_color(material, &self.world_inverse, &self.point, self.uv);
+        // Only the ambient term stands in for light from the sky, so only it
+        // is occluded.
+        let unoccluded = world.ambient_occlusion.as_ref().map_or(1.0, |occlusion| {
+            world.unoccluded_fraction(&self.over_point, &self.normalv, occlusion)
+        });
         for light in world.lights.iter() {
             let intensity = lights::intensity_at(&light, &self.over_point, &world);
             surface = surface
-                + lighting::lighting(
-                    &self.object.material,
-                    &self.world_transform,
-                    &light,
+                + lighting::ambient_lighting(material, color, light) * unoccluded
+                + lighting::direct_lighting(
+                    material,
+                    color,
+                    light,
                     &self.point,
                     &self.eyev,
                     &self.normalv,
                     intensity,
                 );
         }
+        surface = surface + self.material.emission;
         let reflected = world.reflected_color(&self, remaining);
         let refracted = world.refracted_color(&self, remaining);
 
-        if self.object.material.reflective > 0.0 && self.object.material.transparency > 0.0 {
+        if self.material.reflective > 0.0 && self.material.transparency > 0.0 {
             let reflectance = self.reflectance();
             return surface + reflected * reflectance + refracted * (1.0 - reflectance);
         }
@@ -220,7 +266,7 @@ mod intersection_tests {
         let intersection1 = intersection::intersection(1.0, &sphere);
         let intersection2 = intersection::intersection(2.0, &sphere);
 
-        let intersections = vec![intersection1, intersection2];
+        let intersections = [intersection1, intersection2];
 
         assert_eq!(intersections[0].t, 1.0_f64);
         assert_eq!(intersections[1].t, 2.0_f64);
@@ -354,7 +400,7 @@ mod intersection_tests {
             tuple::Point::new(0.0, 0.0, -5.0),
             tuple::Vector::new(0.0, 0.0, 1.0),
         );
-        let shape = &world.shapes[0];
+        let shape = &world.shapes()[0];
         let intersection = intersection::intersection(4.0, &shape);
 
         let computations =
@@ -375,7 +421,7 @@ mod intersection_tests {
             tuple::Point::new(0.0, 0.0, 0.0),
             tuple::Vector::new(0.0, 0.0, 1.0),
         );
-        let shape = &world.shapes[1];
+        let shape = &world.shapes()[1];
         let intersection = intersection::intersection(0.5, &shape);
 
         let computations =
@@ -429,7 +475,7 @@ mod intersection_tests {
         let mut sphere_c = shape::Shape::glass_sphere();
         sphere_c.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 0.25));
         sphere_c.material.refractive_index = 2.5;
-        let intersections = vec![
+        let intersections = [
             intersection::intersection(2.0, &sphere_a),
             intersection::intersection(2.75, &sphere_b),
             intersection::intersection(3.25, &sphere_c),
@@ -479,7 +525,7 @@ mod intersection_tests {
         );
         let mut sphere_a = shape::Shape::glass_sphere();
         sphere_a.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 1.0));
-        let intersections = vec![intersection::intersection(5.0, &sphere_a)];
+        let intersections = [intersection::intersection(5.0, &sphere_a)];
         let xs: Vec<&intersection::Intersection> = intersections.iter().collect();
 
         let computations_intersection =
@@ -496,7 +542,7 @@ mod intersection_tests {
             tuple::Vector::new(0.0, 1.0, 0.0),
         );
         let sphere = shape::Shape::glass_sphere();
-        let intersections = vec![
+        let intersections = [
             intersection::intersection(-2.0_f64.sqrt() / 2.0, &sphere),
             intersection::intersection(2.0_f64.sqrt() / 2.0, &sphere),
         ];
@@ -516,7 +562,7 @@ mod intersection_tests {
             tuple::Vector::new(0.0, 1.0, 0.0),
         );
         let sphere = shape::Shape::glass_sphere();
-        let intersections = vec![
+        let intersections = [
             intersection::intersection(-1.0, &sphere),
             intersection::intersection(1.0, &sphere),
         ];
@@ -536,7 +582,7 @@ mod intersection_tests {
             tuple::Vector::new(0.0, 0.0, 1.0),
         );
         let sphere = shape::Shape::glass_sphere();
-        let intersections = vec![intersection::intersection(1.8589, &sphere)];
+        let intersections = [intersection::intersection(1.8589, &sphere)];
         let xs: Vec<&intersection::Intersection> = intersections.iter().collect();
         let computations_intersection =
             intersection::prepare_computations(&intersections[0], &ray, &xs);
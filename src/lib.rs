@@ -1,6 +1,13 @@
 #![allow(clippy::style)]
+#![allow(
+    clippy::type_complexity,
+    clippy::unnecessary_cast,
+    clippy::manual_is_multiple_of
+)]
+#![cfg_attr(test, allow(clippy::approx_constant))]
 
 pub mod bounds;
+pub mod bvh;
 pub mod camera;
 pub mod canvas;
 pub mod color;
@@ -8,12 +15,14 @@ pub mod lighting;
 pub mod lights;
 pub mod material;
 pub mod matrix;
+pub mod mesh;
 pub mod obj_file;
 pub mod patterns;
 pub mod ray;
 pub mod scene_file;
 pub mod sequences;
 pub mod shape;
+pub mod stereo;
 pub mod transformation;
 pub mod tuple;
 pub mod uv;
@@ -6,42 +6,72 @@ use crate::tuple;
 
 pub fn lighting(
     material: &material::Material,
-    object_to_world: &matrix::Matrix4,
+    world_to_object: &matrix::Matrix4,
     light: &lights::Light,
     point: &tuple::Point,
     camerav: &tuple::Vector,
     normalv: &tuple::Vector,
-    intensity: f64,
+    intensity: color::Color,
 ) -> color::Color {
-    let color = if material.pattern.is_some() {
-        material
-            .pattern
-            .as_ref()
-            .unwrap()
-            .pattern_at_object(object_to_world, &point)
-    } else {
-        material.color
-    };
+    let color = surface_color(material, world_to_object, point, None);
 
-    // combine the surface color with the light's color/intensity
-    let effective_color = color * light.intensity;
+    return ambient_lighting(material, color, light)
+        + direct_lighting(material, color, light, point, camerav, normalv, intensity);
+}
 
-    // compute the ambient contribution once, outside the sample loop; it is
-    // never scaled by intensity or averaged over samples
-    let ambient = effective_color * material.ambient;
+/// The flat light `light` adds to a surface of `color` everywhere, lit or
+/// in shadow. It is never scaled by intensity or averaged over samples.
+pub fn ambient_lighting(
+    material: &material::Material,
+    color: color::Color,
+    light: &lights::Light,
+) -> color::Color {
+    return color * light.intensity * material.ambient;
+}
 
-    let (usteps, vsteps) = match &light.kind {
-        lights::LightKind::Point | lights::LightKind::Spot { .. } => (1, 1),
-        lights::LightKind::Area { usteps, vsteps, .. } => (*usteps, *vsteps),
-    };
+/// The material's color at `point`: its pattern's if it has one.
+/// `surface_uv` is the surface's own texture coordinates there, if it has
+/// any, for a pattern mapped onto them.
+pub fn surface_color(
+    material: &material::Material,
+    world_to_object: &matrix::Matrix4,
+    point: &tuple::Point,
+    surface_uv: Option<(f64, f64)>,
+) -> color::Color {
+    if let Some(pattern) = &material.pattern {
+        return pattern.pattern_at_surface(world_to_object, point, surface_uv);
+    }
+    return material.color;
+}
+
+/// The diffuse and specular light `light` casts on a surface of `color`
+/// at `point`: `lighting` without the ambient term.
+pub fn direct_lighting(
+    material: &material::Material,
+    color: color::Color,
+    light: &lights::Light,
+    point: &tuple::Point,
+    camerav: &tuple::Vector,
+    normalv: &tuple::Vector,
+    intensity: color::Color,
+) -> color::Color {
+    // combine the surface color with the light's color/intensity
+    let light_color = light.color_at(point);
+    let effective_color = color * light_color;
+
+    let (usteps, vsteps) = light.sample_grid();
 
     let mut sum = color::black();
     for v in 0..vsteps {
         for u in 0..usteps {
-            let light_position = lights::point_on_light(light, u, v);
-
-            // find the direction to this light sample
-            let lightv = tuple::normalize(&(light_position - *point));
+            // find the direction to this light sample, and how much of the
+            // light is left by the time it gets here
+            let (lightv, distance) = lights::towards_light(light, point, u, v);
+            let falloff = if distance.is_finite() {
+                light.falloff.factor(distance)
+            } else {
+                1.0
+            };
 
             // light_dot_normal represents the cosine of the angle between the
             // light vector and the normal vector. A negative number means the
@@ -52,7 +82,7 @@ pub fn lighting(
             }
 
             // accumulate the diffuse contribution
-            sum = sum + effective_color * material.diffuse * light_dot_normal;
+            sum = sum + effective_color * falloff * material.diffuse * light_dot_normal;
 
             // reflect_dot_camera represents the cosine of the angle between
             // the light reflects away from the camera.
@@ -64,10 +94,10 @@ pub fn lighting(
 
             // accumulate the specular contribution
             let factor = reflect_dot_camera.powf(material.shininess);
-            sum = sum + light.intensity * material.specular * factor;
+            sum = sum + light_color * falloff * material.specular * factor;
         }
     }
 
     let samples = (usteps * vsteps) as f64;
-    return ambient + sum * (intensity / samples);
+    return sum * (intensity / samples);
 }
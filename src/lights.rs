@@ -1,6 +1,7 @@
 use crate::color;
 use crate::sequences;
 use crate::tuple;
+use crate::uv;
 use crate::world;
 
 pub enum LightKind {
@@ -21,33 +22,145 @@ pub enum LightKind {
         /// Half-angle (radians) beyond which the light contributes nothing.
         fade_angle: f64,
     },
+    /// A light that projects a picture, like a slide projector or a gobo
+    /// in front of a spotlight: each point in its frustum takes the light's
+    /// intensity tinted by the part of the picture that lands on it.
+    Projector {
+        /// Normalized direction the projector faces, away from `position`.
+        forward: tuple::Vector,
+        /// Normalized directions of the picture's right and top edges.
+        right: tuple::Vector,
+        up: tuple::Vector,
+        /// Tangents of the half-angles the picture spans across and up.
+        half_width: f64,
+        half_height: f64,
+        pattern: uv::UvPattern,
+    },
+    /// A ball of light, like a bulb, centered on `position`.
+    Sphere {
+        radius: f64,
+        /// Shadow rays per point, spread over the ball's surface.
+        samples: usize,
+        jitter: sequences::Sequence,
+    },
+    /// A flat round light, like a softbox, centered on `position`.
+    Disk {
+        /// Normalized direction the disk faces. It shines both ways.
+        normal: tuple::Vector,
+        radius: f64,
+        /// Shadow rays per point, spread over the disk.
+        samples: usize,
+        jitter: sequences::Sequence,
+    },
+    /// A light infinitely far away, like the sun, whose rays all arrive
+    /// parallel. It has no position.
+    Directional {
+        /// Normalized direction the light travels, towards the scene.
+        direction: tuple::Vector,
+        /// Angle (radians) the light's disk spans in the sky; 0.0 for hard
+        /// shadows.
+        angular_diameter: f64,
+        /// Shadow rays per point, spread across the disk.
+        samples: usize,
+        jitter: sequences::Sequence,
+    },
+}
+
+/// Turn (radians) between successive samples of a round light, spreading
+/// them evenly in a sunflower spiral.
+const GOLDEN_ANGLE: f64 = 2.399_963_229_728_653;
+
+/// The nearest a point is taken to be to a light when working out its
+/// falloff, so a point at the light itself isn't infinitely bright.
+const MIN_FALLOFF_DISTANCE: f64 = 1e-3;
+
+/// How a light dims with distance from it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Falloff {
+    /// Full intensity at any distance.
+    None,
+    /// Intensity divided by the distance.
+    Linear,
+    /// Intensity divided by the distance squared, as real lights dim.
+    InverseSquare,
+    /// Intensity divided by `constant + linear * d + quadratic * d * d`
+    /// at distance `d`.
+    Coefficients {
+        constant: f64,
+        linear: f64,
+        quadratic: f64,
+    },
+}
+
+impl Falloff {
+    /// The fraction of a light's intensity left `distance` away from it.
+    pub fn factor(&self, distance: f64) -> f64 {
+        match *self {
+            Falloff::None => 1.0,
+            Falloff::Linear => 1.0 / distance.max(MIN_FALLOFF_DISTANCE),
+            Falloff::InverseSquare => 1.0 / distance.max(MIN_FALLOFF_DISTANCE).powi(2),
+            Falloff::Coefficients {
+                constant,
+                linear,
+                quadratic,
+            } => {
+                let distance = distance.max(MIN_FALLOFF_DISTANCE);
+                1.0 / (constant + linear * distance + quadratic * distance * distance)
+            }
+        }
+    }
 }
 
 pub struct Light {
     pub position: tuple::Point,
     pub intensity: color::Color,
     pub kind: LightKind,
+    /// Ignored by a directional light, which is equally far from
+    /// everything.
+    pub falloff: Falloff,
 }
 
 impl Light {
     pub fn samples(&self) -> usize {
+        let (usteps, vsteps) = self.sample_grid();
+        return usteps * vsteps;
+    }
+
+    /// How the light's samples are numbered for `point_on_light`: `u` runs
+    /// over the first count and `v` over the second. Only a rectangular
+    /// area light has more than one row.
+    pub fn sample_grid(&self) -> (usize, usize) {
         match self.kind {
-            LightKind::Point | LightKind::Spot { .. } => 1,
-            LightKind::Area { usteps, vsteps, .. } => usteps * vsteps,
+            LightKind::Point | LightKind::Spot { .. } | LightKind::Projector { .. } => (1, 1),
+            LightKind::Area { usteps, vsteps, .. } => (usteps, vsteps),
+            LightKind::Sphere { samples, .. }
+            | LightKind::Disk { samples, .. }
+            | LightKind::Directional { samples, .. } => (samples, 1),
         }
     }
 
     pub fn set_jitter(&mut self, jitter_by: sequences::Sequence) {
-        if let LightKind::Area { jitter, .. } = &mut self.kind {
-            *jitter = jitter_by;
+        match &mut self.kind {
+            LightKind::Area { jitter, .. }
+            | LightKind::Sphere { jitter, .. }
+            | LightKind::Disk { jitter, .. }
+            | LightKind::Directional { jitter, .. } => {
+                *jitter = jitter_by;
+            }
+            LightKind::Point | LightKind::Spot { .. } | LightKind::Projector { .. } => {}
         }
     }
 
     /// The fraction of this light's intensity that reaches `point`,
-    /// ignoring shadowing: only a spotlight's cone attenuates it.
+    /// ignoring shadowing: only a spotlight's cone and a projector's
+    /// frustum attenuate it.
     pub fn attenuation_at(&self, point: &tuple::Point) -> f64 {
         match &self.kind {
-            LightKind::Point | LightKind::Area { .. } => 1.0,
+            LightKind::Point
+            | LightKind::Area { .. }
+            | LightKind::Sphere { .. }
+            | LightKind::Disk { .. }
+            | LightKind::Directional { .. } => 1.0,
             LightKind::Spot {
                 direction,
                 cone_angle,
@@ -65,7 +178,53 @@ impl Light {
                     (fade_angle - angle) / (fade_angle - cone_angle)
                 }
             }
+            LightKind::Projector { .. } => match self.projected_uv(point) {
+                Some(_) => 1.0,
+                None => 0.0,
+            },
+        }
+    }
+
+    /// The color of the light arriving at `point`, ignoring shadowing and
+    /// falloff: its intensity, tinted by the picture for a projector.
+    pub fn color_at(&self, point: &tuple::Point) -> color::Color {
+        if let LightKind::Projector { pattern, .. } = &self.kind {
+            return match self.projected_uv(point) {
+                Some((u, v)) => self.intensity * pattern.uv_pattern_at(u, v),
+                None => color::black(),
+            };
+        }
+        return self.intensity;
+    }
+
+    // Where `point` lands on a projector's picture, or `None` if it is
+    // outside the projector's frustum or the light isn't a projector.
+    fn projected_uv(&self, point: &tuple::Point) -> Option<(f64, f64)> {
+        let LightKind::Projector {
+            forward,
+            right,
+            up,
+            half_width,
+            half_height,
+            ..
+        } = &self.kind
+        else {
+            return None;
+        };
+        let to_point = *point - self.position;
+        let depth = tuple::dot(&to_point, forward);
+        if depth <= 0.0 {
+            return None;
+        }
+        // Project onto the picture one unit in front of the projector.
+        let x = tuple::dot(&to_point, right) / depth;
+        let y = tuple::dot(&to_point, up) / depth;
+        let u = 0.5 + x / (2.0 * half_width);
+        let v = 0.5 + y / (2.0 * half_height);
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
         }
+        return Some((u, v));
     }
 }
 
@@ -74,6 +233,7 @@ pub fn point_light(position: tuple::Point, intensity: color::Color) -> Light {
         position,
         intensity,
         kind: LightKind::Point,
+        falloff: Falloff::None,
     }
 }
 
@@ -99,6 +259,7 @@ pub fn area_light(
             vsteps,
             jitter: sequences::Sequence::constant(0.5),
         },
+        falloff: Falloff::None,
     }
 }
 
@@ -119,12 +280,117 @@ pub fn spot_light(
             cone_angle,
             fade_angle,
         },
+        falloff: Falloff::None,
+    }
+}
+
+/// A light at `position` projecting `pattern` towards `to`, with the top
+/// of the picture towards `up`. The picture spans `field_of_view` radians
+/// across and is `aspect` times as wide as it is tall.
+pub fn projector_light(
+    position: tuple::Point,
+    to: tuple::Point,
+    up: tuple::Vector,
+    field_of_view: f64,
+    aspect: f64,
+    pattern: uv::UvPattern,
+    intensity: color::Color,
+) -> Light {
+    let forward = tuple::normalize(&(to - position));
+    let right = tuple::normalize(&tuple::cross(&up, &forward));
+    let up = tuple::cross(&forward, &right);
+    let half_width = (field_of_view / 2.0).tan();
+    Light {
+        position,
+        intensity,
+        kind: LightKind::Projector {
+            forward,
+            right,
+            up,
+            half_width,
+            half_height: half_width / aspect,
+            pattern,
+        },
+        falloff: Falloff::None,
+    }
+}
+
+/// A ball of light of `radius` around `center`, with soft shadows from
+/// `samples` shadow rays per point spread over its surface.
+pub fn sphere_light(
+    center: tuple::Point,
+    radius: f64,
+    samples: usize,
+    intensity: color::Color,
+) -> Light {
+    Light {
+        position: center,
+        intensity,
+        kind: LightKind::Sphere {
+            radius,
+            samples,
+            jitter: sequences::Sequence::constant(0.5),
+        },
+        falloff: Falloff::None,
+    }
+}
+
+/// A disk of light of `radius` around `center`, facing `normal`, with soft
+/// shadows from `samples` shadow rays per point spread across it.
+pub fn disk_light(
+    center: tuple::Point,
+    normal: tuple::Vector,
+    radius: f64,
+    samples: usize,
+    intensity: color::Color,
+) -> Light {
+    Light {
+        position: center,
+        intensity,
+        kind: LightKind::Disk {
+            normal: tuple::normalize(&normal),
+            radius,
+            samples,
+            jitter: sequences::Sequence::constant(0.5),
+        },
+        falloff: Falloff::None,
+    }
+}
+
+/// A light at infinity shining along `direction`, casting hard shadows.
+pub fn directional_light(direction: tuple::Vector, intensity: color::Color) -> Light {
+    return sun_light(direction, 0.0, 1, intensity);
+}
+
+/// A light at infinity shining along `direction` from a disk that spans
+/// `angular_diameter` radians of sky, with soft shadows from `samples`
+/// shadow rays per point spread across the disk.
+pub fn sun_light(
+    direction: tuple::Vector,
+    angular_diameter: f64,
+    samples: usize,
+    intensity: color::Color,
+) -> Light {
+    Light {
+        // Unused: a light at infinity has no position.
+        position: tuple::Point::new(0.0, 0.0, 0.0),
+        intensity,
+        kind: LightKind::Directional {
+            direction: tuple::normalize(&direction),
+            angular_diameter,
+            samples,
+            jitter: sequences::Sequence::constant(0.5),
+        },
+        falloff: Falloff::None,
     }
 }
 
 pub fn point_on_light(light: &Light, u: usize, v: usize) -> tuple::Point {
     match &light.kind {
-        LightKind::Point | LightKind::Spot { .. } => light.position,
+        LightKind::Point
+        | LightKind::Spot { .. }
+        | LightKind::Projector { .. }
+        | LightKind::Directional { .. } => light.position,
         LightKind::Area {
             corner,
             uvec,
@@ -132,36 +398,127 @@ pub fn point_on_light(light: &Light, u: usize, v: usize) -> tuple::Point {
             jitter,
             ..
         } => *corner + *uvec * (u as f64 + jitter.next()) + *vvec * (v as f64 + jitter.next()),
+        LightKind::Sphere {
+            radius,
+            samples,
+            jitter,
+        } => {
+            // Equal bands of height cover equal areas of a sphere, so
+            // stepping the height evenly spreads the samples evenly.
+            let z = 1.0 - 2.0 * (u as f64 + jitter.next()) / *samples as f64;
+            let angle = u as f64 * GOLDEN_ANGLE + 2.0 * std::f64::consts::PI * jitter.next();
+            let ring = (1.0 - z * z).max(0.0).sqrt();
+            light.position + tuple::Vector::new(ring * angle.cos(), ring * angle.sin(), z) * *radius
+        }
+        LightKind::Disk {
+            normal,
+            radius,
+            samples,
+            jitter,
+        } => {
+            let (fraction, angle) = spiral_sample(u, *samples, jitter);
+            let (tangent, bitangent) = perpendicular_axes(normal);
+            light.position
+                + (tangent * angle.cos() + bitangent * angle.sin()) * (*radius * fraction)
+        }
     }
 }
 
-pub fn intensity_at(light: &Light, point: &tuple::Point, world: &world::World) -> f64 {
-    match light.kind {
-        LightKind::Point => {
-            if world::is_shadowed(world, &light.position, point) {
-                0.0
-            } else {
-                1.0
+// Sample `u` of `samples` spread over a unit disk in a sunflower spiral,
+// jittered: its distance from the center and its angle.
+fn spiral_sample(u: usize, samples: usize, jitter: &sequences::Sequence) -> (f64, f64) {
+    let fraction = ((u as f64 + jitter.next()) / samples as f64).sqrt();
+    let angle = u as f64 * GOLDEN_ANGLE + 2.0 * std::f64::consts::PI * jitter.next();
+    return (fraction, angle);
+}
+
+// Two unit vectors perpendicular to `normal` and each other.
+fn perpendicular_axes(normal: &tuple::Vector) -> (tuple::Vector, tuple::Vector) {
+    let helper = if normal.x.abs() > 0.9 {
+        tuple::Vector::new(0.0, 1.0, 0.0)
+    } else {
+        tuple::Vector::new(1.0, 0.0, 0.0)
+    };
+    let tangent = tuple::normalize(&tuple::cross(&helper, normal));
+    let bitangent = tuple::cross(normal, &tangent);
+    return (tangent, bitangent);
+}
+
+/// The normalized direction from `point` towards sample `u`, `v` of
+/// `light`: towards the sampled point for lights with a position, or
+/// against the light's direction, tilted to a point on its disk, for a
+/// light at infinity.
+pub fn direction_to_light(
+    light: &Light,
+    point: &tuple::Point,
+    u: usize,
+    v: usize,
+) -> tuple::Vector {
+    return towards_light(light, point, u, v).0;
+}
+
+/// `direction_to_light`, along with how far away the sample is: infinitely
+/// far for a light at infinity.
+pub fn towards_light(
+    light: &Light,
+    point: &tuple::Point,
+    u: usize,
+    v: usize,
+) -> (tuple::Vector, f64) {
+    match &light.kind {
+        LightKind::Directional {
+            direction,
+            angular_diameter,
+            samples,
+            jitter,
+        } => {
+            let towards = -*direction;
+            if *angular_diameter == 0.0 {
+                return (towards, f64::INFINITY);
             }
+            // The disk's radius on a plane one unit away, facing the light.
+            let disk_radius = (angular_diameter / 2.0).tan();
+            let (fraction, angle) = spiral_sample(u, *samples, jitter);
+            let radius = disk_radius * fraction;
+            let (tangent, bitangent) = perpendicular_axes(&towards);
+            let tilted =
+                towards + tangent * (radius * angle.cos()) + bitangent * (radius * angle.sin());
+            return (tuple::normalize(&tilted), f64::INFINITY);
         }
-        LightKind::Area { usteps, vsteps, .. } => {
-            let mut total = 0.0;
+        _ => {
+            let to_light = point_on_light(light, u, v) - *point;
+            (tuple::normalize(&to_light), tuple::magnitude(&to_light))
+        }
+    }
+}
+
+/// How much of `light` reaches `point`, channel by channel, averaged over
+/// the light's samples: shadows, and a spotlight's cone or a projector's
+/// frustum, dim it. Light through transparent objects comes out tinted.
+pub fn intensity_at(light: &Light, point: &tuple::Point, world: &world::World) -> color::Color {
+    match light.kind {
+        LightKind::Point => world::transmittance(world, &light.position, point),
+        LightKind::Area { .. } | LightKind::Sphere { .. } | LightKind::Disk { .. } => {
+            let (usteps, vsteps) = light.sample_grid();
+            let mut total = color::black();
             for v in 0..vsteps {
                 for u in 0..usteps {
                     let light_position = point_on_light(light, u, v);
-                    if !world::is_shadowed(world, &light_position, point) {
-                        total += 1.0;
-                    }
+                    total = total + world::transmittance(world, &light_position, point);
                 }
             }
             total / light.samples() as f64
         }
-        LightKind::Spot { .. } => {
-            if world::is_shadowed(world, &light.position, point) {
-                0.0
-            } else {
-                light.attenuation_at(point)
+        LightKind::Spot { .. } | LightKind::Projector { .. } => {
+            world::transmittance(world, &light.position, point) * light.attenuation_at(point)
+        }
+        LightKind::Directional { samples, .. } => {
+            let mut total = color::black();
+            for u in 0..samples {
+                let direction = direction_to_light(light, point, u, 0);
+                total = total + world::transmittance_towards(world, point, &direction);
             }
+            total / samples as f64
         }
     }
 }
@@ -170,11 +527,13 @@ pub fn intensity_at(light: &Light, point: &tuple::Point, world: &world::World) -
 mod lights_tests {
     use std::f64::consts::PI;
 
+    use crate::assert_color_approx_eq;
     use crate::assert_tuple_approx_eq;
     use crate::color;
     use crate::lights;
     use crate::sequences;
     use crate::tuple;
+    use crate::uv;
     use crate::world;
 
     #[test]
@@ -205,13 +564,7 @@ mod lights_tests {
 
         for (point, expected) in cases {
             let got = lights::intensity_at(light, &point, &w);
-            assert!(
-                (got - expected).abs() < 1e-5,
-                "intensity_at({:?}) = {}, want {}",
-                point,
-                got,
-                expected
-            );
+            assert_color_approx_eq!(got, color::white() * expected);
         }
     }
 
@@ -282,13 +635,7 @@ mod lights_tests {
 
         for (point, expected) in cases {
             let got = lights::intensity_at(&light, &point, &w);
-            assert!(
-                (got - expected).abs() < 1e-5,
-                "intensity_at({:?}) = {}, want {}",
-                point,
-                got,
-                expected
-            );
+            assert_color_approx_eq!(got, color::white() * expected);
         }
     }
 
@@ -335,13 +682,7 @@ mod lights_tests {
             light.set_jitter(sequences::sequence(&[0.7, 0.3, 0.9, 0.1, 0.5]));
 
             let got = lights::intensity_at(&light, &point, &w);
-            assert!(
-                (got - expected).abs() < 1e-5,
-                "intensity_at({:?}) = {}, want {}",
-                point,
-                got,
-                expected
-            );
+            assert_color_approx_eq!(got, color::white() * expected);
         }
     }
 
@@ -448,13 +789,245 @@ mod lights_tests {
 
         for (point, expected) in cases {
             let got = lights::intensity_at(&light, &point, &w);
-            assert!(
-                (got - expected).abs() < 1e-5,
-                "intensity_at({:?}) = {}, want {}",
-                point,
-                got,
-                expected
+            assert_color_approx_eq!(got, color::white() * expected);
+        }
+    }
+
+    #[test]
+    fn test_a_directional_light_shines_from_the_opposite_way_everywhere() {
+        let light = lights::directional_light(tuple::Vector::new(0.0, -2.0, 0.0), color::white());
+
+        for point in [
+            tuple::Point::new(0.0, 0.0, 0.0),
+            tuple::Point::new(100.0, -50.0, 3.0),
+        ] {
+            assert_eq!(
+                lights::direction_to_light(&light, &point, 0, 0),
+                tuple::Vector::new(0.0, 1.0, 0.0)
             );
         }
+        assert_eq!(light.samples(), 1);
+    }
+
+    #[test]
+    fn test_a_directional_light_is_shadowed_from_any_distance() {
+        let w = world::default_world();
+        let light = lights::directional_light(tuple::Vector::new(0.0, -1.0, 0.0), color::white());
+
+        let cases = [
+            (tuple::Point::new(0.0, -1.0001, 0.0), 0.0),
+            (tuple::Point::new(0.0, -1000.0, 0.0), 0.0),
+            (tuple::Point::new(1.5, -1000.0, 0.0), 1.0),
+            (tuple::Point::new(0.0, 1.0001, 0.0), 1.0),
+        ];
+
+        for (point, expected) in cases {
+            let got = lights::intensity_at(&light, &point, &w);
+            assert_eq!(got, color::white() * expected, "intensity_at({:?})", point);
+        }
+    }
+
+    #[test]
+    fn test_a_sun_light_samples_directions_across_its_disk() {
+        let light = lights::sun_light(
+            tuple::Vector::new(0.0, -1.0, 0.0),
+            PI / 6.0,
+            16,
+            color::white(),
+        );
+        let up = tuple::Vector::new(0.0, 1.0, 0.0);
+        let point = tuple::Point::new(0.0, 0.0, 0.0);
+
+        let directions: Vec<tuple::Vector> = (0..16)
+            .map(|u| lights::direction_to_light(&light, &point, u, 0))
+            .collect();
+
+        for direction in &directions {
+            assert_approx_eq::assert_approx_eq!(tuple::magnitude(direction), 1.0);
+            assert!(tuple::dot(direction, &up).acos() <= PI / 12.0 + 1e-9);
+        }
+        assert!(directions.iter().any(|direction| direction.x < -0.1));
+        assert!(directions.iter().any(|direction| direction.x > 0.1));
+    }
+
+    #[test]
+    fn test_a_sun_light_casts_soft_shadow_edges() {
+        let w = world::default_world();
+        let light = lights::sun_light(
+            tuple::Vector::new(0.0, -1.0, 0.0),
+            PI / 6.0,
+            16,
+            color::white(),
+        );
+
+        // Straight below the edge of the sphere, part of the sun's disk is
+        // hidden.
+        let got = lights::intensity_at(&light, &tuple::Point::new(1.0, -3.0, 0.0), &w);
+
+        assert!(got.r > 0.0 && got.r < 1.0, "intensity_at = {:?}", got);
+    }
+
+    #[test]
+    fn test_points_on_a_sphere_light_lie_on_its_surface() {
+        let center = tuple::Point::new(1.0, 2.0, 3.0);
+        let mut light = lights::sphere_light(center, 0.5, 8, color::white());
+        light.set_jitter(sequences::sequence(&[0.2, 0.9, 0.6, 0.4]));
+
+        let points: Vec<tuple::Point> = (0..8)
+            .map(|u| lights::point_on_light(&light, u, 0))
+            .collect();
+
+        assert_eq!(light.samples(), 8);
+        for point in &points {
+            assert_approx_eq::assert_approx_eq!(tuple::magnitude(&(*point - center)), 0.5);
+        }
+        // The samples reach both ends of the sphere.
+        assert!(points.iter().any(|point| point.z > 3.25));
+        assert!(points.iter().any(|point| point.z < 2.75));
+    }
+
+    #[test]
+    fn test_points_on_a_disk_light_lie_on_the_disk() {
+        let center = tuple::Point::new(0.0, 5.0, 0.0);
+        let normal = tuple::Vector::new(0.0, -1.0, 0.0);
+        let mut light = lights::disk_light(center, normal, 2.0, 8, color::white());
+        light.set_jitter(sequences::sequence(&[0.2, 0.9, 0.6, 0.4]));
+
+        for u in 0..8 {
+            let point = lights::point_on_light(&light, u, 0);
+            assert_approx_eq::assert_approx_eq!(point.y, 5.0);
+            assert!(tuple::magnitude(&(point - center)) <= 2.0 + 1e-9);
+        }
+        // Later samples sit farther out along the spiral.
+        let first = lights::point_on_light(&light, 0, 0);
+        let last = lights::point_on_light(&light, 7, 0);
+        assert!(tuple::magnitude(&(last - center)) > tuple::magnitude(&(first - center)));
+    }
+
+    #[test]
+    fn test_a_round_light_is_partly_hidden_at_the_edge_of_a_shadow() {
+        let w = world::default_world();
+        let sphere =
+            lights::sphere_light(tuple::Point::new(0.0, 5.0, 0.0), 1.0, 16, color::white());
+        let disk = lights::disk_light(
+            tuple::Point::new(0.0, 5.0, 0.0),
+            tuple::Vector::new(0.0, -1.0, 0.0),
+            1.0,
+            16,
+            color::white(),
+        );
+
+        for light in [sphere, disk] {
+            let hidden = lights::intensity_at(&light, &tuple::Point::new(0.0, -1.0001, 0.0), &w);
+            let edge = lights::intensity_at(&light, &tuple::Point::new(1.2, -1.0, 0.0), &w);
+            let lit = lights::intensity_at(&light, &tuple::Point::new(0.0, 1.0001, 0.0), &w);
+
+            assert_eq!(hidden, color::black());
+            assert!(edge.r > 0.0 && edge.r < 1.0, "intensity_at = {:?}", edge);
+            assert_eq!(lit, color::white());
+        }
+    }
+
+    #[test]
+    fn test_falloff_dims_a_light_with_distance() {
+        let coefficients = lights::Falloff::Coefficients {
+            constant: 1.0,
+            linear: 0.5,
+            quadratic: 0.25,
+        };
+        let nearest = lights::MIN_FALLOFF_DISTANCE;
+
+        let cases = [
+            (lights::Falloff::None, 4.0, 1.0),
+            (lights::Falloff::Linear, 4.0, 0.25),
+            (lights::Falloff::InverseSquare, 4.0, 0.0625),
+            (
+                coefficients,
+                0.0,
+                1.0 / (1.0 + 0.5 * nearest + 0.25 * nearest * nearest),
+            ),
+            (coefficients, 2.0, 1.0 / 3.0),
+        ];
+
+        for (falloff, distance, expected) in cases {
+            assert_approx_eq::assert_approx_eq!(falloff.factor(distance), expected);
+        }
+
+        // At the light itself the intensity stays finite.
+        assert!(lights::Falloff::Linear.factor(0.0).is_finite());
+        assert!(lights::Falloff::InverseSquare.factor(0.0).is_finite());
+    }
+
+    #[test]
+    fn test_quadratic_falloff_stays_finite_at_the_light() {
+        let quadratic = lights::Falloff::Coefficients {
+            constant: 0.0,
+            linear: 0.0,
+            quadratic: 1.0,
+        };
+
+        let factor = quadratic.factor(0.0);
+
+        assert!(factor.is_finite());
+        assert_eq!(factor, 1.0 / (lights::MIN_FALLOFF_DISTANCE.powi(2)));
+    }
+
+    #[test]
+    fn test_lights_start_without_falloff() {
+        let light = lights::point_light(tuple::Point::new(0.0, 0.0, 0.0), color::white());
+
+        assert_eq!(light.falloff, lights::Falloff::None);
+    }
+
+    // A projector at the origin facing +z, casting a 2x2 checkerboard of
+    // black and white squares 90 degrees wide and 45 degrees tall.
+    fn checkered_projector() -> lights::Light {
+        return lights::projector_light(
+            tuple::Point::new(0.0, 0.0, 0.0),
+            tuple::Point::new(0.0, 0.0, 1.0),
+            tuple::Vector::new(0.0, 1.0, 0.0),
+            PI / 2.0,
+            2.0,
+            uv::UvPattern::checkers(2, 2, color::black(), color::white()),
+            color::color(1.0, 0.5, 0.5),
+        );
+    }
+
+    #[test]
+    fn test_a_projector_tints_its_light_by_the_picture() {
+        let light = checkered_projector();
+
+        // Looking along +z with +y up, +x is to the right.
+        let cases = [
+            (tuple::Point::new(-1.0, -0.25, 2.0), color::black()),
+            (
+                tuple::Point::new(1.0, -0.25, 2.0),
+                color::color(1.0, 0.5, 0.5),
+            ),
+            (
+                tuple::Point::new(-1.0, 0.25, 2.0),
+                color::color(1.0, 0.5, 0.5),
+            ),
+            (tuple::Point::new(1.0, 0.25, 2.0), color::black()),
+        ];
+
+        for (point, expected) in cases {
+            assert_eq!(light.color_at(&point), expected, "color_at({:?})", point);
+            assert_eq!(light.attenuation_at(&point), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_a_projector_casts_nothing_outside_its_frustum() {
+        let light = checkered_projector();
+
+        for point in [
+            tuple::Point::new(3.0, 0.0, 2.0),
+            tuple::Point::new(0.0, 1.0, 1.0),
+            tuple::Point::new(0.0, 0.0, -2.0),
+        ] {
+            assert_eq!(light.color_at(&point), color::black(), "{:?}", point);
+            assert_eq!(light.attenuation_at(&point), 0.0, "{:?}", point);
+        }
     }
 }
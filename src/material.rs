@@ -12,6 +12,9 @@ pub struct Material {
     pub reflective: f64,
     pub refractive_index: f64,
     pub pattern: Option<patterns::Pattern>,
+    /// Light the surface gives off itself, whether or not anything lights
+    /// it.
+    pub emission: color::Color,
 }
 
 pub fn material() -> Material {
@@ -25,6 +28,7 @@ pub fn material() -> Material {
         reflective: 0.0_f64,
         refractive_index: 1.0_f64,
         pattern: None,
+        emission: color::black(),
     }
 }
 
@@ -56,6 +60,7 @@ mod material_tests {
         assert_eq!(material.specular, 0.9);
         assert_eq!(material.shininess, 200.0);
         assert_eq!(material.reflective, 0.0);
+        assert_eq!(material.emission, color::black());
     }
 
     ///               ║
@@ -75,12 +80,12 @@ mod material_tests {
 
         let result = lighting::lighting(
             &material,
-            &object.transform,
+            object.inverse_transformation_matrix(),
             &light,
             &position,
             &camera,
             &normal,
-            1.0,
+            color::white(),
         );
 
         let expected = color::color(1.9, 1.9, 1.9);
@@ -107,12 +112,12 @@ mod material_tests {
 
         let result = lighting::lighting(
             &material,
-            &object.transform,
+            object.inverse_transformation_matrix(),
             &light,
             &position,
             &camera,
             &normal,
-            1.0,
+            color::white(),
         );
 
         let expected = color::color(1.0, 1.0, 1.0);
@@ -139,12 +144,12 @@ mod material_tests {
 
         let result = lighting::lighting(
             &material,
-            &object.transform,
+            object.inverse_transformation_matrix(),
             &light,
             &position,
             &camera,
             &normal,
-            1.0,
+            color::white(),
         );
 
         let expected = color::color(0.7364, 0.7364, 0.7364);
@@ -172,12 +177,12 @@ mod material_tests {
 
         let result = lighting::lighting(
             &material,
-            &object.transform,
+            object.inverse_transformation_matrix(),
             &light,
             &position,
             &camera,
             &normal,
-            1.0,
+            color::white(),
         );
 
         let expected = color::color(1.6364, 1.6364, 1.6364);
@@ -201,12 +206,12 @@ mod material_tests {
 
         let result = lighting::lighting(
             &material,
-            &object.transform,
+            object.inverse_transformation_matrix(),
             &light,
             &position,
             &camera,
             &normal,
-            1.0,
+            color::white(),
         );
 
         let expected = color::color(0.1, 0.1, 0.1);
@@ -224,11 +229,11 @@ mod material_tests {
             tuple::Point::new(0.0, 0.0, -10.0),
             color::color(1.0, 1.0, 1.0),
         );
-        let intensity = 0.0;
+        let intensity = color::black();
 
         let result = lighting::lighting(
             &material,
-            &object.transform,
+            object.inverse_transformation_matrix(),
             &light,
             &position,
             &camera,
@@ -254,15 +259,17 @@ mod material_tests {
         let normalv = tuple::Vector::new(0.0, 0.0, -1.0);
 
         let cases = [
-            (1.0, color::color(1.0, 1.0, 1.0)),
-            (0.5, color::color(0.55, 0.55, 0.55)),
-            (0.0, color::color(0.1, 0.1, 0.1)),
+            (color::white(), color::color(1.0, 1.0, 1.0)),
+            (color::color(0.5, 0.5, 0.5), color::color(0.55, 0.55, 0.55)),
+            (color::black(), color::color(0.1, 0.1, 0.1)),
+            // light tinted by colored glass on its way here
+            (color::color(1.0, 0.5, 0.0), color::color(1.0, 0.55, 0.1)),
         ];
 
         for (intensity, expected) in cases {
             let result = lighting::lighting(
                 &material,
-                &object.transform,
+                object.inverse_transformation_matrix(),
                 &light,
                 &position,
                 &eyev,
@@ -292,21 +299,21 @@ mod material_tests {
 
         let result1 = lighting::lighting(
             &material,
-            &object.transform,
+            object.inverse_transformation_matrix(),
             &light,
             &position1,
             &camera,
             &normal,
-            1.0,
+            color::white(),
         );
         let result2 = lighting::lighting(
             &material,
-            &object.transform,
+            object.inverse_transformation_matrix(),
             &light,
             &position2,
             &camera,
             &normal,
-            1.0,
+            color::white(),
         );
         assert_color_approx_eq!(color::color(1.0, 1.0, 1.0), result1);
         assert_color_approx_eq!(color::color(0.0, 0.0, 0.0), result2);
@@ -344,12 +351,12 @@ mod material_tests {
 
             let result = lighting::lighting(
                 &material,
-                &object.transform,
+                object.inverse_transformation_matrix(),
                 &light,
                 &point,
                 &eyev,
                 &normalv,
-                1.0,
+                color::white(),
             );
 
             // The book's expected values are only given to 4 decimal
@@ -366,6 +373,68 @@ mod material_tests {
         }
     }
 
+    ///               ║
+    ///  ⇐ ⇐    C   ←-║
+    ///               ║
+    #[test]
+    fn test_lighting_with_a_directional_light_shining_at_the_surface() {
+        let material = material::material();
+        let object = shape::Shape::default_sphere();
+        let position = tuple::Point::new(0.0, 0.0, 0.0);
+        let camera = tuple::Vector::new(0.0, 0.0, -1.0);
+        let normal = tuple::Vector::new(0.0, 0.0, -1.0);
+        let light = lights::directional_light(
+            tuple::Vector::new(0.0, 0.0, 3.0),
+            color::color(1.0, 1.0, 1.0),
+        );
+
+        let result = lighting::lighting(
+            &material,
+            object.inverse_transformation_matrix(),
+            &light,
+            &position,
+            &camera,
+            &normal,
+            color::white(),
+        );
+
+        let expected = color::color(1.9, 1.9, 1.9);
+        assert_color_approx_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_lighting_dims_a_light_with_falloff_by_its_distance() {
+        let mut material = material::material();
+        material.ambient = 0.0;
+        material.specular = 0.0;
+        let object = shape::Shape::default_sphere();
+        let position = tuple::Point::new(0.0, 0.0, 0.0);
+        let camera = tuple::Vector::new(0.0, 0.0, -1.0);
+        let normal = tuple::Vector::new(0.0, 0.0, -1.0);
+        let light_at = |distance: f64| {
+            let mut light = lights::point_light(
+                tuple::Point::new(0.0, 0.0, -distance),
+                color::color(1.0, 1.0, 1.0),
+            );
+            light.falloff = lights::Falloff::InverseSquare;
+            return lighting::lighting(
+                &material,
+                object.inverse_transformation_matrix(),
+                &light,
+                &position,
+                &camera,
+                &normal,
+                color::white(),
+            );
+        };
+
+        assert_color_approx_eq!(light_at(2.0), color::color(0.225, 0.225, 0.225));
+        assert_color_approx_eq!(
+            light_at(200.0),
+            color::color(0.0000225, 0.0000225, 0.0000225)
+        );
+    }
+
     #[test]
     fn test_transparency_and_refactive_index_for_the_default_material() {
         let material = material::material();
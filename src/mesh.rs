@@ -0,0 +1,397 @@
+// A triangle mesh: a whole model's triangles as one shape. The faces share
+// their corners through buffers of vertices, normals and texture
+// coordinates that they name by index, and the mesh's one transform and
+// material serve them all, where a group of `Shape::triangle`s gives every
+// triangle its own points, edges, normals, transforms and material.
+//
+// A mesh organizes its faces into a bounding volume hierarchy of its own
+// when it's built (see `bvh::build_hierarchy`), so a ray tests only the
+// faces in the boxes it passes through, without the mesh being split into
+// groups.
+
+use crate::bounds;
+use crate::bvh;
+use crate::ray;
+use crate::shape;
+use crate::tuple;
+
+// Leaves of a mesh's hierarchy hold at most this many faces unless they
+// can't be split.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// A triangle of a mesh, as 0-based indices into its buffers: which
+/// vertices are its corners, and which normals and texture coordinates go
+/// with them, if it has any.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Face {
+    pub vertices: [u32; 3],
+    /// Without normals, a face is flat shaded.
+    pub normals: Option<[u32; 3]>,
+    pub uvs: Option<[u32; 3]>,
+}
+
+// Where a ray hits a mesh: which face, how far along the ray, and the
+// barycentric coordinates of the hit relative to the face's corners.
+pub(crate) struct MeshHit {
+    pub(crate) face: usize,
+    pub(crate) t: f64,
+    pub(crate) u: f64,
+    pub(crate) v: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Mesh {
+    vertices: Vec<tuple::Point>,
+    normals: Vec<tuple::Vector>,
+    uvs: Vec<(f64, f64)>,
+    // In the order the hierarchy's leaves hold them, which isn't the order
+    // they were given in.
+    faces: Vec<Face>,
+    nodes: Vec<bvh::Node>,
+}
+
+impl Mesh {
+    /// Panics if a face names a vertex, normal or texture coordinate that
+    /// isn't there.
+    pub fn new(
+        vertices: Vec<tuple::Point>,
+        normals: Vec<tuple::Vector>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<Face>,
+    ) -> Mesh {
+        for face in faces.iter() {
+            let in_range = |indices: Option<[u32; 3]>, len: usize| {
+                indices.is_none_or(|indices| indices.iter().all(|&index| (index as usize) < len))
+            };
+            assert!(
+                in_range(Some(face.vertices), vertices.len())
+                    && in_range(face.normals, normals.len())
+                    && in_range(face.uvs, uvs.len()),
+                "mesh face {:?} names a corner that isn't in the buffers",
+                face
+            );
+        }
+
+        let mut mesh = Mesh {
+            vertices,
+            normals,
+            uvs,
+            faces: vec![],
+            nodes: vec![],
+        };
+        let boxes: Vec<bounds::BoundingBox> = faces
+            .iter()
+            .map(|face| {
+                let mut bbox = bounds::BoundingBox::empty();
+                for &vertex in face.vertices.iter() {
+                    bbox.add_point(mesh.vertices[vertex as usize]);
+                }
+                return bbox;
+            })
+            .collect();
+        let (nodes, order) = bvh::build_hierarchy(&boxes, MAX_LEAF_SIZE);
+        mesh.nodes = nodes;
+        mesh.faces = order.into_iter().map(|face| faces[face]).collect();
+        return mesh;
+    }
+
+    pub fn face_count(&self) -> usize {
+        return self.faces.len();
+    }
+
+    /// A face by its index in the mesh, which numbers them in the order
+    /// its hierarchy holds them rather than the order they were given in.
+    pub fn face(&self, index: usize) -> &Face {
+        return &self.faces[index];
+    }
+
+    pub fn bounds(&self) -> bounds::BoundingBox {
+        return match self.nodes.first() {
+            Some(root) => root.bounds,
+            None => bounds::BoundingBox::empty(),
+        };
+    }
+
+    // A face's first corner and the edges from it to the other two.
+    pub(crate) fn edges(&self, face: usize) -> (tuple::Point, tuple::Vector, tuple::Vector) {
+        let [a, b, c] = self.faces[face].vertices;
+        let p1 = self.vertices[a as usize];
+        return (
+            p1,
+            self.vertices[b as usize] - p1,
+            self.vertices[c as usize] - p1,
+        );
+    }
+
+    /// The normal at `u`, `v` on a face: its corners' normals blended as a
+    /// smooth triangle's are, or the flat face's own if it has none.
+    pub fn normal_at(&self, face: usize, u: f64, v: f64) -> tuple::Vector {
+        match self.faces[face].normals {
+            Some([n1, n2, n3]) => {
+                return self.normals[n2 as usize] * u
+                    + self.normals[n3 as usize] * v
+                    + self.normals[n1 as usize] * (1.0 - u - v);
+            }
+            None => {
+                let (_, e1, e2) = self.edges(face);
+                return tuple::normalize(&tuple::cross(&e2, &e1));
+            }
+        }
+    }
+
+    /// The texture coordinates at `u`, `v` on a face, blended from its
+    /// corners', or `None` if it has none.
+    pub fn uv_at(&self, face: usize, u: f64, v: f64) -> Option<(f64, f64)> {
+        let [t1, t2, t3] = self.faces[face].uvs?;
+        let (t1, t2, t3) = (
+            self.uvs[t1 as usize],
+            self.uvs[t2 as usize],
+            self.uvs[t3 as usize],
+        );
+        let w = 1.0 - u - v;
+        return Some((
+            t2.0 * u + t3.0 * v + t1.0 * w,
+            t2.1 * u + t3.1 * v + t1.1 * w,
+        ));
+    }
+
+    fn intersect_face(&self, face: usize, ray: &ray::Ray) -> Option<MeshHit> {
+        let (p1, e1, e2) = self.edges(face);
+        let (t, u, v) = shape::intersect_triangle(ray, &p1, &e1, &e2, 1.0)?;
+        return Some(MeshHit { face, t, u, v });
+    }
+
+    // Every face `ray` hits, behind its origin as well as in front, in no
+    // particular order.
+    pub(crate) fn hits(&self, ray: &ray::Ray) -> Vec<MeshHit> {
+        let mut hits = vec![];
+        bvh::traverse(&self.nodes, ray, |node| {
+            if !node.bounds.intersects(ray) {
+                return false;
+            }
+            if let bvh::NodeKind::Leaf { first, count } = node.kind {
+                let faces = first as usize..(first + count) as usize;
+                hits.extend(faces.filter_map(|face| self.intersect_face(face, ray)));
+            }
+            return true;
+        });
+        return hits;
+    }
+
+    // The nearest face `ray` hits in front of its origin, if it's nearer
+    // than `limit`. Boxes the ray enters beyond the nearest hit so far are
+    // skipped.
+    pub(crate) fn closest_hit(&self, ray: &ray::Ray, limit: f64) -> Option<MeshHit> {
+        let mut closest: Option<MeshHit> = None;
+        bvh::traverse(&self.nodes, ray, |node| {
+            let nearest = closest.as_ref().map_or(limit, |hit| hit.t);
+            match node.bounds.entry(ray) {
+                Some(entry) if entry < nearest => {}
+                _ => return false,
+            }
+            if let bvh::NodeKind::Leaf { first, count } = node.kind {
+                for face in first as usize..(first + count) as usize {
+                    let Some(hit) = self.intersect_face(face, ray) else {
+                        continue;
+                    };
+                    let nearest = closest.as_ref().map_or(limit, |hit| hit.t);
+                    if hit.t.is_sign_positive() && hit.t < nearest {
+                        closest = Some(hit);
+                    }
+                }
+            }
+            return true;
+        });
+        return closest;
+    }
+
+    // Whether `ray` hits a face in front of its origin, closer than
+    // `max_t`, that `accept` says counts, stopping at the first one.
+    pub(crate) fn any_hit(
+        &self,
+        ray: &ray::Ray,
+        max_t: f64,
+        mut accept: impl FnMut(&MeshHit) -> bool,
+    ) -> bool {
+        let mut found = false;
+        bvh::traverse(&self.nodes, ray, |node| {
+            if found || !node.bounds.intersects(ray) {
+                return false;
+            }
+            if let bvh::NodeKind::Leaf { first, count } = node.kind {
+                found = (first as usize..(first + count) as usize).any(|face| {
+                    self.intersect_face(face, ray).is_some_and(|hit| {
+                        hit.t.is_sign_positive() && hit.t < max_t && accept(&hit)
+                    })
+                });
+            }
+            return !found;
+        });
+        return found;
+    }
+}
+
+#[cfg(test)]
+mod mesh_tests {
+    use crate::mesh;
+    use crate::ray;
+    use crate::tuple;
+    use assert_approx_eq::assert_approx_eq;
+
+    // A unit square in the z = 0 plane as two faces sharing the diagonal
+    // from (-1, -1) to (1, 1). The first face has normals and texture
+    // coordinates; the second is flat and untextured.
+    fn square() -> mesh::Mesh {
+        return mesh::Mesh::new(
+            vec![
+                tuple::Point::new(-1.0, -1.0, 0.0),
+                tuple::Point::new(1.0, -1.0, 0.0),
+                tuple::Point::new(1.0, 1.0, 0.0),
+                tuple::Point::new(-1.0, 1.0, 0.0),
+            ],
+            vec![
+                tuple::Vector::new(0.0, 0.0, -1.0),
+                tuple::Vector::new(1.0, 0.0, 0.0),
+                tuple::Vector::new(0.0, 1.0, 0.0),
+            ],
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            vec![
+                mesh::Face {
+                    vertices: [0, 1, 2],
+                    normals: Some([0, 1, 2]),
+                    uvs: Some([0, 1, 2]),
+                },
+                mesh::Face {
+                    vertices: [0, 2, 3],
+                    normals: None,
+                    uvs: None,
+                },
+            ],
+        );
+    }
+
+    // The index the mesh gave the face with normals, since building the
+    // hierarchy may have reordered the faces.
+    fn smooth_face(mesh: &mesh::Mesh) -> usize {
+        return (0..mesh.face_count())
+            .find(|&face| mesh.face(face).normals.is_some())
+            .unwrap();
+    }
+
+    fn flat_face(mesh: &mesh::Mesh) -> usize {
+        return 1 - smooth_face(mesh);
+    }
+
+    fn ray_at(x: f64, y: f64) -> ray::Ray {
+        return ray::ray(
+            tuple::Point::new(x, y, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn test_a_mesh_is_bounded_by_its_vertices() {
+        let bounds = square().bounds();
+
+        assert_eq!(bounds.min, tuple::Point::new(-1.0, -1.0, 0.0));
+        assert_eq!(bounds.max, tuple::Point::new(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_a_flat_face_has_one_normal() {
+        let mesh = square();
+        let face = flat_face(&mesh);
+
+        assert_eq!(
+            mesh.normal_at(face, 0.2, 0.3),
+            tuple::Vector::new(0.0, 0.0, -1.0)
+        );
+        assert_eq!(
+            mesh.normal_at(face, 0.7, 0.1),
+            tuple::Vector::new(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn test_a_face_with_normals_blends_them() {
+        // The same weights a smooth triangle uses: u for the second
+        // corner, v for the third and what's left for the first.
+        let mesh = square();
+        let face = smooth_face(&mesh);
+
+        let normal = mesh.normal_at(face, 0.45, 0.25);
+
+        assert_approx_eq!(normal.x, 0.45);
+        assert_approx_eq!(normal.y, 0.25);
+        assert_approx_eq!(normal.z, -0.3);
+    }
+
+    #[test]
+    fn test_texture_coordinates_are_blended_like_normals() {
+        let mesh = square();
+
+        let (u, v) = mesh.uv_at(smooth_face(&mesh), 0.45, 0.25).unwrap();
+
+        assert_approx_eq!(u, 0.7);
+        assert_approx_eq!(v, 0.25);
+        assert_eq!(mesh.uv_at(flat_face(&mesh), 0.45, 0.25), None);
+    }
+
+    #[test]
+    fn test_a_ray_hits_the_face_it_passes_through() {
+        let mesh = square();
+
+        let hits = mesh.hits(&ray_at(0.5, -0.5));
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].face, smooth_face(&mesh));
+        assert_eq!(hits[0].t, 5.0);
+        assert_approx_eq!(hits[0].u, 0.5);
+        assert_approx_eq!(hits[0].v, 0.25);
+
+        let hits = mesh.hits(&ray_at(-0.5, 0.5));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].face, flat_face(&mesh));
+        assert!(mesh.hits(&ray_at(1.5, 0.5)).is_empty());
+    }
+
+    #[test]
+    fn test_the_closest_hit_must_be_in_front_of_the_ray_and_the_limit() {
+        let mesh = square();
+
+        let hit = mesh.closest_hit(&ray_at(0.5, -0.5), f64::INFINITY);
+        assert_eq!(hit.map(|hit| hit.t), Some(5.0));
+
+        assert!(mesh.closest_hit(&ray_at(0.5, -0.5), 4.0).is_none());
+        let behind = ray::ray(
+            tuple::Point::new(0.5, -0.5, 5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+        assert!(mesh.closest_hit(&behind, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_any_hit_counts_only_accepted_hits_before_max_t() {
+        let mesh = square();
+        let ray = ray_at(0.5, -0.5);
+
+        assert!(mesh.any_hit(&ray, 6.0, |_| true));
+        assert!(!mesh.any_hit(&ray, 4.0, |_| true));
+        assert!(!mesh.any_hit(&ray, 6.0, |_| false));
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't in the buffers")]
+    fn test_a_face_must_name_corners_in_the_buffers() {
+        mesh::Mesh::new(
+            vec![tuple::Point::new(0.0, 0.0, 0.0); 3],
+            vec![],
+            vec![],
+            vec![mesh::Face {
+                vertices: [0, 1, 2],
+                normals: Some([0, 1, 2]),
+                uvs: None,
+            }],
+        );
+    }
+}
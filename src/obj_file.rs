@@ -1,9 +1,9 @@
 // A parser for the Wavefront OBJ 3D model format (chapter 15). Only the
 // statements the ray tracer needs are recognized: `v` (vertex), `vn`
-// (vertex normal), and `f` (face), with `g` (named group) routing faces
-// into groups. A face names its corners as plain vertex indices or as
-// vertex/texture/normal triples like `1//3`; when normals are present the
-// face produces smooth triangles (texture indices are ignored). Faces
+// (vertex normal), `vt` (texture coordinate), and `f` (face), with `g`
+// (named group) routing faces into groups. A face names its corners as
+// plain vertex indices or as vertex/texture/normal triples like `1//3`;
+// when normals are present the face produces smooth triangles. Faces
 // with more than three vertices are fan-triangulated, so only convex
 // polygons are supported. Everything else is silently ignored.
 //
@@ -16,12 +16,19 @@
 // - `parser.vertex(i)` and `parser.normal(i)` return a vertex or vertex
 //   normal by its 1-based OBJ index.
 // - `parser.default_group()` and `parser.group(name)` return the triangles
-//   collected into the default and named groups, as slices.
+//   collected into the default and named groups, as triangle shapes.
 // - `parser.into_group()` consumes the parser and assembles the model into
 //   a single `Group` shape: each non-empty group (the default group
 //   included) becomes a child `Group` of triangles, in file order; empty
-//   groups contribute nothing.
+//   groups contribute nothing. `parser.into_sah_group()` does the same but
+//   organizes the triangles with the surface area heuristic builder.
+// - `parser.into_mesh()` consumes the parser and assembles every face into
+//   a single `Mesh` shape, whose faces share the file's vertices, normals
+//   and texture coordinates instead of each keeping copies of their own.
 
+use std::cell::OnceCell;
+
+use crate::mesh;
 use crate::shape;
 use crate::tuple;
 
@@ -30,10 +37,20 @@ pub struct Parser {
     pub ignored_lines: usize,
     vertices: Vec<tuple::Point>,
     normals: Vec<tuple::Vector>,
-    default_group: Vec<shape::Shape>,
+    texture_coordinates: Vec<(f64, f64)>,
+    default_group: FaceGroup,
     // The named groups in file order. Vertex indices are global to the
     // file, so only the triangles are grouped, not the vertices.
-    named_groups: Vec<(String, Vec<shape::Shape>)>,
+    named_groups: Vec<(String, FaceGroup)>,
+}
+
+// A group's faces, as triangles of 0-based indices into the parser's
+// buffers. They're only turned into triangle shapes the first time those
+// are asked for, so a model loaded as a mesh never makes any.
+#[derive(Debug, Default)]
+struct FaceGroup {
+    faces: Vec<mesh::Face>,
+    triangles: OnceCell<Vec<shape::Shape>>,
 }
 
 pub fn parse_obj(source: &str) -> Parser {
@@ -41,7 +58,8 @@ pub fn parse_obj(source: &str) -> Parser {
         ignored_lines: 0,
         vertices: Vec::new(),
         normals: Vec::new(),
-        default_group: Vec::new(),
+        texture_coordinates: Vec::new(),
+        default_group: FaceGroup::default(),
         named_groups: Vec::new(),
     };
 
@@ -54,6 +72,7 @@ pub fn parse_obj(source: &str) -> Parser {
         let recognized = match tokens.split_first() {
             Some((&"v", args)) => parser.parse_vertex(args),
             Some((&"vn", args)) => parser.parse_normal(args),
+            Some((&"vt", args)) => parser.parse_texture_coordinate(args),
             Some((&"f", args)) => parser.parse_face(args, current_group),
             Some((&"g", args)) => match parser.enter_group(args) {
                 Some(index) => {
@@ -82,19 +101,39 @@ impl Parser {
         return self.normals[index - 1];
     }
 
+    pub fn texture_coordinate(&self, index: usize) -> (f64, f64) {
+        return self.texture_coordinates[index - 1];
+    }
+
     pub fn default_group(&self) -> &[shape::Shape] {
-        return &self.default_group;
+        return self.triangles(&self.default_group);
     }
 
     pub fn group(&self, name: &str) -> &[shape::Shape] {
-        for (group_name, triangles) in self.named_groups.iter() {
+        for (group_name, group) in self.named_groups.iter() {
             if group_name == name {
-                return triangles;
+                return self.triangles(group);
             }
         }
         panic!("no group named `{}` in the OBJ file", name);
     }
 
+    // Every face of the model as one mesh, the default group's first and
+    // then the named groups' in file order. Group names don't survive:
+    // a mesh is a single shape with a single material.
+    pub fn into_mesh(self) -> shape::Shape {
+        let mut faces = self.default_group.faces;
+        for (_, group) in self.named_groups {
+            faces.extend(group.faces);
+        }
+        return shape::Shape::mesh(mesh::Mesh::new(
+            self.vertices,
+            self.normals,
+            self.texture_coordinates,
+            faces,
+        ));
+    }
+
     pub fn into_group(self) -> shape::Shape {
         // Imported meshes have thousands of triangles in a flat group, so
         // reorganize them into a bounding volume hierarchy: rays then skip
@@ -103,30 +142,51 @@ impl Parser {
         // children the extra box tests cost more than they save.
         const BVH_THRESHOLD: usize = 8;
 
+        let mut model = self.into_flat_group();
+        model.divide(BVH_THRESHOLD);
+        return model;
+    }
+
+    // `into_group`, but with the hierarchy built by the surface area
+    // heuristic, which gives shallower trees with smaller leaves on
+    // meshes whose triangles vary a lot in size.
+    pub fn into_sah_group(self) -> shape::Shape {
+        const MAX_LEAF_SIZE: usize = 4;
+
+        let mut model = self.into_flat_group();
+        model.divide_sah(MAX_LEAF_SIZE);
+        return model;
+    }
+
+    fn into_flat_group(self) -> shape::Shape {
         let mut model = shape::Shape::default_group();
 
         let mut groups = vec![self.default_group];
-        groups.extend(
-            self.named_groups
-                .into_iter()
-                .map(|(_, triangles)| triangles),
-        );
+        groups.extend(self.named_groups.into_iter().map(|(_, group)| group));
 
-        for triangles in groups {
-            if triangles.is_empty() {
+        for faces in groups {
+            if faces.faces.is_empty() {
                 continue;
             }
+            let triangles = faces
+                .triangles
+                .into_inner()
+                .unwrap_or_else(|| triangles(&self.vertices, &self.normals, &faces.faces));
             let mut group = shape::Shape::default_group();
             for triangle in triangles {
                 group.add_child(triangle);
             }
             model.add_child(group);
         }
-
-        model.divide(BVH_THRESHOLD);
         return model;
     }
 
+    fn triangles<'a>(&self, group: &'a FaceGroup) -> &'a [shape::Shape] {
+        return group
+            .triangles
+            .get_or_init(|| triangles(&self.vertices, &self.normals, &group.faces));
+    }
+
     fn parse_vertex(&mut self, args: &[&str]) -> bool {
         let coordinates: Vec<f64> = args.iter().filter_map(|arg| arg.parse().ok()).collect();
         if args.len() != 3 || coordinates.len() != 3 {
@@ -156,8 +216,21 @@ impl Parser {
         return true;
     }
 
+    // A texture coordinate is `u`, optionally followed by `v` and a depth
+    // `w`; a missing `v` is 0, and the depth is ignored.
+    fn parse_texture_coordinate(&mut self, args: &[&str]) -> bool {
+        let components: Vec<f64> = args.iter().filter_map(|arg| arg.parse().ok()).collect();
+        if args.is_empty() || args.len() > 3 || components.len() != args.len() {
+            return false;
+        }
+
+        let v = components.get(1).copied().unwrap_or(0.0);
+        self.texture_coordinates.push((components[0], v));
+        return true;
+    }
+
     fn parse_face(&mut self, args: &[&str], current_group: Option<usize>) -> bool {
-        let mut corners: Vec<(usize, Option<usize>)> = Vec::new();
+        let mut corners: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
         for arg in args {
             match parse_face_corner(arg) {
                 Some(corner) => corners.push(corner),
@@ -167,7 +240,7 @@ impl Parser {
         if corners.len() < 3 {
             return false;
         }
-        if corners.iter().any(|&(vertex, normal)| {
+        if corners.iter().any(|&(vertex, _, normal)| {
             vertex < 1
                 || vertex > self.vertices.len()
                 || normal.is_some_and(|index| index < 1 || index > self.normals.len())
@@ -177,30 +250,31 @@ impl Parser {
 
         // A face is smooth only when every corner names a normal; a face
         // that mixes corners with and without normals is malformed.
-        let smooth = corners.iter().all(|&(_, normal)| normal.is_some());
-        if !smooth && corners.iter().any(|&(_, normal)| normal.is_some()) {
+        let smooth = corners.iter().all(|&(_, _, normal)| normal.is_some());
+        if !smooth && corners.iter().any(|&(_, _, normal)| normal.is_some()) {
             return false;
         }
+        // Texture indices are only ever used for texture coordinates, so
+        // rather than reject the face, a face with any missing or unknown
+        // one just goes without.
+        let textured = corners.iter().all(|&(_, texture, _)| {
+            texture.is_some_and(|index| index >= 1 && index <= self.texture_coordinates.len())
+        });
 
         // Fan triangulation: this assumes the polygon is convex, so every
         // triangle can share the face's first corner.
         for i in 1..corners.len() - 1 {
-            let (v1, v2, v3) = (corners[0].0, corners[i].0, corners[i + 1].0);
-            let triangle = if smooth {
-                shape::Shape::smooth_triangle(
-                    self.vertex(v1),
-                    self.vertex(v2),
-                    self.vertex(v3),
-                    self.normal(corners[0].1.unwrap()),
-                    self.normal(corners[i].1.unwrap()),
-                    self.normal(corners[i + 1].1.unwrap()),
-                )
-            } else {
-                shape::Shape::triangle(self.vertex(v1), self.vertex(v2), self.vertex(v3))
+            let triangle = [corners[0], corners[i], corners[i + 1]];
+            let face = mesh::Face {
+                vertices: triangle.map(|(vertex, _, _)| (vertex - 1) as u32),
+                normals: smooth
+                    .then(|| triangle.map(|(_, _, normal)| (normal.unwrap() - 1) as u32)),
+                uvs: textured
+                    .then(|| triangle.map(|(_, texture, _)| (texture.unwrap() - 1) as u32)),
             };
             match current_group {
-                Some(group) => self.named_groups[group].1.push(triangle),
-                None => self.default_group.push(triangle),
+                Some(group) => self.named_groups[group].1.faces.push(face),
+                None => self.default_group.faces.push(face),
             }
         }
         return true;
@@ -217,18 +291,41 @@ impl Parser {
         if let Some(index) = self.named_groups.iter().position(|(n, _)| n == name) {
             return Some(index);
         }
-        self.named_groups.push((name.to_string(), Vec::new()));
+        self.named_groups
+            .push((name.to_string(), FaceGroup::default()));
         return Some(self.named_groups.len() - 1);
     }
 }
 
+// One triangle shape per face: a smooth triangle if the face has normals,
+// a flat one otherwise.
+fn triangles(
+    vertices: &[tuple::Point],
+    normals: &[tuple::Vector],
+    faces: &[mesh::Face],
+) -> Vec<shape::Shape> {
+    return faces
+        .iter()
+        .map(|face| {
+            let [v1, v2, v3] = face.vertices.map(|index| vertices[index as usize]);
+            return match face.normals {
+                Some(indices) => {
+                    let [n1, n2, n3] = indices.map(|index| normals[index as usize]);
+                    shape::Shape::smooth_triangle(v1, v2, v3, n1, n2, n3)
+                }
+                None => shape::Shape::triangle(v1, v2, v3),
+            };
+        })
+        .collect();
+}
+
 // Parses one face corner of the form `v`, `v/t`, `v/t/n`, or `v//n` into
-// the vertex index and optional normal index. The texture index in the
-// middle is not used by the ray tracer and is ignored unparsed.
-fn parse_face_corner(arg: &str) -> Option<(usize, Option<usize>)> {
+// the vertex index and optional texture and normal indices. A texture
+// index that isn't a number is treated as missing rather than as an error.
+fn parse_face_corner(arg: &str) -> Option<(usize, Option<usize>, Option<usize>)> {
     let mut parts = arg.split('/');
     let vertex = parts.next()?.parse().ok()?;
-    let _texture = parts.next();
+    let texture = parts.next().and_then(|part| part.parse().ok());
     let normal = match parts.next() {
         Some(part) => Some(part.parse().ok()?),
         None => None,
@@ -236,14 +333,17 @@ fn parse_face_corner(arg: &str) -> Option<(usize, Option<usize>)> {
     if parts.next().is_some() {
         return None;
     }
-    return Some((vertex, normal));
+    return Some((vertex, texture, normal));
 }
 
 #[cfg(test)]
 mod obj_file_tests {
+    use crate::mesh;
     use crate::obj_file;
+    use crate::ray;
     use crate::shape;
     use crate::tuple;
+    use assert_approx_eq::assert_approx_eq;
 
     // The book's files/triangles.obj, used by the named-group tests.
     const TRIANGLES_OBJ: &str = "\
@@ -450,4 +550,97 @@ f 1/0/3 2/102/1 3/14/2
 
         assert_eq!(group, expected);
     }
+
+    #[test]
+    fn test_texture_coordinate_records() {
+        // A `vt` statement is `u`, then optionally `v` (0 if missing) and
+        // a depth that the ray tracer has no use for.
+        let file = "\
+vt 0.25 0.5
+vt 0.75
+vt 0.1 0.2 0.3
+vt
+";
+
+        let parser = obj_file::parse_obj(file);
+
+        assert_eq!(parser.texture_coordinate(1), (0.25, 0.5));
+        assert_eq!(parser.texture_coordinate(2), (0.75, 0.0));
+        assert_eq!(parser.texture_coordinate(3), (0.1, 0.2));
+        assert_eq!(parser.ignored_lines, 1);
+    }
+
+    #[test]
+    fn test_converting_an_obj_model_to_a_mesh() {
+        // Every face, whatever its group, becomes a face of one mesh that
+        // shares the file's buffers. Only a face whose corners all name a
+        // texture coordinate gets them.
+        let file = "\
+v 0 1 0
+v -1 0 0
+v 1 0 0
+v 0 -1 0
+vn 0 0 -1
+vt 0 1
+vt 0 0
+vt 1 0
+f 1/1/1 2/2/1 3/3/1
+g Lower
+f 2/2 4/9 3/3
+";
+
+        let model = obj_file::parse_obj(file).into_mesh();
+
+        let mesh = model.as_mesh().unwrap();
+        assert_eq!(mesh.face_count(), 2);
+        let mut faces: Vec<mesh::Face> = (0..2).map(|face| *mesh.face(face)).collect();
+        faces.sort_by_key(|face| face.vertices);
+        assert_eq!(
+            faces,
+            vec![
+                mesh::Face {
+                    vertices: [0, 1, 2],
+                    normals: Some([0, 0, 0]),
+                    uvs: Some([0, 1, 2]),
+                },
+                mesh::Face {
+                    vertices: [1, 3, 2],
+                    normals: None,
+                    uvs: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_a_mesh_is_hit_where_the_group_of_triangles_is() {
+        let source = std::fs::read_to_string("object_files/teapot-low.obj").unwrap();
+        let group = obj_file::parse_obj(&source).into_group();
+        let mesh = obj_file::parse_obj(&source).into_mesh();
+
+        let mut hits = 0;
+        for i in 0..40 {
+            for j in 0..40 {
+                let origin = tuple::Point::new(-20.0 + i as f64, -40.0, -5.0 + j as f64 * 0.8);
+                let ray = ray::ray(origin, tuple::Vector::new(0.0, 1.0, 0.0));
+                let (mut expected, mut actual) = (None, None);
+                group.intersect_closest(&ray, &mut expected);
+                mesh.intersect_closest(&ray, &mut actual);
+                match (expected, actual) {
+                    (None, None) => {}
+                    (Some(expected), Some(actual)) => {
+                        hits += 1;
+                        let point = ray.position(actual.t);
+                        assert_approx_eq!(expected.t, actual.t, 1e-9);
+                        crate::assert_tuple_approx_eq!(
+                            expected.normal_at(point),
+                            actual.normal_at(point)
+                        );
+                    }
+                    (expected, actual) => panic!("expected {:?}, found {:?}", expected, actual),
+                }
+            }
+        }
+        assert!(hits > 0);
+    }
 }
@@ -25,6 +25,9 @@ pub struct Pattern {
     a: color::Color,
     b: color::Color,
     transform: matrix::Matrix4,
+    // Kept in step with `transform` by `set_transformation_matrix`, so
+    // shading a point doesn't invert it.
+    inverse: matrix::Matrix4,
     pattern_type: PatternType,
 }
 
@@ -34,6 +37,7 @@ impl Pattern {
             a: color::black(),
             b: color::black(),
             transform: matrix::Matrix4::IDENTITY,
+            inverse: matrix::Matrix4::IDENTITY,
             pattern_type: PatternType::TestPattern,
         };
     }
@@ -43,6 +47,7 @@ impl Pattern {
             a,
             b,
             transform: matrix::Matrix4::IDENTITY,
+            inverse: matrix::Matrix4::IDENTITY,
             pattern_type: PatternType::Stripe,
         };
     }
@@ -52,6 +57,7 @@ impl Pattern {
             a,
             b,
             transform: matrix::Matrix4::IDENTITY,
+            inverse: matrix::Matrix4::IDENTITY,
             pattern_type: PatternType::Gradient,
         };
     }
@@ -61,6 +67,7 @@ impl Pattern {
             a,
             b,
             transform: matrix::Matrix4::IDENTITY,
+            inverse: matrix::Matrix4::IDENTITY,
             pattern_type: PatternType::Ring,
         };
     }
@@ -70,6 +77,7 @@ impl Pattern {
             a,
             b,
             transform: matrix::Matrix4::IDENTITY,
+            inverse: matrix::Matrix4::IDENTITY,
             pattern_type: PatternType::Checkers,
         };
     }
@@ -79,6 +87,7 @@ impl Pattern {
             a: color::black(),
             b: color::black(),
             transform: matrix::Matrix4::IDENTITY,
+            inverse: matrix::Matrix4::IDENTITY,
             pattern_type: PatternType::TextureMap { uv_pattern, uv_map },
         };
     }
@@ -88,6 +97,7 @@ impl Pattern {
             a: color::black(),
             b: color::black(),
             transform: matrix::Matrix4::IDENTITY,
+            inverse: matrix::Matrix4::IDENTITY,
             pattern_type: PatternType::CubeMap {
                 faces: Box::new(faces),
             },
@@ -150,20 +160,48 @@ impl Pattern {
         };
     }
 
-    // `object_to_world` is the object's transform with any enclosing group
-    // transforms composed on, so patterns follow shapes into groups.
+    // `world_to_object` is the inverse of the object's transform with any
+    // enclosing group transforms composed on, so patterns follow shapes
+    // into groups.
     pub fn pattern_at_object(
         &self,
-        object_to_world: &matrix::Matrix4,
+        world_to_object: &matrix::Matrix4,
         world_point: &tuple::Point,
     ) -> color::Color {
-        let object_point = object_to_world.inverse().unwrap() * *world_point;
-        let pattern_point = self.transform.inverse().unwrap() * object_point;
+        let object_point = *world_to_object * *world_point;
+        let pattern_point = self.inverse * object_point;
 
         self.pattern_at(&pattern_point)
     }
 
+    /// As `pattern_at_object`, but a texture map onto the surface's own
+    /// coordinates looks up `surface_uv`, the texture coordinates at the
+    /// point, when the surface has them.
+    pub fn pattern_at_surface(
+        &self,
+        world_to_object: &matrix::Matrix4,
+        world_point: &tuple::Point,
+        surface_uv: Option<(f64, f64)>,
+    ) -> color::Color {
+        if let (
+            PatternType::TextureMap {
+                uv_pattern,
+                uv_map: uv::UvMap::Surface,
+            },
+            Some((u, v)),
+        ) = (&self.pattern_type, surface_uv)
+        {
+            return uv_pattern.uv_pattern_at(u, v);
+        }
+        return self.pattern_at_object(world_to_object, world_point);
+    }
+
+    /// Panics if `new_transform` can't be inverted, as
+    /// `Shape::set_transformation_matrix` does.
     pub fn set_transformation_matrix(&mut self, new_transform: matrix::Matrix4) {
+        self.inverse = new_transform
+            .inverse()
+            .expect("a pattern's transform must be invertible: is it scaled by zero?");
         self.transform = new_transform;
     }
 }
@@ -255,11 +293,14 @@ mod patterns_tests {
     #[test]
     fn test_stripes_with_an_object_transformation() {
         let mut object = shape::Shape::default_sphere();
-        object.transform = object.transform.scaling(2.0, 2.0, 2.0);
+        object.set_transformation_matrix(object.transformation_matrix().scaling(2.0, 2.0, 2.0));
         let pattern = patterns::Pattern::stripe(color::white(), color::black());
 
         assert_color_approx_eq!(
-            pattern.pattern_at_object(&object.transform, &tuple::Point::new(1.5, 0.0, 0.0)),
+            pattern.pattern_at_object(
+                object.inverse_transformation_matrix(),
+                &tuple::Point::new(1.5, 0.0, 0.0)
+            ),
             color::white()
         );
     }
@@ -268,10 +309,13 @@ mod patterns_tests {
     fn test_stripes_with_a_pattern_transformation() {
         let object = shape::Shape::default_sphere();
         let mut pattern = patterns::Pattern::stripe(color::white(), color::black());
-        pattern.transform = pattern.transform.scaling(2.0, 2.0, 2.0);
+        pattern.set_transformation_matrix(pattern.transform.scaling(2.0, 2.0, 2.0));
 
         assert_color_approx_eq!(
-            pattern.pattern_at_object(&object.transform, &tuple::Point::new(1.5, 0.0, 0.0)),
+            pattern.pattern_at_object(
+                object.inverse_transformation_matrix(),
+                &tuple::Point::new(1.5, 0.0, 0.0)
+            ),
             color::white()
         );
     }
@@ -279,12 +323,15 @@ mod patterns_tests {
     #[test]
     fn test_stripes_with_both_an_object_and_a_pattern_transformation() {
         let mut object = shape::Shape::default_sphere();
-        object.transform = object.transform.scaling(2.0, 2.0, 2.0);
+        object.set_transformation_matrix(object.transformation_matrix().scaling(2.0, 2.0, 2.0));
         let mut pattern = patterns::Pattern::stripe(color::white(), color::black());
-        pattern.transform = pattern.transform.translation(0.5, 0.0, 0.0);
+        pattern.set_transformation_matrix(pattern.transform.translation(0.5, 0.0, 0.0));
 
         assert_color_approx_eq!(
-            pattern.pattern_at_object(&object.transform, &tuple::Point::new(2.5, 0.0, 0.0)),
+            pattern.pattern_at_object(
+                object.inverse_transformation_matrix(),
+                &tuple::Point::new(2.5, 0.0, 0.0)
+            ),
             color::white()
         );
     }
@@ -397,11 +444,14 @@ mod patterns_tests {
     #[test]
     fn test_a_pattern_with_object_transformation() {
         let mut object = shape::Shape::default_sphere();
-        object.transform = object.transform.scaling(2.0, 2.0, 2.0);
+        object.set_transformation_matrix(object.transformation_matrix().scaling(2.0, 2.0, 2.0));
         let pattern = patterns::Pattern::test_pattern();
 
         assert_color_approx_eq!(
-            pattern.pattern_at_object(&object.transform, &tuple::Point::new(2.0, 3.0, 4.0)),
+            pattern.pattern_at_object(
+                object.inverse_transformation_matrix(),
+                &tuple::Point::new(2.0, 3.0, 4.0)
+            ),
             color::color(1.0, 1.5, 2.0)
         );
     }
@@ -410,10 +460,13 @@ mod patterns_tests {
     fn test_a_pattern_with_pattern_transformation() {
         let object = shape::Shape::default_sphere();
         let mut pattern = patterns::Pattern::test_pattern();
-        pattern.transform = pattern.transform.scaling(2.0, 2.0, 2.0);
+        pattern.set_transformation_matrix(pattern.transform.scaling(2.0, 2.0, 2.0));
 
         assert_color_approx_eq!(
-            pattern.pattern_at_object(&object.transform, &tuple::Point::new(2.0, 3.0, 4.0)),
+            pattern.pattern_at_object(
+                object.inverse_transformation_matrix(),
+                &tuple::Point::new(2.0, 3.0, 4.0)
+            ),
             color::color(1.0, 1.5, 2.0)
         );
     }
@@ -421,16 +474,41 @@ mod patterns_tests {
     #[test]
     fn test_a_pattern_with_both_object_and_pattern_transformation() {
         let mut object = shape::Shape::default_sphere();
-        object.transform = object.transform.scaling(2.0, 2.0, 2.0);
+        object.set_transformation_matrix(object.transformation_matrix().scaling(2.0, 2.0, 2.0));
         let mut pattern = patterns::Pattern::test_pattern();
-        pattern.transform = pattern.transform.translation(0.5, 1.0, 1.5);
+        pattern.set_transformation_matrix(pattern.transform.translation(0.5, 1.0, 1.5));
 
         assert_color_approx_eq!(
-            pattern.pattern_at_object(&object.transform, &tuple::Point::new(2.5, 3.0, 3.5)),
+            pattern.pattern_at_object(
+                object.inverse_transformation_matrix(),
+                &tuple::Point::new(2.5, 3.0, 3.5)
+            ),
             color::color(0.75, 0.5, 0.25)
         );
     }
 
+    #[test]
+    fn test_a_texture_map_onto_the_surface_uses_its_texture_coordinates() {
+        let checkers = uv::UvPattern::checkers(2, 2, color::black(), color::white());
+        let pattern = patterns::Pattern::texture_map(checkers, uv::UvMap::Surface);
+        let object = shape::Shape::default_sphere();
+        let point = tuple::Point::new(0.25, 0.0, 0.25);
+
+        assert_eq!(
+            pattern.pattern_at_surface(
+                object.inverse_transformation_matrix(),
+                &point,
+                Some((0.75, 0.25))
+            ),
+            color::white()
+        );
+        // Without texture coordinates the point is mapped onto the plane.
+        assert_eq!(
+            pattern.pattern_at_surface(object.inverse_transformation_matrix(), &point, None),
+            color::black()
+        );
+    }
+
     // Scenario Outline: Using a texture map pattern with a spherical map
     #[test]
     fn test_texture_map_pattern_with_a_spherical_map() {
@@ -23,7 +23,7 @@ impl Ray {
         world: &'a world::World,
     ) -> Vec<intersection::Intersection<'a>> {
         let mut intersections: Vec<intersection::Intersection> = world
-            .shapes
+            .shapes()
             .iter()
             .flat_map(|shape| shape.intersect(self))
             .collect();
@@ -1,7 +1,7 @@
 //! TOML descriptions of scenes and animations.
 //!
 //! A scene file describes a still image: a `[scene]` table (name and
-//! resolution), a `[camera]`, one or more `[[lights]]`, and one or more
+//! resolution), a `[camera]`, any number of `[[lights]]`, and one or more
 //! `[[objects]]`. An animation file is the same but with an `[animation]`
 //! table in place of `[scene]`, followed by a list of `[[frames]]`, each
 //! restating how that frame differs from the base scene: extra transform
@@ -9,20 +9,46 @@
 //! coordinates. An empty `[[frames]]` entry renders the base scene
 //! unchanged.
 //!
-//! A `[[lights]]` entry is a point light, an area light, or a spotlight —
-//! see `LightDescription` for how the fields select between them.
+//! The `[scene]` (or `[animation]`) table may also set `samples_per_pixel`,
+//! a perfect square, to anti-alias the render with that many jittered rays
+//! per pixel, and `integrator = "path_traced"` to light it by path
+//! tracing instead of the default `"whitted"` ray tracing. An
+//! `ambient_occlusion` table (`samples` rays out to `distance`) darkens the
+//! ambient term in creases and corners, and `integrator =
+//! "ambient_occlusion"` renders just the occlusion, in grayscale.
 //!
-//! Angles (rotations, the camera field of view, and spotlight cones) are
-//! in degrees.
+//! The `[camera]` table's `projection` is `"perspective"` (the default),
+//! framed by `field_of_view`; `"orthographic"`, framed by `view_width` in
+//! world units; `"equirectangular"`, a 360 degree panorama best rendered
+//! at 2:1; or `"fisheye"`, framed by a `field_of_view` of up to 360
+//! degrees. It may also give the lens an `aperture` radius for depth
+//! of field, focused either `focal_distance` in front of the camera or on
+//! a `focus` point (by default, on the `to` point).
+//!
+//! An `[[objects]]` entry with `kind = "instance"` places another copy of
+//! the object named by its `instance_of`, which must come before it, under
+//! its own transform (applied after the named object's) and, if it gives
+//! one, its own material. The copies share the named object rather than
+//! building it again, so a model loaded once can be placed many times.
+//!
+//! A `[[lights]]` entry is a point light, a rectangular area light, a ball
+//! or disk of light, a spotlight, a projector casting an image, or a
+//! directional light like the sun — see `LightDescription` for how the
+//! fields select between them.
+//!
+//! Angles (rotations, the camera field of view, spotlight cones, and a
+//! directional light's angular diameter) are in degrees.
 //! Transform steps apply in list order, each in world space after the ones
 //! before it, matching the fluent `Transform` trait. See `scenes/*.toml`
 //! and `animations/*.toml` for examples.
 
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
 
 use serde::Deserialize;
 
 use crate::camera;
+use crate::canvas;
 use crate::color;
 use crate::lights;
 use crate::material;
@@ -33,18 +59,28 @@ use crate::shape;
 use crate::transformation;
 use crate::transformation::Transform;
 use crate::tuple;
+use crate::uv;
 use crate::world;
 
-/// Seed for a scene file's area-light jitter, so renders stay reproducible
-/// across runs instead of drawing on OS randomness.
+/// Seed for a scene file's area, round and sun light jitter, so renders
+/// stay reproducible across runs instead of drawing on OS randomness.
 const AREA_LIGHT_JITTER_SEED: u64 = 0x5EED;
 
+/// Shadow rays per point for a round light, or a sun with an
+/// `angular_diameter`, that gives no `samples`.
+const DEFAULT_LIGHT_SAMPLES: usize = 16;
+
+/// Seed for the camera's supersampling jitter, for the same reason.
+const CAMERA_JITTER_SEED: u64 = 0xA1A5;
+
 /// A still image: a base scene with no frames.
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct SceneFile {
     scene: RenderSettings,
     camera: CameraDescription,
+    /// Optional, for scenes lit only by emissive objects.
+    #[serde(default)]
     lights: Vec<LightDescription>,
     objects: Vec<ObjectDescription>,
 }
@@ -55,6 +91,8 @@ pub struct SceneFile {
 pub struct AnimationFile {
     animation: RenderSettings,
     camera: CameraDescription,
+    /// Optional, for scenes lit only by emissive objects.
+    #[serde(default)]
     lights: Vec<LightDescription>,
     objects: Vec<ObjectDescription>,
     frames: Vec<FrameDescription>,
@@ -66,21 +104,115 @@ pub struct RenderSettings {
     pub name: String,
     pub width: u32,
     pub height: u32,
+    /// Rays averaged per pixel; a perfect square. One, the default, fires
+    /// a single ray through each pixel's center.
+    #[serde(default = "one_sample")]
+    pub samples_per_pixel: u32,
+    #[serde(default)]
+    pub integrator: IntegratorKind,
+    /// Darkens the ambient term in creases and corners, if given.
+    pub ambient_occlusion: Option<OcclusionSettings>,
+}
+
+/// How ambient occlusion is sampled: see `world::AmbientOcclusion`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OcclusionSettings {
+    #[serde(default = "default_occlusion_samples")]
+    pub samples: usize,
+    #[serde(default = "default_occlusion_distance")]
+    pub distance: f64,
+}
+
+impl Default for OcclusionSettings {
+    fn default() -> OcclusionSettings {
+        return OcclusionSettings {
+            samples: default_occlusion_samples(),
+            distance: default_occlusion_distance(),
+        };
+    }
+}
+
+impl OcclusionSettings {
+    pub fn build(&self) -> world::AmbientOcclusion {
+        return world::AmbientOcclusion::new(self.samples, self.distance);
+    }
+}
+
+fn default_occlusion_samples() -> usize {
+    return 16;
+}
+
+fn default_occlusion_distance() -> f64 {
+    return 1.0;
+}
+
+/// How the scene is lit: see `world::Integrator`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorKind {
+    #[default]
+    Whitted,
+    PathTraced,
+    /// A grayscale ambient occlusion image, using the `ambient_occlusion`
+    /// settings (or their defaults).
+    AmbientOcclusion,
+}
+
+fn one_sample() -> u32 {
+    return 1;
 }
 
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 struct CameraDescription {
-    /// Field of view in degrees.
-    field_of_view: f64,
+    #[serde(default)]
+    projection: ProjectionKind,
+    /// Field of view in degrees, for a perspective camera.
+    field_of_view: Option<f64>,
+    /// Width of the view in world units, for an orthographic camera.
+    view_width: Option<f64>,
     from: [f64; 3],
     to: [f64; 3],
     up: [f64; 3],
+    /// Radius of the lens; zero, the default, is a pinhole with everything
+    /// in focus.
+    #[serde(default)]
+    aperture: f64,
+    /// Distance in front of the camera of the plane in focus.
+    focal_distance: Option<f64>,
+    /// A point in focus, in place of `focal_distance`.
+    focus: Option<[f64; 3]>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ProjectionKind {
+    #[default]
+    Perspective,
+    Orthographic,
+    Equirectangular,
+    Fisheye,
+}
+
+impl ProjectionKind {
+    fn name(self) -> &'static str {
+        return match self {
+            ProjectionKind::Perspective => "perspective",
+            ProjectionKind::Orthographic => "orthographic",
+            ProjectionKind::Equirectangular => "equirectangular",
+            ProjectionKind::Fisheye => "fisheye",
+        };
+    }
 }
 
 /// A `[[lights]]` block is a point light if it has `position` (a spotlight
-/// when a `spot` table restricts it to a cone), or an area light if it has
-/// `corner`/`uvec`/`usteps`/`vvec`/`vsteps` instead.
+/// when a `spot` table restricts it to a cone, a projector when a
+/// `projector` table gives it a picture, or a round light when it
+/// has a `radius`), a directional light at infinity if it has
+/// `direction`, or an area light if it has
+/// `corner`/`uvec`/`usteps`/`vvec`/`vsteps` instead. Any but a directional
+/// light may also dim with distance.
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 struct LightDescription {
@@ -88,11 +220,88 @@ struct LightDescription {
     position: Option<[f64; 3]>,
     /// Present alongside `position` for a spotlight.
     spot: Option<SpotDescription>,
+    /// Present alongside `position` for a projector.
+    projector: Option<ProjectorDescription>,
+    /// Present alongside `position` for a ball of light, or a disk of
+    /// light when `normal` gives the way it faces.
+    radius: Option<f64>,
+    normal: Option<[f64; 3]>,
+    /// The way a directional light's rays travel.
+    direction: Option<[f64; 3]>,
+    /// How wide a directional light's disk looks, in degrees, for soft
+    /// shadows (the sun is about 0.5).
+    angular_diameter: Option<f64>,
+    /// Shadow rays per point across a round light or a directional
+    /// light's disk.
+    samples: Option<usize>,
     corner: Option<[f64; 3]>,
     uvec: Option<[f64; 3]>,
     usteps: Option<usize>,
     vvec: Option<[f64; 3]>,
     vsteps: Option<usize>,
+    /// How the light dims with distance; it doesn't by default.
+    falloff: Option<FalloffDescription>,
+}
+
+/// How a light dims with distance: `falloff = "linear"` or
+/// `falloff = "inverse_square"` (or `"none"`), or the coefficients of
+/// `1 / (constant + linear * d + quadratic * d * d)` at distance `d`, e.g.
+/// `falloff = { constant = 1.0, linear = 0.0, quadratic = 0.25 }`.
+#[derive(Deserialize)]
+#[serde(
+    untagged,
+    expecting = "a falloff of \"none\", \"linear\" or \"inverse_square\", or a table of exactly `constant`, `linear` and `quadratic`"
+)]
+enum FalloffDescription {
+    Named(FalloffKind),
+    Coefficients(FalloffCoefficients),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+struct FalloffCoefficients {
+    constant: f64,
+    linear: f64,
+    quadratic: f64,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum FalloffKind {
+    None,
+    Linear,
+    InverseSquare,
+}
+
+impl FalloffDescription {
+    fn build(&self) -> Result<lights::Falloff, String> {
+        return match *self {
+            FalloffDescription::Named(FalloffKind::None) => Ok(lights::Falloff::None),
+            FalloffDescription::Named(FalloffKind::Linear) => Ok(lights::Falloff::Linear),
+            FalloffDescription::Named(FalloffKind::InverseSquare) => {
+                Ok(lights::Falloff::InverseSquare)
+            }
+            FalloffDescription::Coefficients(FalloffCoefficients {
+                constant,
+                linear,
+                quadratic,
+            }) => {
+                if constant < 0.0 || linear < 0.0 || quadratic < 0.0 {
+                    return Err(String::from("falloff coefficients can't be negative"));
+                }
+                if constant + linear + quadratic == 0.0 {
+                    return Err(String::from(
+                        "falloff needs at least one non-zero coefficient",
+                    ));
+                }
+                Ok(lights::Falloff::Coefficients {
+                    constant,
+                    linear,
+                    quadratic,
+                })
+            }
+        };
+    }
 }
 
 /// The cone of a spotlight, e.g.
@@ -108,13 +317,31 @@ struct SpotDescription {
     fade_angle: f64,
 }
 
+/// The picture a projector casts and where, e.g.
+/// `projector = { to = [0.0, 1.0, 0.0], field_of_view = 30.0, image = "textures/earth.ppm" }`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectorDescription {
+    /// The point the middle of the picture lands on.
+    to: [f64; 3],
+    /// The way the top of the picture faces; +y by default.
+    up: Option<[f64; 3]>,
+    /// How wide the picture spreads, in degrees.
+    field_of_view: f64,
+    /// Path to a PPM image. Its shape sets the picture's aspect ratio.
+    image: String,
+}
+
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 struct ObjectDescription {
     name: String,
     kind: ObjectKind,
-    /// Path to a Wavefront OBJ model, required when `kind = "obj"`.
+    /// Path to a Wavefront OBJ model, required when `kind = "obj"`. It's
+    /// loaded as a single mesh by `obj_file::Parser::into_mesh`.
     file: Option<String>,
+    /// The name of an earlier object, required when `kind = "instance"`.
+    instance_of: Option<String>,
     #[serde(default)]
     transform: Vec<TransformOp>,
     material: Option<MaterialDescription>,
@@ -127,6 +354,7 @@ enum ObjectKind {
     Sphere,
     Cube,
     Obj,
+    Instance,
 }
 
 /// One step of a transform chain, e.g. `{ rotate_y = 90.0 }`.
@@ -152,6 +380,7 @@ struct MaterialDescription {
     reflective: Option<f64>,
     transparency: Option<f64>,
     refractive_index: Option<f64>,
+    emission: Option<[f64; 3]>,
 }
 
 /// How a single frame differs from the base scene.
@@ -182,7 +411,10 @@ struct ObjectChange {
 
 impl SceneFile {
     pub fn parse(source: &str) -> Result<SceneFile, String> {
-        return toml::from_str(source).map_err(|error| error.to_string());
+        let scene: SceneFile = toml::from_str(source).map_err(|error| error.to_string())?;
+        scene.scene.validate()?;
+        scene.camera.validate()?;
+        return Ok(scene);
     }
 
     pub fn settings(&self) -> &RenderSettings {
@@ -190,10 +422,10 @@ impl SceneFile {
     }
 
     pub fn build_world(&self) -> Result<world::World, String> {
-        return build_world(&self.objects, &self.lights, &BTreeMap::new());
+        return build_world(&self.objects, &self.lights, &BTreeMap::new(), &self.scene);
     }
 
-    pub fn build_camera(&self, scale: u32) -> camera::Camera {
+    pub fn build_camera(&self, scale: u32) -> Result<camera::Camera, String> {
         return build_camera(&self.camera, None, &self.scene, scale);
     }
 }
@@ -215,15 +447,17 @@ impl AnimationFile {
 
     pub fn build_world(&self, frame_index: usize) -> Result<world::World, String> {
         let frame = &self.frames[frame_index];
-        return build_world(&self.objects, &self.lights, &frame.objects);
+        return build_world(&self.objects, &self.lights, &frame.objects, &self.animation);
     }
 
-    pub fn build_camera(&self, frame_index: usize, scale: u32) -> camera::Camera {
+    pub fn build_camera(&self, frame_index: usize, scale: u32) -> Result<camera::Camera, String> {
         let frame = &self.frames[frame_index];
         return build_camera(&self.camera, frame.camera.as_ref(), &self.animation, scale);
     }
 
     fn validate(&self) -> Result<(), String> {
+        self.animation.validate()?;
+        self.camera.validate()?;
         let mut names = HashSet::new();
         for object in &self.objects {
             if !names.insert(&object.name) {
@@ -244,6 +478,79 @@ impl AnimationFile {
     }
 }
 
+impl RenderSettings {
+    fn validate(&self) -> Result<(), String> {
+        let side = self.samples_per_pixel.isqrt();
+        if self.samples_per_pixel == 0 || side * side != self.samples_per_pixel {
+            return Err(format!(
+                "samples_per_pixel must be a perfect square (1, 4, 9, ...), got {}",
+                self.samples_per_pixel
+            ));
+        }
+        if let Some(occlusion) = &self.ambient_occlusion {
+            if occlusion.samples == 0 || occlusion.distance <= 0.0 {
+                return Err(
+                    "ambient_occlusion needs at least one sample and a positive distance"
+                        .to_string(),
+                );
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl CameraDescription {
+    fn validate(&self) -> Result<(), String> {
+        let projection = self.projection.name();
+        let (needs_field_of_view, needs_view_width) = match self.projection {
+            ProjectionKind::Perspective | ProjectionKind::Fisheye => (true, false),
+            ProjectionKind::Orthographic => (false, true),
+            ProjectionKind::Equirectangular => (false, false),
+        };
+        for (field, needed, given) in [
+            (
+                "field_of_view",
+                needs_field_of_view,
+                self.field_of_view.is_some(),
+            ),
+            ("view_width", needs_view_width, self.view_width.is_some()),
+        ] {
+            if needed && !given {
+                return Err(format!("a {} camera needs a `{}`", projection, field));
+            }
+            if given && !needed {
+                return Err(format!("a {} camera takes no `{}`", projection, field));
+            }
+        }
+        if let (ProjectionKind::Fisheye, Some(degrees)) = (self.projection, self.field_of_view) {
+            if degrees <= 0.0 || degrees > 360.0 {
+                return Err(format!(
+                    "a fisheye field_of_view must be more than 0 and at most 360 degrees, got {}",
+                    degrees
+                ));
+            }
+        }
+        if self.aperture < 0.0 {
+            return Err(format!(
+                "the camera aperture must not be negative, got {}",
+                self.aperture
+            ));
+        }
+        if self.focal_distance.is_some() && self.focus.is_some() {
+            return Err("the camera takes `focal_distance` or `focus`, not both".to_string());
+        }
+        if let Some(distance) = self.focal_distance {
+            if distance <= 0.0 {
+                return Err(format!(
+                    "the camera focal_distance must be positive, got {}",
+                    distance
+                ));
+            }
+        }
+        return Ok(());
+    }
+}
+
 fn point(coordinates: [f64; 3]) -> tuple::Point {
     return tuple::Point::new(coordinates[0], coordinates[1], coordinates[2]);
 }
@@ -294,12 +601,18 @@ fn build_material(description: &MaterialDescription) -> material::Material {
     if let Some(value) = description.refractive_index {
         material.refractive_index = value;
     }
+    if let Some(components) = description.emission {
+        material.emission = to_color(components);
+    }
     return material;
 }
 
+// `prototypes` holds the objects built so far that others are instances
+// of, by name.
 fn build_shape(
     description: &ObjectDescription,
     change: Option<&ObjectChange>,
+    prototypes: &HashMap<&str, Arc<shape::Shape>>,
 ) -> Result<shape::Shape, String> {
     let mut shape = match description.kind {
         ObjectKind::Plane => shape::Shape::default_plane(),
@@ -314,7 +627,25 @@ fn build_shape(
             })?;
             let source = std::fs::read_to_string(path)
                 .map_err(|error| format!("could not read `{}`: {}", path, error))?;
-            obj_file::parse_obj(&source).into_group()
+            obj_file::parse_obj(&source).into_mesh()
+        }
+        ObjectKind::Instance => {
+            let name = description.instance_of.as_ref().ok_or_else(|| {
+                format!(
+                    "object `{}` has kind \"instance\" but no `instance_of`",
+                    description.name
+                )
+            })?;
+            let prototype = prototypes.get(name.as_str()).ok_or_else(|| {
+                format!(
+                    "object `{}` is an instance of `{}`, which isn't an object before it",
+                    description.name, name
+                )
+            })?;
+            // An instance's material replaces the materials of everything
+            // in the prototype, rather than going on the instance itself.
+            let material = description.material.as_ref().map(build_material);
+            shape::Shape::instance(prototype.clone(), material)
         }
     };
 
@@ -322,9 +653,18 @@ fn build_shape(
     if let Some(change) = change {
         steps.extend_from_slice(&change.transform);
     }
-    shape.set_transformation_matrix(transform_matrix(&steps));
+    shape
+        .try_set_transformation_matrix(transform_matrix(&steps))
+        .map_err(|_| {
+            format!(
+                "object `{}` has a transform that can't be undone: is it scaled by zero?",
+                description.name
+            )
+        })?;
 
-    if let Some(material_description) = &description.material {
+    if let Some(material_description) = &description.material
+        && !matches!(description.kind, ObjectKind::Instance)
+    {
         shape.material = build_material(material_description);
     }
 
@@ -332,9 +672,58 @@ fn build_shape(
 }
 
 fn build_light(description: &LightDescription) -> Result<lights::Light, String> {
+    let mut light = build_undimmed_light(description)?;
+    if let Some(falloff) = &description.falloff {
+        if description.direction.is_some() {
+            return Err(String::from(
+                "a directional light can't have a `falloff`: it is equally far from everything",
+            ));
+        }
+        light.falloff = falloff.build()?;
+    }
+    return Ok(light);
+}
+
+// Fails naming the first two of `keys` a light sets, when it sets more
+// than one of them.
+fn one_light_key_of(keys: &[(&str, bool)]) -> Result<(), String> {
+    let mut given = keys.iter().filter(|(_, given)| *given);
+    if let (Some((first, _)), Some((second, _))) = (given.next(), given.next()) {
+        return Err(format!(
+            "a light takes `{}` or `{}`, not both",
+            first, second
+        ));
+    }
+    return Ok(());
+}
+
+// The light `description` gives, before any falloff.
+fn build_undimmed_light(description: &LightDescription) -> Result<lights::Light, String> {
     let intensity = to_color(description.intensity);
+    one_light_key_of(&[
+        ("position", description.position.is_some()),
+        ("direction", description.direction.is_some()),
+    ])?;
 
     if let Some(position) = description.position {
+        one_light_key_of(&[
+            ("spot", description.spot.is_some()),
+            ("projector", description.projector.is_some()),
+            ("radius", description.radius.is_some()),
+        ])?;
+        if description.radius.is_none() {
+            for (key, given) in [
+                ("samples", description.samples.is_some()),
+                ("normal", description.normal.is_some()),
+            ] {
+                if given {
+                    return Err(format!(
+                        "a light at a `position` takes `{}` only with a `radius`",
+                        key
+                    ));
+                }
+            }
+        }
         if let Some(spot) = &description.spot {
             return Ok(lights::spot_light(
                 point(position),
@@ -344,10 +733,74 @@ fn build_light(description: &LightDescription) -> Result<lights::Light, String>
                 intensity,
             ));
         }
+        if let Some(projector) = &description.projector {
+            let path = &projector.image;
+            let ppm = std::fs::read_to_string(path)
+                .map_err(|error| format!("could not read `{}`: {}", path, error))?;
+            let image = canvas::canvas_from_ppm(&ppm)
+                .map_err(|error| format!("could not load `{}`: {}", path, error))?;
+            let aspect = image.width as f64 / image.height as f64;
+            return Ok(lights::projector_light(
+                point(position),
+                point(projector.to),
+                vector(projector.up.unwrap_or([0.0, 1.0, 0.0])),
+                projector.field_of_view.to_radians(),
+                aspect,
+                uv::UvPattern::image(image),
+                intensity,
+            ));
+        }
+        if let Some(radius) = description.radius {
+            let samples = description.samples.unwrap_or(DEFAULT_LIGHT_SAMPLES);
+            if radius <= 0.0 || samples == 0 {
+                return Err(String::from(
+                    "a round light needs a positive `radius` and at least one sample",
+                ));
+            }
+            let mut light = match description.normal {
+                Some(normal) => {
+                    lights::disk_light(point(position), vector(normal), radius, samples, intensity)
+                }
+                None => lights::sphere_light(point(position), radius, samples, intensity),
+            };
+            light.set_jitter(sequences::Sequence::random(256, AREA_LIGHT_JITTER_SEED));
+            return Ok(light);
+        }
         return Ok(lights::point_light(point(position), intensity));
     }
 
-    let missing = |field: &str| format!("light needs `position`, or `{}` for an area light", field);
+    if let Some(direction) = description.direction {
+        let angular_diameter = description.angular_diameter.unwrap_or(0.0);
+        let samples = description.samples.unwrap_or(DEFAULT_LIGHT_SAMPLES);
+        if angular_diameter < 0.0 || samples == 0 {
+            return Err(String::from(
+                "a directional light needs a non-negative `angular_diameter` and at least one sample",
+            ));
+        }
+        if angular_diameter == 0.0 {
+            if description.samples.is_some() {
+                return Err(String::from(
+                    "a directional light takes `samples` only with an `angular_diameter`",
+                ));
+            }
+            return Ok(lights::directional_light(vector(direction), intensity));
+        }
+        let mut light = lights::sun_light(
+            vector(direction),
+            angular_diameter.to_radians(),
+            samples,
+            intensity,
+        );
+        light.set_jitter(sequences::Sequence::random(256, AREA_LIGHT_JITTER_SEED));
+        return Ok(light);
+    }
+
+    let missing = |field: &str| {
+        format!(
+            "light needs `position`, `direction`, or `{}` for an area light",
+            field
+        )
+    };
     let corner = description.corner.ok_or_else(|| missing("corner"))?;
     let uvec = description.uvec.ok_or_else(|| missing("uvec"))?;
     let usteps = description.usteps.ok_or_else(|| missing("usteps"))?;
@@ -370,15 +823,35 @@ fn build_world(
     objects: &[ObjectDescription],
     lights: &[LightDescription],
     changes: &BTreeMap<String, ObjectChange>,
+    settings: &RenderSettings,
 ) -> Result<world::World, String> {
     let mut builder = world::WorldBuilder::new();
+    let mut prototypes = HashMap::new();
+    let instanced: HashSet<&str> = objects
+        .iter()
+        .filter_map(|object| object.instance_of.as_deref())
+        .collect();
     for object in objects {
-        builder.add_shape(build_shape(object, changes.get(&object.name))?);
+        let shape = build_shape(object, changes.get(&object.name), &prototypes)?;
+        if !instanced.contains(object.name.as_str()) {
+            builder.add_shape(shape);
+            continue;
+        }
+        // The object itself goes into the world as one more instance of
+        // the shape its instances share.
+        let prototype = Arc::new(shape);
+        builder.add_shape(shape::Shape::instance(prototype.clone(), None));
+        prototypes.insert(object.name.as_str(), prototype);
     }
     for light in lights {
         builder.add_light_source(build_light(light)?);
     }
-    return Ok(builder.world);
+    let mut world = builder.world;
+    world.ambient_occlusion = settings
+        .ambient_occlusion
+        .map(|occlusion| occlusion.build());
+    world.build_bvh();
+    return Ok(world);
 }
 
 fn build_camera(
@@ -386,23 +859,70 @@ fn build_camera(
     change: Option<&CameraChange>,
     settings: &RenderSettings,
     scale: u32,
-) -> camera::Camera {
+) -> Result<camera::Camera, String> {
     let from = change.and_then(|camera| camera.from).unwrap_or(base.from);
     let to = change.and_then(|camera| camera.to).unwrap_or(base.to);
     let up = change.and_then(|camera| camera.up).unwrap_or(base.up);
 
-    let mut camera = camera::Camera::new(
-        settings.width * scale,
-        settings.height * scale,
-        base.field_of_view.to_radians(),
-    );
-    camera.transform = transformation::view_transform(&point(from), &point(to), &vector(up));
-    return camera;
+    let width = settings.width * scale;
+    let height = settings.height * scale;
+    // `validate` has checked each projection has the field it needs.
+    let mut camera = match base.projection {
+        ProjectionKind::Perspective => {
+            camera::Camera::new(width, height, base.field_of_view.unwrap().to_radians())
+        }
+        ProjectionKind::Orthographic => {
+            camera::Camera::orthographic(width, height, base.view_width.unwrap())
+        }
+        ProjectionKind::Equirectangular => camera::Camera::equirectangular(width, height),
+        ProjectionKind::Fisheye => {
+            camera::Camera::fisheye(width, height, base.field_of_view.unwrap().to_radians())
+        }
+    };
+    if from == to {
+        return Err(String::from("the camera's `from` and `to` must differ"));
+    }
+    let view = transformation::view_transform(&point(from), &point(to), &vector(up));
+    camera
+        .try_set_transformation_matrix(view)
+        .map_err(|_| String::from("the camera's `up` must not point along its view direction"))?;
+    camera.set_samples_per_pixel(settings.samples_per_pixel);
+    camera.set_jitter(sequences::Sequence::random(256, CAMERA_JITTER_SEED));
+    match settings.integrator {
+        IntegratorKind::Whitted => {}
+        IntegratorKind::PathTraced => camera.set_integrator(world::Integrator::path_traced()),
+        IntegratorKind::AmbientOcclusion => {
+            let occlusion = settings.ambient_occlusion.unwrap_or_default();
+            camera.set_integrator(world::Integrator::AmbientOcclusion(occlusion.build()));
+        }
+    }
+
+    if base.aperture > 0.0 {
+        // A focus point sets the distance to the plane through it that
+        // faces the camera, measured along the view direction.
+        let forward = tuple::normalize(&(point(to) - point(from)));
+        let focal_distance = match (base.focal_distance, base.focus) {
+            (Some(distance), _) => distance,
+            (None, Some(focus)) => tuple::dot(&(point(focus) - point(from)), &forward),
+            (None, None) => tuple::magnitude(&(point(to) - point(from))),
+        };
+        if focal_distance <= 0.0 {
+            return Err(format!(
+                "the camera focus must be in front of the camera, got a focal distance of {}",
+                focal_distance
+            ));
+        }
+        camera.set_focus(base.aperture, focal_distance);
+    }
+    return Ok(camera);
 }
 
 #[cfg(test)]
 mod scene_file_tests {
     use super::*;
+    use crate::assert_tuple_approx_eq;
+    use crate::ray;
+    use assert_approx_eq::assert_approx_eq;
 
     const MINIMAL_ANIMATION: &str = r#"
         [animation]
@@ -498,7 +1018,12 @@ mod scene_file_tests {
         let animation = AnimationFile::parse(MINIMAL_ANIMATION).unwrap();
         let frame = &animation.frames[1];
 
-        let ball = build_shape(&animation.objects[1], frame.objects.get("ball")).unwrap();
+        let ball = build_shape(
+            &animation.objects[1],
+            frame.objects.get("ball"),
+            &HashMap::new(),
+        )
+        .unwrap();
 
         let expected = matrix::Matrix4::IDENTITY
             .scaling(0.5, 0.5, 0.5)
@@ -511,8 +1036,8 @@ mod scene_file_tests {
     fn test_a_frame_camera_change_overrides_only_the_given_fields() {
         let animation = AnimationFile::parse(MINIMAL_ANIMATION).unwrap();
 
-        let base_camera = animation.build_camera(0, 1);
-        let moved_camera = animation.build_camera(2, 1);
+        let base_camera = animation.build_camera(0, 1).unwrap();
+        let moved_camera = animation.build_camera(2, 1).unwrap();
 
         let expected_base = transformation::view_transform(
             &tuple::Point::new(0.0, 2.0, -4.0),
@@ -524,8 +1049,8 @@ mod scene_file_tests {
             &tuple::Point::new(0.0, 0.7, 0.0),
             &tuple::Vector::new(0.0, 1.0, 0.0),
         );
-        assert_eq!(base_camera.transform, expected_base);
-        assert_eq!(moved_camera.transform, expected_moved);
+        assert_eq!(*base_camera.transformation_matrix(), expected_base);
+        assert_eq!(*moved_camera.transformation_matrix(), expected_moved);
     }
 
     #[test]
@@ -564,6 +1089,86 @@ mod scene_file_tests {
         assert!(error.contains("unknown object `teacup`"), "{}", error);
     }
 
+    #[test]
+    fn test_an_object_scaled_to_nothing_is_an_error() {
+        let source = format!(
+            "{}\n[[frames]]\nobjects.ball.transform = [{{ scale = [1.0, 0.0, 1.0] }}]\n",
+            MINIMAL_ANIMATION
+        );
+        let animation = AnimationFile::parse(&source).unwrap();
+        let last_frame = animation.frame_count() - 1;
+
+        let error = animation.build_world(last_frame).err().unwrap();
+        assert!(error.contains("object `ball`"), "{}", error);
+    }
+
+    #[test]
+    fn test_an_instance_places_the_object_it_names_again() {
+        let source = format!(
+            "{}\n{}",
+            MINIMAL_ANIMATION.split("[[frames]]").next().unwrap(),
+            r#"
+            [[objects]]
+            name = "copy"
+            kind = "instance"
+            instance_of = "ball"
+            transform = [{ translate = [2.0, 0.0, 0.0] }]
+            material = { color = [0.0, 0.0, 1.0] }
+
+            [[frames]]
+            "#
+        );
+        let world = AnimationFile::parse(&source)
+            .unwrap()
+            .build_world(0)
+            .unwrap();
+        let toward = |x| {
+            ray::ray(
+                tuple::Point::new(x, 0.5, -5.0),
+                tuple::Vector::new(0.0, 0.0, 1.0),
+            )
+        };
+
+        let ball = world.closest_hit(&toward(0.0)).unwrap();
+        let copy = world.closest_hit(&toward(2.0)).unwrap();
+
+        assert_eq!(world.shapes().len(), 3);
+        assert!(std::ptr::eq(ball.object, copy.object));
+        assert_eq!(ball.material.color, color::color(1.0, 1.0, 1.0));
+        assert_eq!(copy.material.color, color::color(0.0, 0.0, 1.0));
+        assert_approx_eq!(copy.t, 4.5);
+    }
+
+    #[test]
+    fn test_an_instance_must_name_an_earlier_object() {
+        // The instance goes in first, ahead of the ball as well.
+        let instance = r#"
+            [[objects]]
+            name = "copy"
+            kind = "instance"
+        "#;
+        let scene = |instance: &str| {
+            let source = MINIMAL_ANIMATION
+                .replace("[animation]", "[scene]")
+                .split("[[frames]]")
+                .next()
+                .unwrap()
+                .replace(
+                    "[[objects]]\n        name = \"floor\"",
+                    &format!("{}\n[[objects]]\nname = \"floor\"", instance),
+                );
+            return SceneFile::parse(&source).unwrap().build_world();
+        };
+
+        let error = scene(instance).err().unwrap();
+        assert!(error.contains("no `instance_of`"), "{}", error);
+
+        let error = scene(&format!("{}instance_of = \"ball\"\n", instance))
+            .err()
+            .unwrap();
+        assert!(error.contains("isn't an object before it"), "{}", error);
+    }
+
     #[test]
     fn test_a_light_block_with_area_light_fields_builds_an_area_light() {
         let source = MINIMAL_ANIMATION
@@ -604,4 +1209,521 @@ mod scene_file_tests {
         let error = scene.build_world().err().unwrap();
         assert!(error.contains("corner"), "{}", error);
     }
+
+    #[test]
+    fn test_a_light_block_with_a_direction_builds_a_directional_light() {
+        let source = MINIMAL_ANIMATION
+            .replace("[animation]", "[scene]")
+            .replace(
+                "position = [-6.0, 8.0, -8.0]",
+                "direction = [0.0, -2.0, 0.0]\n        angular_diameter = 0.5",
+            )
+            .split("[[frames]]")
+            .next()
+            .unwrap()
+            .to_string();
+
+        let scene = SceneFile::parse(&source).unwrap();
+        let world = scene.build_world().unwrap();
+
+        match &world.lights[0].kind {
+            lights::LightKind::Directional {
+                direction,
+                angular_diameter,
+                samples,
+                ..
+            } => {
+                assert_eq!(*direction, tuple::Vector::new(0.0, -1.0, 0.0));
+                assert_approx_eq!(*angular_diameter, 0.5_f64.to_radians());
+                assert_eq!(*samples, DEFAULT_LIGHT_SAMPLES);
+            }
+            _ => panic!("expected a directional light"),
+        }
+    }
+
+    // The error building a scene whose light has `fields` in place of its
+    // position.
+    fn light_error(fields: &str) -> String {
+        let source = MINIMAL_ANIMATION
+            .replace("[animation]", "[scene]")
+            .replace("position = [-6.0, 8.0, -8.0]", fields)
+            .split("[[frames]]")
+            .next()
+            .unwrap()
+            .to_string();
+        return SceneFile::parse(&source)
+            .unwrap()
+            .build_world()
+            .err()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_a_light_block_with_a_position_and_a_direction_is_an_error() {
+        let error = light_error(
+            "position = [-6.0, 8.0, -8.0]
+        direction = [0.0, -1.0, 0.0]",
+        );
+
+        assert!(error.contains("`position` or `direction`"), "{}", error);
+    }
+
+    #[test]
+    fn test_a_directional_light_without_a_disk_takes_no_samples() {
+        let error = light_error(
+            "direction = [0.0, -1.0, 0.0]
+        samples = 8",
+        );
+
+        assert!(error.contains("`samples`"), "{}", error);
+    }
+
+    #[test]
+    fn test_a_spotlight_with_a_radius_is_an_error() {
+        let error = light_error(
+            "position = [-6.0, 8.0, -8.0]
+        radius = 0.5
+                     spot = { to = [0.0, 0.0, 0.0], cone_angle = 10.0, fade_angle = 20.0 }",
+        );
+
+        assert!(error.contains("`spot` or `radius`"), "{}", error);
+    }
+
+    #[test]
+    fn test_a_point_light_takes_no_samples_or_normal() {
+        let samples = light_error(
+            "position = [-6.0, 8.0, -8.0]
+        samples = 8",
+        );
+        let normal = light_error(
+            "position = [-6.0, 8.0, -8.0]
+        normal = [0.0, -1.0, 0.0]",
+        );
+
+        assert!(
+            samples.contains("`samples` only with a `radius`"),
+            "{}",
+            samples
+        );
+        assert!(
+            normal.contains("`normal` only with a `radius`"),
+            "{}",
+            normal
+        );
+    }
+
+    #[test]
+    fn test_a_light_block_with_a_radius_builds_a_round_light() {
+        let with_light = |fields: &str| {
+            let source = MINIMAL_ANIMATION
+                .replace("[animation]", "[scene]")
+                .replace(
+                    "position = [-6.0, 8.0, -8.0]",
+                    &format!("position = [-6.0, 8.0, -8.0]\n        {}", fields),
+                )
+                .split("[[frames]]")
+                .next()
+                .unwrap()
+                .to_string();
+            return SceneFile::parse(&source).unwrap().build_world();
+        };
+
+        let sphere = with_light("radius = 0.5").unwrap();
+        let disk =
+            with_light("radius = 0.5\n        normal = [0.0, -1.0, 0.0]\n        samples = 8")
+                .unwrap();
+        let error = with_light("radius = 0.0").err().unwrap();
+
+        match sphere.lights[0].kind {
+            lights::LightKind::Sphere {
+                radius, samples, ..
+            } => {
+                assert_eq!(radius, 0.5);
+                assert_eq!(samples, DEFAULT_LIGHT_SAMPLES);
+            }
+            _ => panic!("expected a sphere light"),
+        }
+        match disk.lights[0].kind {
+            lights::LightKind::Disk {
+                normal, samples, ..
+            } => {
+                assert_eq!(normal, tuple::Vector::new(0.0, -1.0, 0.0));
+                assert_eq!(samples, 8);
+            }
+            _ => panic!("expected a disk light"),
+        }
+        assert!(error.contains("positive `radius`"), "{}", error);
+    }
+
+    #[test]
+    fn test_a_light_block_may_give_a_falloff() {
+        let with_falloff = |falloff: &str| {
+            let source = MINIMAL_ANIMATION
+                .replace("[animation]", "[scene]")
+                .replace(
+                    "intensity = [1.0, 1.0, 1.0]",
+                    &format!("intensity = [1.0, 1.0, 1.0]\n        {}", falloff),
+                )
+                .split("[[frames]]")
+                .next()
+                .unwrap()
+                .to_string();
+            return SceneFile::parse(&source).unwrap().build_world();
+        };
+
+        let cases = [
+            ("", lights::Falloff::None),
+            ("falloff = \"none\"", lights::Falloff::None),
+            ("falloff = \"linear\"", lights::Falloff::Linear),
+            (
+                "falloff = \"inverse_square\"",
+                lights::Falloff::InverseSquare,
+            ),
+            (
+                "falloff = { constant = 1.0, linear = 0.5, quadratic = 0.25 }",
+                lights::Falloff::Coefficients {
+                    constant: 1.0,
+                    linear: 0.5,
+                    quadratic: 0.25,
+                },
+            ),
+        ];
+
+        for (falloff, expected) in cases {
+            let world = with_falloff(falloff).unwrap();
+            assert_eq!(world.lights[0].falloff, expected, "{}", falloff);
+        }
+        let error = with_falloff("falloff = { constant = 0.0, linear = 0.0, quadratic = 0.0 }")
+            .err()
+            .unwrap();
+        assert!(error.contains("non-zero coefficient"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_rejects_a_misspelled_falloff_coefficient() {
+        let source = MINIMAL_ANIMATION.replace(
+            "intensity = [1.0, 1.0, 1.0]",
+            "intensity = [1.0, 1.0, 1.0]\nfalloff = { constant = 1.0, linear = 0.5, quadradic = 0.25 }",
+        );
+
+        let error = AnimationFile::parse(&source).err().unwrap();
+        assert!(error.contains("exactly `constant`"), "{}", error);
+    }
+
+    #[test]
+    fn test_a_light_with_a_projector_table_projects_its_image() {
+        let source = MINIMAL_ANIMATION
+            .replace("[animation]", "[scene]")
+            .split("[[frames]]")
+            .next()
+            .unwrap()
+            .replace(
+                "intensity = [1.0, 1.0, 1.0]",
+                "intensity = [1.0, 1.0, 1.0]\n\
+                 projector = { to = [0.0, 0.0, 0.0], field_of_view = 20.0, image = \"textures/earth.ppm\" }",
+            );
+
+        let world = SceneFile::parse(&source).unwrap().build_world().unwrap();
+
+        let light = &world.lights[0];
+        match &light.kind {
+            lights::LightKind::Projector {
+                half_width,
+                half_height,
+                ..
+            } => assert_approx_eq!(half_width / half_height, 2.0),
+            _ => panic!("expected a projector"),
+        }
+        assert_eq!(light.attenuation_at(&tuple::Point::new(0.0, 0.0, 0.0)), 1.0);
+        assert_eq!(
+            light.attenuation_at(&tuple::Point::new(20.0, 8.0, -8.0)),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_a_projector_is_neither_a_spotlight_nor_round() {
+        let projector = "projector = { to = [0.0, 0.0, 0.0], field_of_view = 20.0, \
+                         image = \"textures/earth.ppm\" }";
+        let spot = light_error(&format!(
+            "position = [-6.0, 8.0, -8.0]\n        {}\n        \
+             spot = {{ to = [0.0, 0.0, 0.0], cone_angle = 10.0, fade_angle = 20.0 }}",
+            projector
+        ));
+        let round = light_error(&format!(
+            "position = [-6.0, 8.0, -8.0]\n        {}\n        radius = 0.5",
+            projector
+        ));
+
+        assert!(spot.contains("`spot` or `projector`"), "{}", spot);
+        assert!(round.contains("`projector` or `radius`"), "{}", round);
+    }
+
+    #[test]
+    fn test_a_projector_with_a_missing_image_is_an_error() {
+        let source = MINIMAL_ANIMATION
+            .replace("[animation]", "[scene]")
+            .split("[[frames]]")
+            .next()
+            .unwrap()
+            .replace(
+                "intensity = [1.0, 1.0, 1.0]",
+                "intensity = [1.0, 1.0, 1.0]\n\
+                 projector = { to = [0.0, 0.0, 0.0], field_of_view = 20.0, image = \"textures/missing.ppm\" }",
+            );
+
+        let error = SceneFile::parse(&source)
+            .unwrap()
+            .build_world()
+            .err()
+            .unwrap();
+
+        assert!(error.contains("textures/missing.ppm"), "{}", error);
+    }
+
+    #[test]
+    fn test_samples_per_pixel_defaults_to_one() {
+        let animation = AnimationFile::parse(MINIMAL_ANIMATION).unwrap();
+
+        assert_eq!(animation.settings().samples_per_pixel, 1);
+    }
+
+    #[test]
+    fn test_samples_per_pixel_sets_the_camera_supersampling() {
+        let source = MINIMAL_ANIMATION.replace("height = 5", "height = 5\nsamples_per_pixel = 16");
+
+        let animation = AnimationFile::parse(&source).unwrap();
+
+        assert_eq!(animation.settings().samples_per_pixel, 16);
+        assert_eq!(
+            animation.build_camera(0, 1).unwrap().samples_per_pixel(),
+            16
+        );
+    }
+
+    #[test]
+    fn test_integrator_defaults_to_whitted() {
+        let animation = AnimationFile::parse(MINIMAL_ANIMATION).unwrap();
+
+        assert_eq!(animation.settings().integrator, IntegratorKind::Whitted);
+    }
+
+    #[test]
+    fn test_integrator_can_be_path_traced() {
+        let source =
+            MINIMAL_ANIMATION.replace("height = 5", "height = 5\nintegrator = \"path_traced\"");
+
+        let animation = AnimationFile::parse(&source).unwrap();
+
+        assert_eq!(animation.settings().integrator, IntegratorKind::PathTraced);
+    }
+
+    #[test]
+    fn test_ambient_occlusion_is_off_unless_the_scene_asks_for_it() {
+        let animation = AnimationFile::parse(MINIMAL_ANIMATION).unwrap();
+
+        assert!(
+            animation
+                .build_world(0)
+                .unwrap()
+                .ambient_occlusion
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_ambient_occlusion_settings_are_passed_to_the_world() {
+        let source = MINIMAL_ANIMATION.replace(
+            "height = 5",
+            "height = 5\nambient_occlusion = { samples = 9, distance = 2.5 }",
+        );
+
+        let world = AnimationFile::parse(&source)
+            .unwrap()
+            .build_world(0)
+            .unwrap();
+
+        let occlusion = world.ambient_occlusion.unwrap();
+        assert_eq!(occlusion.samples, 9);
+        assert_eq!(occlusion.distance, 2.5);
+    }
+
+    #[test]
+    fn test_parse_rejects_ambient_occlusion_without_samples() {
+        let source = MINIMAL_ANIMATION.replace(
+            "height = 5",
+            "height = 5\nambient_occlusion = { samples = 0 }",
+        );
+
+        let error = AnimationFile::parse(&source).err().unwrap();
+
+        assert!(error.contains("ambient_occlusion"), "{}", error);
+    }
+
+    #[test]
+    fn test_material_emission_is_read_from_the_scene_file() {
+        let source = MINIMAL_ANIMATION.replace(
+            "specular = 0.0 }",
+            "specular = 0.0, emission = [2.0, 1.5, 0.5] }",
+        );
+
+        let world = AnimationFile::parse(&source)
+            .unwrap()
+            .build_world(0)
+            .unwrap();
+
+        assert_eq!(
+            world.shapes()[0].material.emission,
+            color::color(2.0, 1.5, 0.5)
+        );
+        assert_eq!(world.shapes()[1].material.emission, color::black());
+    }
+
+    #[test]
+    fn test_a_scene_lit_only_by_emissive_objects_needs_no_lights() {
+        let source = MINIMAL_ANIMATION.replace(
+            "[[lights]]\n        position = [-6.0, 8.0, -8.0]\n        intensity = [1.0, 1.0, 1.0]",
+            "",
+        );
+
+        let world = AnimationFile::parse(&source)
+            .unwrap()
+            .build_world(0)
+            .unwrap();
+
+        assert!(world.lights.is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_samples_per_pixel_that_is_not_a_perfect_square() {
+        let source = MINIMAL_ANIMATION
+            .replace("[animation]", "[scene]")
+            .replace("height = 5", "height = 5\nsamples_per_pixel = 8")
+            .split("[[frames]]")
+            .next()
+            .unwrap()
+            .to_string();
+
+        let error = SceneFile::parse(&source).err().unwrap();
+        assert!(error.contains("perfect square"), "{}", error);
+    }
+
+    #[test]
+    fn test_a_camera_focus_point_sets_the_distance_to_its_plane() {
+        // The focus point is 3 units along the view direction and 1 unit
+        // off to the side, so its plane is 3 units away.
+        let source = MINIMAL_ANIMATION
+            .replace("to = [0.0, 0.7, 0.0]", "to = [0.0, 2.0, 0.0]")
+            .replace(
+                "up = [0.0, 1.0, 0.0]",
+                "up = [0.0, 1.0, 0.0]\naperture = 0.2\nfocus = [1.0, 2.0, -1.0]",
+            );
+
+        let animation = AnimationFile::parse(&source).unwrap();
+        let camera = animation.build_camera(0, 1).unwrap();
+
+        assert_eq!(camera.aperture(), 0.2);
+        assert_approx_eq!(camera.focal_distance(), 3.0);
+    }
+
+    #[test]
+    fn test_a_camera_looking_along_up_is_an_error() {
+        let source = MINIMAL_ANIMATION.replace("up = [0.0, 1.0, 0.0]", "up = [0.0, -1.3, 4.0]");
+
+        let animation = AnimationFile::parse(&source).unwrap();
+        let error = animation.build_camera(0, 1).err().unwrap();
+        assert!(error.contains("must not point along"), "{}", error);
+    }
+
+    #[test]
+    fn test_a_camera_focus_point_behind_the_camera_is_an_error() {
+        let source = MINIMAL_ANIMATION.replace(
+            "up = [0.0, 1.0, 0.0]",
+            "up = [0.0, 1.0, 0.0]\naperture = 0.2\nfocus = [0.0, 2.0, -6.0]",
+        );
+
+        let animation = AnimationFile::parse(&source).unwrap();
+        let error = animation.build_camera(0, 1).err().unwrap();
+        assert!(error.contains("in front of the camera"), "{}", error);
+    }
+
+    #[test]
+    fn test_a_camera_aperture_focuses_on_the_to_point_by_default() {
+        let source = MINIMAL_ANIMATION.replace(
+            "up = [0.0, 1.0, 0.0]",
+            "up = [0.0, 1.0, 0.0]\naperture = 0.2",
+        );
+
+        let animation = AnimationFile::parse(&source).unwrap();
+        let camera = animation.build_camera(0, 1).unwrap();
+
+        // From (0, 2, -4) to (0, 0.7, 0).
+        assert_approx_eq!(camera.focal_distance(), (1.3_f64 * 1.3 + 16.0).sqrt());
+    }
+
+    #[test]
+    fn test_parse_rejects_both_a_focal_distance_and_a_focus_point() {
+        let source = MINIMAL_ANIMATION.replace(
+            "up = [0.0, 1.0, 0.0]",
+            "up = [0.0, 1.0, 0.0]\naperture = 0.2\nfocal_distance = 4.0\nfocus = [0.0, 0.0, 0.0]",
+        );
+
+        let error = AnimationFile::parse(&source).err().unwrap();
+        assert!(error.contains("not both"), "{}", error);
+    }
+
+    #[test]
+    fn test_an_orthographic_camera_fires_parallel_rays() {
+        let source = MINIMAL_ANIMATION.replace(
+            "field_of_view = 60.0",
+            "projection = \"orthographic\"\nview_width = 4.0",
+        );
+
+        let animation = AnimationFile::parse(&source).unwrap();
+        let camera = animation.build_camera(0, 1).unwrap();
+
+        let corner = camera.ray_for_pixel(0, 0).unwrap();
+        let center = camera.ray_for_pixel(5, 2).unwrap();
+        assert_tuple_approx_eq!(corner.direction, center.direction);
+        assert_ne!(corner.origin, center.origin);
+    }
+
+    #[test]
+    fn test_parse_rejects_an_orthographic_camera_without_a_view_width() {
+        let source =
+            MINIMAL_ANIMATION.replace("field_of_view = 60.0", "projection = \"orthographic\"");
+
+        let error = AnimationFile::parse(&source).err().unwrap();
+        assert!(error.contains("view_width"), "{}", error);
+    }
+
+    #[test]
+    fn test_a_fisheye_camera_takes_a_field_of_view_up_to_360_degrees() {
+        let source = MINIMAL_ANIMATION.replace(
+            "field_of_view = 60.0",
+            "projection = \"fisheye\"\nfield_of_view = 360.0",
+        );
+
+        let animation = AnimationFile::parse(&source).unwrap();
+        let camera = animation.build_camera(0, 1).unwrap();
+
+        // The 10x5 image's circle leaves its corners empty.
+        assert!(camera.ray_for_pixel(0, 0).is_none());
+        assert!(camera.ray_for_pixel(5, 2).is_some());
+
+        let too_wide = source.replace("field_of_view = 360.0", "field_of_view = 400.0");
+        let error = AnimationFile::parse(&too_wide).err().unwrap();
+        assert!(error.contains("at most 360"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_rejects_a_field_of_view_for_an_equirectangular_camera() {
+        let source = MINIMAL_ANIMATION.replace(
+            "field_of_view = 60.0",
+            "projection = \"equirectangular\"\nfield_of_view = 60.0",
+        );
+
+        let error = AnimationFile::parse(&source).err().unwrap();
+        assert!(error.contains("takes no `field_of_view`"), "{}", error);
+    }
 }
@@ -1,17 +1,58 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicU64, Ordering};
 
+// Every sequence gets its own id, so each thread can keep a cursor per
+// sequence without the sequence itself holding any mutable state.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // This thread's position in each sequence it has drawn from, as
+    // (sequence id, index) pairs. Only a handful of sequences are in use
+    // at once, so a list is cheaper to search than a map.
+    static CURSORS: RefCell<Vec<(u64, usize)>> = const { RefCell::new(Vec::new()) };
+    // Where a sequence this thread hasn't drawn from yet starts.
+    static START: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A cyclic list of numbers, used to jitter samples deterministically.
+///
+/// The cursor is kept per thread: render threads can share a sequence, and
+/// each walks it in the same order a single-threaded render would.
 pub struct Sequence {
     numbers: Vec<f64>,
-    index: Cell<usize>,
+    id: u64,
+}
+
+// A clone is a new sequence with the same numbers: it keeps its own
+// cursor rather than sharing the original's.
+impl Clone for Sequence {
+    fn clone(&self) -> Sequence {
+        sequence(&self.numbers)
+    }
 }
 
 pub fn sequence(numbers: &[f64]) -> Sequence {
     Sequence {
         numbers: numbers.to_vec(),
-        index: Cell::new(0),
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
     }
 }
 
+/// Rewinds every sequence to its first number, for the calling thread
+/// only. The camera rewinds before each pixel, so a pixel's samples don't
+/// depend on which pixels were rendered before it.
+pub fn rewind() {
+    rewind_to(0);
+}
+
+/// Rewinds every sequence to its `offset`th number (wrapping around), for
+/// the calling thread only. Progressive rendering rewinds each pass to a
+/// different offset, so the passes draw different samples.
+pub fn rewind_to(offset: usize) {
+    CURSORS.with(|cursors| cursors.borrow_mut().clear());
+    START.with(|start| start.set(offset));
+}
+
 impl Sequence {
     pub fn constant(value: f64) -> Sequence {
         sequence(&[value])
@@ -36,10 +77,20 @@ impl Sequence {
     }
 
     pub fn next(&self) -> f64 {
-        let i = self.index.get();
-        let value = self.numbers[i];
-        self.index.set((i + 1) % self.numbers.len());
-        value
+        CURSORS.with(|cursors| {
+            let mut cursors = cursors.borrow_mut();
+            let slot = match cursors.iter().position(|&(id, _)| id == self.id) {
+                Some(slot) => slot,
+                None => {
+                    let start = START.with(|start| start.get()) % self.numbers.len();
+                    cursors.push((self.id, start));
+                    cursors.len() - 1
+                }
+            };
+            let i = cursors[slot].1;
+            cursors[slot].1 = (i + 1) % self.numbers.len();
+            self.numbers[i]
+        })
     }
 }
 
@@ -67,6 +118,47 @@ mod sequences_tests {
         }
     }
 
+    #[test]
+    fn test_rewinding_to_an_offset_starts_every_sequence_there() {
+        let short = sequences::sequence(&[0.1, 0.5, 1.0]);
+        let long = sequences::sequence(&[0.0, 0.2, 0.4, 0.6, 0.8]);
+
+        sequences::rewind_to(4);
+
+        assert_eq!(short.next(), 0.5);
+        assert_eq!(long.next(), 0.8);
+        assert_eq!(long.next(), 0.0);
+        sequences::rewind();
+        assert_eq!(short.next(), 0.1);
+    }
+
+    #[test]
+    fn test_rewinding_restarts_a_sequence() {
+        let generator = sequences::sequence(&[0.1, 0.5, 1.0]);
+        generator.next();
+        generator.next();
+
+        sequences::rewind();
+
+        assert_eq!(generator.next(), 0.1);
+    }
+
+    #[test]
+    fn test_each_thread_walks_a_shared_sequence_from_the_start() {
+        let generator = sequences::sequence(&[0.1, 0.5, 1.0]);
+        generator.next();
+
+        let from_other_thread = std::thread::scope(|scope| {
+            scope
+                .spawn(|| (generator.next(), generator.next()))
+                .join()
+                .unwrap()
+        });
+
+        assert_eq!(from_other_thread, (0.1, 0.5));
+        assert_eq!(generator.next(), 0.5);
+    }
+
     #[test]
     fn test_random_values_stay_within_zero_and_one() {
         let generator = sequences::Sequence::random(1000, 7);
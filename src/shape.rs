@@ -1,13 +1,32 @@
+use std::sync::Arc;
+
 use crate::bounds;
+use crate::bvh;
 use crate::intersection;
 use crate::material;
 use crate::matrix;
 use crate::matrix::{Inverse, Transpose};
+use crate::mesh;
 use crate::ray;
 use crate::tuple;
 
 const EPSILON: f64 = 1e-5;
 
+/// The shape of a bounding volume hierarchy, for comparing how well
+/// `divide` and `divide_sah` organize a model.
+#[derive(Debug, Default, PartialEq)]
+pub struct BvhStats {
+    /// The most groups nested along any path, the outermost included.
+    pub depth: usize,
+    /// Groups holding primitives directly: the ones whose shapes a ray
+    /// that reaches them has to test one by one.
+    pub leaves: usize,
+    /// Every primitive in the hierarchy.
+    pub primitives: usize,
+    /// The most primitives any one group holds directly.
+    pub largest_leaf: usize,
+}
+
 #[derive(Debug, PartialEq)]
 enum ShapeType {
     Sphere,
@@ -56,15 +75,55 @@ enum ShapeType {
         // CSG shape's children never change afterwards.
         bounds: bounds::BoundingBox,
     },
+    Mesh {
+        mesh: mesh::Mesh,
+    },
+    // Another shape placed again under this one's transform, shared rather
+    // than copied so that many instances of a big mesh cost one mesh. Hits
+    // land on the prototype's own shapes, so anything that tells objects
+    // apart by identity (CSG, refraction) sees every instance of one
+    // prototype as the same object.
+    Instance {
+        prototype: Arc<Shape>,
+        // Shades everything in the prototype in place of its own materials.
+        // Boxed, as a material is several times the size of any other
+        // variant.
+        material: Option<Box<material::Material>>,
+    },
     // A stand-in shape for tests: it records that an intersection was
     // attempted, so tests can observe whether an aggregate shape's bounding
     // box check skipped its children.
     #[cfg(test)]
     Test {
-        intersect_called: std::cell::Cell<bool>,
+        intersect_called: IntersectCalled,
     },
 }
 
+// The test shape's record of whether it was intersected. An atomic rather
+// than a `Cell`, so shapes stay shareable between render threads even in
+// test builds.
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct IntersectCalled(std::sync::atomic::AtomicBool);
+
+#[cfg(test)]
+impl IntersectCalled {
+    fn get(&self) -> bool {
+        self.0.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn set(&self, value: bool) {
+        self.0.store(value, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+impl PartialEq for IntersectCalled {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum CsgOperation {
     Union,
@@ -93,34 +152,37 @@ impl CsgOperation {
 
 #[derive(Debug, PartialEq)]
 pub struct Shape {
-    pub transform: matrix::Matrix4,
+    transform: matrix::Matrix4,
+    // The inverse of `transform`, and its transpose, kept in step with it
+    // by `set_transformation_matrix` so that rays and normals don't invert
+    // it again every time they pass through.
+    inverse: matrix::Matrix4,
+    inverse_transpose: matrix::Matrix4,
     pub material: material::Material,
     shape_type: ShapeType,
 }
 
 impl Shape {
-    pub fn default_sphere() -> Shape {
+    fn with_type(shape_type: ShapeType) -> Shape {
         return Shape {
             transform: matrix::Matrix4::IDENTITY,
+            inverse: matrix::Matrix4::IDENTITY,
+            inverse_transpose: matrix::Matrix4::IDENTITY,
             material: material::material(),
-            shape_type: ShapeType::Sphere,
+            shape_type,
         };
     }
 
+    pub fn default_sphere() -> Shape {
+        return Shape::with_type(ShapeType::Sphere);
+    }
+
     pub fn default_plane() -> Shape {
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::material(),
-            shape_type: ShapeType::Plane,
-        };
+        return Shape::with_type(ShapeType::Plane);
     }
 
     pub fn default_cube() -> Shape {
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::material(),
-            shape_type: ShapeType::Cube,
-        };
+        return Shape::with_type(ShapeType::Cube);
     }
 
     pub fn default_cylinder() -> Shape {
@@ -128,15 +190,11 @@ impl Shape {
     }
 
     pub fn cylinder(minimum: f64, maximum: f64, closed: bool) -> Shape {
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::material(),
-            shape_type: ShapeType::Cylinder {
-                minimum,
-                maximum,
-                closed,
-            },
-        };
+        return Shape::with_type(ShapeType::Cylinder {
+            minimum,
+            maximum,
+            closed,
+        });
     }
 
     pub fn default_cone() -> Shape {
@@ -144,37 +202,25 @@ impl Shape {
     }
 
     pub fn cone(minimum: f64, maximum: f64, closed: bool) -> Shape {
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::material(),
-            shape_type: ShapeType::Cone {
-                minimum,
-                maximum,
-                closed,
-            },
-        };
+        return Shape::with_type(ShapeType::Cone {
+            minimum,
+            maximum,
+            closed,
+        });
     }
 
     #[cfg(test)]
     pub(crate) fn test_shape() -> Shape {
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::material(),
-            shape_type: ShapeType::Test {
-                intersect_called: std::cell::Cell::new(false),
-            },
-        };
+        return Shape::with_type(ShapeType::Test {
+            intersect_called: IntersectCalled::default(),
+        });
     }
 
     pub fn default_group() -> Shape {
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::material(),
-            shape_type: ShapeType::Group {
-                children: Vec::new(),
-                bounds: bounds::BoundingBox::empty(),
-            },
-        };
+        return Shape::with_type(ShapeType::Group {
+            children: Vec::new(),
+            bounds: bounds::BoundingBox::empty(),
+        });
     }
 
     pub fn add_child(&mut self, child: Shape) {
@@ -193,16 +239,12 @@ impl Shape {
         bounds.add_box(&left.parent_space_bounds());
         bounds.add_box(&right.parent_space_bounds());
 
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::material(),
-            shape_type: ShapeType::Csg {
-                operation,
-                left: Box::new(left),
-                right: Box::new(right),
-                bounds,
-            },
-        };
+        return Shape::with_type(ShapeType::Csg {
+            operation,
+            left: Box::new(left),
+            right: Box::new(right),
+            bounds,
+        });
     }
 
     pub fn triangle(p1: tuple::Point, p2: tuple::Point, p3: tuple::Point) -> Shape {
@@ -211,18 +253,14 @@ impl Shape {
         let e1 = p2 - p1;
         let e2 = p3 - p1;
         let normal = tuple::normalize(&tuple::cross(&e2, &e1));
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::material(),
-            shape_type: ShapeType::Triangle {
-                p1,
-                p2,
-                p3,
-                e1,
-                e2,
-                normal,
-            },
-        };
+        return Shape::with_type(ShapeType::Triangle {
+            p1,
+            p2,
+            p3,
+            e1,
+            e2,
+            normal,
+        });
     }
 
     pub fn smooth_triangle(
@@ -235,43 +273,270 @@ impl Shape {
     ) -> Shape {
         let e1 = p2 - p1;
         let e2 = p3 - p1;
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::material(),
-            shape_type: ShapeType::SmoothTriangle {
-                p1,
-                p2,
-                p3,
-                e1,
-                e2,
-                n1,
-                n2,
-                n3,
-            },
-        };
+        return Shape::with_type(ShapeType::SmoothTriangle {
+            p1,
+            p2,
+            p3,
+            e1,
+            e2,
+            n1,
+            n2,
+            n3,
+        });
+    }
+
+    pub fn mesh(mesh: mesh::Mesh) -> Shape {
+        return Shape::with_type(ShapeType::Mesh { mesh });
+    }
+
+    /// `prototype` placed under the instance's own transform, shaded with
+    /// `material` if there is one or with the prototype's materials if not.
+    /// The instance's own `material` field isn't used.
+    pub fn instance(prototype: Arc<Shape>, material: Option<material::Material>) -> Shape {
+        return Shape::with_type(ShapeType::Instance {
+            prototype,
+            material: material.map(Box::new),
+        });
     }
 
     pub fn glass_sphere() -> Shape {
-        return Shape {
-            transform: matrix::Matrix4::IDENTITY,
-            material: material::glass(),
-            shape_type: ShapeType::Sphere,
-        };
+        let mut sphere = Shape::default_sphere();
+        sphere.material = material::glass();
+        return sphere;
     }
 
     pub fn transformation_matrix(&self) -> &matrix::Matrix4 {
         &self.transform
     }
 
+    pub fn inverse_transformation_matrix(&self) -> &matrix::Matrix4 {
+        &self.inverse
+    }
+
+    pub fn inverse_transpose_matrix(&self) -> &matrix::Matrix4 {
+        &self.inverse_transpose
+    }
+
+    /// Panics if `new_transform` can't be inverted (a zero scale, say):
+    /// such a shape could never be hit or shaded, so it's caught here
+    /// rather than by the first ray to reach it.
     pub fn set_transformation_matrix(&mut self, new_transform: matrix::Matrix4) {
+        if let Err(error) = self.try_set_transformation_matrix(new_transform) {
+            panic!("{}", error);
+        }
+    }
+
+    /// `set_transformation_matrix` for transforms that come from outside
+    /// the program, like a scene file's: one that can't be inverted is an
+    /// error, and the shape keeps the transform it had.
+    pub fn try_set_transformation_matrix(
+        &mut self,
+        new_transform: matrix::Matrix4,
+    ) -> Result<(), String> {
+        let inverse = new_transform.inverse().ok_or_else(|| {
+            String::from("a shape's transform must be invertible: is it scaled by zero?")
+        })?;
+        self.inverse = inverse;
+        self.inverse_transpose = inverse.transpose();
         self.transform = new_transform;
+        return Ok(());
     }
 
     pub fn intersect(&self, ray: &ray::Ray) -> Vec<intersection::Intersection<'_>> {
-        let local_ray = ray.transform(&self.transformation_matrix().inverse().unwrap());
+        let local_ray = ray.transform(&self.inverse);
         return self.local_intersect(local_ray);
     }
 
+    /// Whether `ray` hits this shape somewhere in front of its origin,
+    /// closer than `max_t`, that `blocks` says stops it. Unlike
+    /// `intersect`, this returns at the first such hit without collecting
+    /// or sorting the rest, so the hits `blocks` sees come in no particular
+    /// order.
+    pub fn occludes<'a>(
+        &'a self,
+        ray: &ray::Ray,
+        max_t: f64,
+        blocks: &mut dyn FnMut(&intersection::Intersection<'a>) -> bool,
+    ) -> bool {
+        let local_ray = ray.transform(&self.inverse);
+        if let ShapeType::Group { children, bounds } = &self.shape_type {
+            if !bounds.intersects(&local_ray) {
+                return false;
+            }
+            // As in `group_local_intersect`, prepend the group's transform
+            // to each child's hit on its way up.
+            let mut blocks_in_parent_space = |intersection: &intersection::Intersection<'a>| {
+                return blocks(&intersection::Intersection {
+                    world_transform: self.transform * intersection.world_transform,
+                    world_inverse: intersection.world_inverse * self.inverse,
+                    world_inverse_transpose: self.inverse_transpose
+                        * intersection.world_inverse_transpose,
+                    ..*intersection
+                });
+            };
+            return children
+                .iter()
+                .any(|child| child.occludes(&local_ray, max_t, &mut blocks_in_parent_space));
+        }
+
+        if let ShapeType::Instance {
+            prototype,
+            material,
+        } = &self.shape_type
+        {
+            let mut blocks_in_parent_space = |intersection: &intersection::Intersection<'a>| {
+                return blocks(&self.instance_hit(material.as_deref(), intersection));
+            };
+            return prototype.occludes(&local_ray, max_t, &mut blocks_in_parent_space);
+        }
+
+        if let ShapeType::Mesh { mesh } = &self.shape_type {
+            return mesh.any_hit(&local_ray, max_t, |hit| {
+                return blocks(&intersection::face_intersection(
+                    hit.t, self, hit.face, hit.u, hit.v,
+                ));
+            });
+        }
+
+        // Anything else reports all its hits at once. (A CSG shape needs
+        // every crossing in order to work out which of them are real.)
+        return self.local_intersect(local_ray).iter().any(|intersection| {
+            intersection.t.is_sign_positive() && intersection.t < max_t && blocks(intersection)
+        });
+    }
+
+    /// Replaces `closest` with this shape's nearest hit in front of `ray`'s
+    /// origin, if that is nearer still, and says whether it did. Unlike
+    /// `intersect`, groups pass `closest` down to their children instead of
+    /// gathering and sorting their hits, and skip any child group whose box
+    /// the ray only enters beyond it.
+    pub fn intersect_closest<'a>(
+        &'a self,
+        ray: &ray::Ray,
+        closest: &mut Option<intersection::Intersection<'a>>,
+    ) -> bool {
+        let local_ray = ray.transform(&self.inverse);
+        if let ShapeType::Group { children, bounds } = &self.shape_type {
+            // A transform carries `t` along with the ray, so the entry `t`
+            // compares directly with the closest hit's.
+            match bounds.entry(&local_ray) {
+                Some(entry) if closest.as_ref().is_none_or(|hit| entry < hit.t) => {}
+                _ => return false,
+            }
+            let mut found = false;
+            for child in children.iter() {
+                found |= child.intersect_closest(&local_ray, closest);
+            }
+            // As in `group_local_intersect`, prepend the group's transform
+            // to a child's hit on its way up.
+            if found {
+                let hit = closest.as_mut().unwrap();
+                hit.world_transform = self.transform * hit.world_transform;
+                hit.world_inverse = hit.world_inverse * self.inverse;
+                hit.world_inverse_transpose = self.inverse_transpose * hit.world_inverse_transpose;
+            }
+            return found;
+        }
+
+        if let ShapeType::Instance {
+            prototype,
+            material,
+        } = &self.shape_type
+        {
+            let found = prototype.intersect_closest(&local_ray, closest);
+            if found {
+                let hit = closest.as_mut().unwrap();
+                *hit = self.instance_hit(material.as_deref(), hit);
+            }
+            return found;
+        }
+
+        // A mesh's own hierarchy prunes its faces against the closest hit.
+        if let ShapeType::Mesh { mesh } = &self.shape_type {
+            let limit = closest.as_ref().map_or(f64::INFINITY, |hit| hit.t);
+            let Some(hit) = mesh.closest_hit(&local_ray, limit) else {
+                return false;
+            };
+            *closest = Some(intersection::face_intersection(
+                hit.t, self, hit.face, hit.u, hit.v,
+            ));
+            return true;
+        }
+
+        // Anything else, CSG shapes included, reports all its hits at once.
+        let mut found = false;
+        for intersection in self.local_intersect(local_ray) {
+            let nearer = closest.as_ref().is_none_or(|hit| intersection.t < hit.t);
+            if intersection.t.is_sign_positive() && nearer {
+                *closest = Some(intersection);
+                found = true;
+            }
+        }
+        return found;
+    }
+
+    // A group's children, an instance's prototype as its only child, or
+    // `None` for any other shape.
+    pub(crate) fn children(&self) -> Option<&[Shape]> {
+        match &self.shape_type {
+            ShapeType::Group { children, .. } => Some(children),
+            ShapeType::Instance { prototype, .. } => Some(std::slice::from_ref(&**prototype)),
+            _ => None,
+        }
+    }
+
+    // The material an instance shades its prototype with, or `None` if it
+    // keeps the prototype's own or isn't an instance.
+    pub(crate) fn material_override(&self) -> Option<&material::Material> {
+        match &self.shape_type {
+            ShapeType::Instance { material, .. } => material.as_deref(),
+            _ => None,
+        }
+    }
+
+    // Carries a hit on an instance's prototype up to the instance's parent
+    // space, as a group does its children's, shading it with `material` if
+    // the instance overrides the prototype's.
+    fn instance_hit<'a>(
+        &'a self,
+        material: Option<&'a material::Material>,
+        hit: &intersection::Intersection<'a>,
+    ) -> intersection::Intersection<'a> {
+        return intersection::Intersection {
+            material: material.unwrap_or(hit.material),
+            world_transform: self.transform * hit.world_transform,
+            world_inverse: hit.world_inverse * self.inverse,
+            world_inverse_transpose: self.inverse_transpose * hit.world_inverse_transpose,
+            ..*hit
+        };
+    }
+
+    // An instance's prototype, or `None` for any other shape.
+    pub(crate) fn prototype(&self) -> Option<&Shape> {
+        match &self.shape_type {
+            ShapeType::Instance { prototype, .. } => Some(prototype),
+            _ => None,
+        }
+    }
+
+    // A mesh's faces, or `None` for any other shape.
+    pub(crate) fn as_mesh(&self) -> Option<&mesh::Mesh> {
+        match &self.shape_type {
+            ShapeType::Mesh { mesh } => Some(mesh),
+            _ => None,
+        }
+    }
+
+    // A flat or smooth triangle's first corner and the edges from it to
+    // the other two, in object space, or `None` for any other shape.
+    pub(crate) fn triangle_edges(&self) -> Option<(tuple::Point, tuple::Vector, tuple::Vector)> {
+        match self.shape_type {
+            ShapeType::Triangle { p1, e1, e2, .. }
+            | ShapeType::SmoothTriangle { p1, e1, e2, .. } => Some((p1, e1, e2)),
+            _ => None,
+        }
+    }
+
     // The shape's axis-aligned bounding box, in object space (before the
     // shape's own transform is applied).
     pub fn bounds(&self) -> bounds::BoundingBox {
@@ -312,6 +577,8 @@ impl Shape {
             }
             ShapeType::Group { bounds, .. } => *bounds,
             ShapeType::Csg { bounds, .. } => *bounds,
+            ShapeType::Mesh { mesh } => mesh.bounds(),
+            ShapeType::Instance { prototype, .. } => prototype.parent_space_bounds(),
             #[cfg(test)]
             ShapeType::Test { .. } => bounds::BoundingBox::new(
                 tuple::Point::new(-1.0, -1.0, -1.0),
@@ -398,6 +665,79 @@ impl Shape {
         }
     }
 
+    // Reorganizes an aggregate's children into a bounding volume hierarchy
+    // by the surface area heuristic, in place of `divide`'s midpoint
+    // splits. Each group is split in two wherever makes a ray cheapest to
+    // trace on average: the children on each side, weighted by the chance
+    // that a ray through the group also passes through that side's box.
+    // Every child lands on one side or the other, so none are left
+    // straddling in the parent. A group stays whole when no split beats
+    // testing its children directly, unless it has more than
+    // `max_leaf_size` of them. Primitives are left untouched.
+    pub fn divide_sah(&mut self, max_leaf_size: usize) {
+        if let ShapeType::Csg { left, right, .. } = &mut self.shape_type {
+            left.divide_sah(max_leaf_size);
+            right.divide_sah(max_leaf_size);
+            return;
+        }
+
+        let split = match &self.shape_type {
+            ShapeType::Group { children, .. } => {
+                let boxes: Vec<bounds::BoundingBox> = children
+                    .iter()
+                    .map(|child| child.parent_space_bounds())
+                    .collect();
+                bvh::sah_split(&boxes, max_leaf_size)
+            }
+            // Primitives are indivisible.
+            _ => return,
+        };
+
+        if let Some(split) = split
+            && let ShapeType::Group { children, .. } = &mut self.shape_type
+        {
+            let (left, right): (Vec<Shape>, Vec<Shape>) = children
+                .drain(..)
+                .partition(|child| split.goes_left(&child.parent_space_bounds()));
+            self.make_subgroup(left);
+            self.make_subgroup(right);
+        }
+
+        if let ShapeType::Group { children, .. } = &mut self.shape_type {
+            for child in children {
+                child.divide_sah(max_leaf_size);
+            }
+        }
+    }
+
+    pub fn bvh_stats(&self) -> BvhStats {
+        let children = match &self.shape_type {
+            ShapeType::Group { children, .. } => children,
+            _ => return BvhStats::default(),
+        };
+
+        let mut stats = BvhStats::default();
+        let mut held = 0;
+        for child in children {
+            if let ShapeType::Group { .. } = child.shape_type {
+                let child_stats = child.bvh_stats();
+                stats.depth = stats.depth.max(child_stats.depth);
+                stats.leaves += child_stats.leaves;
+                stats.primitives += child_stats.primitives;
+                stats.largest_leaf = stats.largest_leaf.max(child_stats.largest_leaf);
+            } else {
+                held += 1;
+            }
+        }
+        stats.depth += 1;
+        if held > 0 {
+            stats.leaves += 1;
+            stats.primitives += held;
+            stats.largest_leaf = stats.largest_leaf.max(held);
+        }
+        return stats;
+    }
+
     fn sphere_local_normal_at(&self, object_point: tuple::Point) -> tuple::Vector {
         object_point - tuple::Point::new(0.0, 0.0, 0.0)
     }
@@ -483,7 +823,7 @@ impl Shape {
             ShapeType::Group { .. } => panic!("groups do not have a local normal"),
             ShapeType::Triangle { normal, .. } => normal,
             // A smooth triangle's normal depends on where the hit landed,
-            // which only the intersection knows; see local_normal_at_with_uv.
+            // which only the intersection knows; see local_normal_at_hit.
             ShapeType::SmoothTriangle { .. } => {
                 panic!("smooth triangles interpolate their normal from the hit's u/v")
             }
@@ -491,6 +831,14 @@ impl Shape {
             // filtered intersections reference the primitive child that was
             // hit, so the child computes the normal.
             ShapeType::Csg { .. } => panic!("csg shapes do not have a local normal"),
+            // Like a smooth triangle's, a mesh's normal depends on the hit:
+            // which face it landed on, and where.
+            ShapeType::Mesh { .. } => {
+                panic!("meshes take their normal from the hit's face and u/v")
+            }
+            // An instance's hits reference the prototype's shapes, which
+            // compute the normal.
+            ShapeType::Instance { .. } => panic!("instances do not have a local normal"),
             #[cfg(test)]
             ShapeType::Test { .. } => {
                 tuple::Vector::new(object_point.x, object_point.y, object_point.z)
@@ -499,25 +847,36 @@ impl Shape {
     }
 
     // The normal for a smooth triangle blends the vertex normals with the
-    // same barycentric weights that locate the hit between the corners.
-    // Every other shape's normal depends only on the point.
-    pub(crate) fn local_normal_at_with_uv(
+    // same barycentric weights that locate the hit between the corners. A
+    // mesh does the same for the face that was hit. Every other shape's
+    // normal depends only on the point.
+    pub(crate) fn local_normal_at_hit(
         &self,
         object_point: tuple::Point,
+        face: usize,
         u: f64,
         v: f64,
     ) -> tuple::Vector {
-        match self.shape_type {
-            ShapeType::SmoothTriangle { n1, n2, n3, .. } => n2 * u + n3 * v + n1 * (1.0 - u - v),
+        match &self.shape_type {
+            ShapeType::SmoothTriangle { n1, n2, n3, .. } => *n2 * u + *n3 * v + *n1 * (1.0 - u - v),
+            ShapeType::Mesh { mesh } => mesh.normal_at(face, u, v),
             _ => self.local_normal_at(object_point),
         }
     }
 
+    // The texture coordinates at a hit `u`, `v` across `face`, for a mesh
+    // whose faces have them; `None` for every other shape.
+    pub(crate) fn surface_uv(&self, face: usize, u: f64, v: f64) -> Option<(f64, f64)> {
+        match &self.shape_type {
+            ShapeType::Mesh { mesh } => mesh.uv_at(face, u, v),
+            _ => None,
+        }
+    }
+
     pub fn normal_at(&self, world_point: tuple::Point) -> tuple::Vector {
-        let transform_inverse = self.transform.inverse().unwrap();
-        let object_point = transform_inverse * world_point;
+        let object_point = self.inverse * world_point;
         let object_normal = self.local_normal_at(object_point);
-        let mut world_normal = transform_inverse.transpose() * object_normal;
+        let mut world_normal = self.inverse_transpose * object_normal;
         // This is sorta a cheat to skip finding the submatrix.
         world_normal.w = 0.0;
         return tuple::normalize(&world_normal);
@@ -554,6 +913,19 @@ impl Shape {
                 ref bounds,
                 ..
             } => self.csg_local_intersect(left, right, bounds, local_ray),
+            ShapeType::Mesh { ref mesh } => mesh
+                .hits(&local_ray)
+                .into_iter()
+                .map(|hit| intersection::face_intersection(hit.t, self, hit.face, hit.u, hit.v))
+                .collect(),
+            ShapeType::Instance {
+                ref prototype,
+                ref material,
+            } => prototype
+                .intersect(&local_ray)
+                .iter()
+                .map(|hit| self.instance_hit(material.as_deref(), hit))
+                .collect(),
             #[cfg(test)]
             ShapeType::Test {
                 ref intersect_called,
@@ -571,35 +943,10 @@ impl Shape {
         e1: tuple::Vector,
         e2: tuple::Vector,
     ) -> Vec<intersection::Intersection<'_>> {
-        // Möller–Trumbore: solve origin + t*dir = p1 + u*e1 + v*e2 for
-        // (t, u, v) via Cramer's rule, where u and v are barycentric
-        // coordinates that must stay within the triangle.
-        let dir_cross_e2 = tuple::cross(&local_ray.direction, &e2);
-        let det = tuple::dot(&e1, &dir_cross_e2);
-
-        // A determinant of zero means the ray is parallel to the triangle.
-        if det.abs() < EPSILON {
-            return vec![];
-        }
-        let f = 1.0 / det;
-
-        // A u outside [0, 1] means the ray passes beyond the p1-p3 edge.
-        let p1_to_origin = local_ray.origin - p1;
-        let u = f * tuple::dot(&p1_to_origin, &dir_cross_e2);
-        if !(0.0..=1.0).contains(&u) {
-            return vec![];
-        }
-
-        // A negative v or u + v beyond 1 means the ray passes beyond the
-        // p1-p2 or p2-p3 edge respectively.
-        let origin_cross_e1 = tuple::cross(&p1_to_origin, &e1);
-        let v = f * tuple::dot(&local_ray.direction, &origin_cross_e1);
-        if v < 0.0 || (u + v) > 1.0 {
-            return vec![];
-        }
-
-        let t = f * tuple::dot(&e2, &origin_cross_e1);
-        return vec![intersection::intersection_with_uv(t, self, u, v)];
+        return match intersect_triangle(&local_ray, &p1, &e1, &e2, 1.0) {
+            Some((t, u, v)) => vec![intersection::intersection_with_uv(t, self, u, v)],
+            None => vec![],
+        };
     }
 
     fn group_local_intersect<'a>(
@@ -620,10 +967,14 @@ impl Shape {
             .collect();
 
         // Each child reports a transform relative to this group, so prepend
-        // the group's own transform to walk the accumulated matrix one level
-        // closer to world space.
+        // the group's own transform (and append its inverse to the
+        // inverse, and prepend its inverse transpose to that transpose) to
+        // walk the accumulated matrices one level closer to world space.
         for intersection in intersections.iter_mut() {
             intersection.world_transform = self.transform * intersection.world_transform;
+            intersection.world_inverse = intersection.world_inverse * self.inverse;
+            intersection.world_inverse_transpose =
+                self.inverse_transpose * intersection.world_inverse_transpose;
         }
 
         intersections.sort_unstable_by(|x, y| x.t.partial_cmp(&y.t).unwrap());
@@ -650,6 +1001,9 @@ impl Shape {
         // child's accumulated matrix one level closer to world space.
         for intersection in intersections.iter_mut() {
             intersection.world_transform = self.transform * intersection.world_transform;
+            intersection.world_inverse = intersection.world_inverse * self.inverse;
+            intersection.world_inverse_transpose =
+                self.inverse_transpose * intersection.world_inverse_transpose;
         }
 
         // The filter walks the crossings in the order the ray meets them,
@@ -699,6 +1053,7 @@ impl Shape {
         match &self.shape_type {
             ShapeType::Group { children, .. } => children.iter().any(|child| child.includes(other)),
             ShapeType::Csg { left, right, .. } => left.includes(other) || right.includes(other),
+            ShapeType::Instance { prototype, .. } => prototype.includes(other),
             _ => std::ptr::eq(self, other),
         }
     }
@@ -821,6 +1176,7 @@ impl Shape {
         return intersections;
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn intersect_caps<'a>(
         &'a self,
         local_ray: &ray::Ray,
@@ -919,6 +1275,46 @@ fn check_cap(ray: &ray::Ray, t: f64, radius: f64) -> bool {
     return x.powf(2.0) + z.powf(2.0) <= radius.powf(2.0);
 }
 
+// Möller–Trumbore: solve origin + t*dir = p1 + u*e1 + v*e2 for (t, u, v)
+// via Cramer's rule, where u and v are barycentric coordinates that must
+// stay within the triangle. The determinant grows with the volume any
+// transform already applied to the triangle and ray scales by, so
+// `det_scale` (that factor) keeps the test for parallel rays the same.
+pub(crate) fn intersect_triangle(
+    ray: &ray::Ray,
+    p1: &tuple::Point,
+    e1: &tuple::Vector,
+    e2: &tuple::Vector,
+    det_scale: f64,
+) -> Option<(f64, f64, f64)> {
+    let dir_cross_e2 = tuple::cross(&ray.direction, e2);
+    let det = tuple::dot(e1, &dir_cross_e2);
+
+    // A determinant of zero means the ray is parallel to the triangle.
+    if det.abs() < EPSILON * det_scale {
+        return None;
+    }
+    let f = 1.0 / det;
+
+    // A u outside [0, 1] means the ray passes beyond the p1-p3 edge.
+    let p1_to_origin = ray.origin - *p1;
+    let u = f * tuple::dot(&p1_to_origin, &dir_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    // A negative v or u + v beyond 1 means the ray passes beyond the
+    // p1-p2 or p2-p3 edge respectively.
+    let origin_cross_e1 = tuple::cross(&p1_to_origin, e1);
+    let v = f * tuple::dot(&ray.direction, &origin_cross_e1);
+    if v < 0.0 || (u + v) > 1.0 {
+        return None;
+    }
+
+    let t = f * tuple::dot(e2, &origin_cross_e1);
+    return Some((t, u, v));
+}
+
 // A fluent alternative to the `default_<shape>()` constructors, which need a
 // `let mut` binding plus separate `set_transformation_matrix`/`material`
 // statements to configure. Each `with_*`/`set_*` call consumes and returns
@@ -999,6 +1395,18 @@ impl ShapeBuilder {
         }
     }
 
+    pub fn mesh(mesh: mesh::Mesh) -> Self {
+        ShapeBuilder {
+            shape: Shape::mesh(mesh),
+        }
+    }
+
+    pub fn instance(prototype: Arc<Shape>, material: Option<material::Material>) -> Self {
+        ShapeBuilder {
+            shape: Shape::instance(prototype, material),
+        }
+    }
+
     pub fn csg(operation: CsgOperation, left: Shape, right: Shape) -> Self {
         ShapeBuilder {
             shape: Shape::csg(operation, left, right),
@@ -1006,7 +1414,7 @@ impl ShapeBuilder {
     }
 
     pub fn set_transform(mut self, transform: matrix::Matrix4) -> Self {
-        self.shape.transform = transform;
+        self.shape.set_transformation_matrix(transform);
         self
     }
 
@@ -1029,6 +1437,7 @@ impl ShapeBuilder {
 mod shape_builder_tests {
     use crate::material;
     use crate::matrix;
+    use crate::matrix::{Inverse, Transpose};
     use crate::shape;
     use crate::transformation::Transform;
 
@@ -1083,6 +1492,50 @@ mod shape_builder_tests {
         expected.set_transformation_matrix(transform);
         assert_eq!(built, expected);
     }
+
+    #[test]
+    fn test_setting_the_transform_keeps_its_inverse_in_step() {
+        let transform = matrix::Matrix4::IDENTITY
+            .scaling(2.0, 3.0, 4.0)
+            .translation(1.0, 2.0, 3.0);
+        let mut shape = shape::Shape::default_sphere();
+
+        shape.set_transformation_matrix(transform);
+
+        assert_eq!(
+            *shape.inverse_transformation_matrix(),
+            transform.inverse().unwrap()
+        );
+        assert_eq!(
+            shape.inverse_transpose,
+            transform.inverse().unwrap().transpose()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must be invertible")]
+    fn test_a_transform_that_cannot_be_inverted_is_rejected() {
+        let mut shape = shape::Shape::default_sphere();
+
+        shape.set_transformation_matrix(matrix::Matrix4::IDENTITY.scaling(1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_trying_a_transform_that_cannot_be_inverted_keeps_the_old_one() {
+        let mut shape = shape::Shape::default_sphere();
+        let transform = matrix::Matrix4::IDENTITY.translation(1.0, 2.0, 3.0);
+        shape.set_transformation_matrix(transform);
+
+        let result =
+            shape.try_set_transformation_matrix(matrix::Matrix4::IDENTITY.scaling(1.0, 0.0, 1.0));
+
+        assert!(result.unwrap_err().contains("must be invertible"));
+        assert_eq!(*shape.transformation_matrix(), transform);
+        assert_eq!(
+            *shape.inverse_transformation_matrix(),
+            transform.inverse().unwrap()
+        );
+    }
 }
 
 #[cfg(test)]
@@ -2058,6 +2511,121 @@ mod smooth_triangle_tests {
     }
 }
 
+#[cfg(test)]
+mod mesh_tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use crate::assert_tuple_approx_eq;
+    use crate::matrix;
+    use crate::mesh;
+    use crate::ray;
+    use crate::shape;
+    use crate::transformation::Transform;
+    use crate::tuple;
+
+    // The smooth triangle tests' triangle as a one-face mesh, beside a flat
+    // face behind it at z = 1.
+    fn mesh() -> shape::Shape {
+        shape::Shape::mesh(mesh::Mesh::new(
+            vec![
+                tuple::Point::new(0.0, 1.0, 0.0),
+                tuple::Point::new(-1.0, 0.0, 0.0),
+                tuple::Point::new(1.0, 0.0, 0.0),
+                tuple::Point::new(0.0, 1.0, 1.0),
+                tuple::Point::new(-1.0, 0.0, 1.0),
+                tuple::Point::new(1.0, 0.0, 1.0),
+            ],
+            vec![
+                tuple::Vector::new(0.0, 1.0, 0.0),
+                tuple::Vector::new(-1.0, 0.0, 0.0),
+                tuple::Vector::new(1.0, 0.0, 0.0),
+            ],
+            vec![],
+            vec![
+                mesh::Face {
+                    vertices: [0, 1, 2],
+                    normals: Some([0, 1, 2]),
+                    uvs: None,
+                },
+                mesh::Face {
+                    vertices: [3, 4, 5],
+                    normals: None,
+                    uvs: None,
+                },
+            ],
+        ))
+    }
+
+    fn ray() -> ray::Ray {
+        ray::ray(
+            tuple::Point::new(-0.2, 0.3, -2.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn test_a_ray_intersects_every_face_of_a_mesh_in_its_path() {
+        let mesh = mesh();
+
+        let intersections = mesh.intersect(&ray());
+
+        assert_eq!(intersections.len(), 2);
+        assert!(intersections.iter().all(|i| std::ptr::eq(i.object, &mesh)));
+        assert_ne!(intersections[0].face, intersections[1].face);
+        for intersection in intersections.iter() {
+            assert_approx_eq!(intersection.u, 0.45, 1e-5f64);
+            assert_approx_eq!(intersection.v, 0.25, 1e-5f64);
+        }
+    }
+
+    #[test]
+    fn test_a_mesh_hit_takes_its_normal_from_the_face_it_hit() {
+        // The front face blends its normals as the smooth triangle does;
+        // the flat face behind it has the triangle's own normal.
+        let mesh = mesh();
+        let mut closest = None;
+
+        assert!(mesh.intersect_closest(&ray(), &mut closest));
+        let front = closest.unwrap();
+        assert_eq!(front.t, 2.0);
+        assert_tuple_approx_eq!(
+            tuple::Vector::new(-0.5547, 0.83205, 0.0),
+            front.normal_at(tuple::Point::new(-0.2, 0.3, 0.0))
+        );
+
+        let mut closest = None;
+        let from_between = ray::ray(
+            tuple::Point::new(-0.2, 0.3, 0.5),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+        mesh.intersect_closest(&from_between, &mut closest);
+        let back = closest.unwrap();
+        assert_eq!(back.t, 0.5);
+        assert_tuple_approx_eq!(
+            tuple::Vector::new(0.0, 0.0, -1.0),
+            back.normal_at(tuple::Point::new(-0.2, 0.3, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_a_transformed_mesh_is_intersected_in_object_space() {
+        let mut mesh = mesh();
+        mesh.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 3.0));
+
+        let mut closest = None;
+        mesh.intersect_closest(&ray(), &mut closest);
+
+        assert_eq!(closest.unwrap().t, 5.0);
+        assert!(mesh.occludes(&ray(), 5.5, &mut |_| true));
+        assert!(!mesh.occludes(&ray(), 4.5, &mut |_| true));
+        assert_eq!(
+            mesh.bounds().min,
+            tuple::Point::new(-1.0, 0.0, 0.0),
+            "bounds are in object space"
+        );
+    }
+}
+
 #[cfg(test)]
 mod plane_tests {
     use crate::ray;
@@ -2334,6 +2902,7 @@ mod csg_tests {
 
 #[cfg(test)]
 mod bounding_box_tests {
+    use crate::intersection;
     use crate::matrix;
     use crate::ray;
     use crate::shape;
@@ -2567,6 +3136,36 @@ mod bounding_box_tests {
         assert!(was_intersected(&children(&group)[0]));
     }
 
+    #[test]
+    fn test_an_occlusion_query_skips_a_groups_children_if_the_box_is_missed() {
+        let mut group = shape::Shape::default_group();
+        group.add_child(shape::Shape::test_shape());
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 1.0, 0.0),
+        );
+
+        group.occludes(&ray, f64::INFINITY, &mut |_| true);
+
+        assert!(!was_intersected(&children(&group)[0]));
+    }
+
+    #[test]
+    fn test_a_closest_hit_query_skips_a_group_entered_beyond_the_closest_hit() {
+        let mut group = shape::Shape::default_group();
+        group.add_child(shape::Shape::test_shape());
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+        let nearer = shape::Shape::default_sphere();
+        let mut closest = Some(intersection::intersection(3.0, &nearer));
+
+        group.intersect_closest(&ray, &mut closest);
+
+        assert!(!was_intersected(&children(&group)[0]));
+    }
+
     #[test]
     fn test_intersecting_a_csg_shape_skips_the_children_if_the_box_is_missed() {
         let csg = shape::Shape::csg(
@@ -2611,7 +3210,6 @@ mod bvh_tests {
     use crate::matrix;
     use crate::shape;
     use crate::transformation::Transform;
-    use crate::tuple;
 
     fn children(group: &shape::Shape) -> &Vec<shape::Shape> {
         match &group.shape_type {
@@ -2769,3 +3367,455 @@ mod bvh_tests {
         );
     }
 }
+
+#[cfg(test)]
+mod sah_tests {
+    use crate::matrix;
+    use crate::shape;
+    use crate::transformation::Transform;
+
+    fn children(group: &shape::Shape) -> &Vec<shape::Shape> {
+        match &group.shape_type {
+            shape::ShapeType::Group { children, .. } => children,
+            _ => panic!("expected a group"),
+        }
+    }
+
+    fn translated_sphere(x: f64, y: f64, z: f64) -> shape::Shape {
+        let mut sphere = shape::Shape::default_sphere();
+        sphere.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(x, y, z));
+        return sphere;
+    }
+
+    fn group_of(spheres_at_x: &[f64]) -> shape::Shape {
+        let mut group = shape::Shape::default_group();
+        for &x in spheres_at_x {
+            group.add_child(translated_sphere(x, 0.0, 0.0));
+        }
+        return group;
+    }
+
+    #[test]
+    fn test_sah_subdividing_a_primitive_does_nothing() {
+        let mut sphere = shape::Shape::default_sphere();
+
+        sphere.divide_sah(1);
+
+        assert_eq!(sphere, shape::Shape::default_sphere());
+    }
+
+    #[test]
+    fn test_sah_splits_two_clusters_apart_and_leaves_each_whole() {
+        let mut group = group_of(&[-6.0, -4.0, 4.0, 6.0]);
+
+        group.divide_sah(4);
+
+        let kids = children(&group);
+        assert_eq!(kids.len(), 2);
+        assert_eq!(
+            children(&kids[0]),
+            &vec![
+                translated_sphere(-6.0, 0.0, 0.0),
+                translated_sphere(-4.0, 0.0, 0.0),
+            ]
+        );
+        assert_eq!(
+            children(&kids[1]),
+            &vec![
+                translated_sphere(4.0, 0.0, 0.0),
+                translated_sphere(6.0, 0.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sah_leaves_overlapping_children_together() {
+        // Any split leaves boxes nearly as big as the whole group's, so
+        // a ray would test almost as many spheres after it as before.
+        let mut group = group_of(&[0.0, 0.5, 1.0]);
+
+        group.divide_sah(4);
+
+        assert_eq!(group, group_of(&[0.0, 0.5, 1.0]));
+    }
+
+    #[test]
+    fn test_sah_splits_a_group_larger_than_the_largest_leaf() {
+        let mut group = group_of(&[0.0, 0.5, 1.0]);
+
+        group.divide_sah(2);
+
+        let kids = children(&group);
+        assert_eq!(kids.len(), 2);
+        assert_eq!(children(&kids[0]).len(), 1);
+        assert_eq!(children(&kids[1]).len(), 2);
+    }
+
+    #[test]
+    fn test_sah_cannot_split_children_with_the_same_centroid() {
+        let mut group = group_of(&[0.0, 0.0, 0.0]);
+
+        group.divide_sah(1);
+
+        assert_eq!(group, group_of(&[0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn test_bvh_stats_describe_the_shape_of_the_tree() {
+        let mut inner = shape::Shape::default_group();
+        inner.add_child(shape::Shape::default_sphere());
+        let mut middle = group_of(&[1.0, 2.0]);
+        middle.add_child(inner);
+        let mut group = group_of(&[0.0]);
+        group.add_child(middle);
+
+        let stats = group.bvh_stats();
+
+        assert_eq!(
+            stats,
+            shape::BvhStats {
+                depth: 3,
+                leaves: 3,
+                primitives: 4,
+                largest_leaf: 2,
+            }
+        );
+        assert_eq!(
+            shape::Shape::default_sphere().bvh_stats(),
+            shape::BvhStats::default()
+        );
+    }
+}
+
+#[cfg(test)]
+mod occlusion_tests {
+    use crate::matrix;
+    use crate::ray;
+    use crate::shape;
+    use crate::transformation::Transform;
+    use crate::tuple;
+
+    // Three unit spheres in a group, strung along the z axis at z = 0, 3
+    // and 6, and a ray from z = -5 through all of them.
+    fn row_of_spheres() -> (shape::Shape, ray::Ray) {
+        let mut group = shape::Shape::default_group();
+        for z in [0.0, 3.0, 6.0] {
+            let mut sphere = shape::Shape::default_sphere();
+            sphere.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, z));
+            group.add_child(sphere);
+        }
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+        return (group, ray);
+    }
+
+    #[test]
+    fn test_an_occlusion_query_stops_at_the_first_blocking_hit() {
+        let (group, ray) = row_of_spheres();
+        let mut seen = 0;
+
+        let occluded = group.occludes(&ray, f64::INFINITY, &mut |_| {
+            seen += 1;
+            return true;
+        });
+
+        assert!(occluded);
+        assert_eq!(seen, 1);
+    }
+
+    #[test]
+    fn test_an_occlusion_query_offers_every_hit_that_does_not_block() {
+        let (group, ray) = row_of_spheres();
+        let mut ts = vec![];
+
+        let occluded = group.occludes(&ray, f64::INFINITY, &mut |intersection| {
+            ts.push(intersection.t);
+            return false;
+        });
+
+        assert!(!occluded);
+        assert_eq!(ts, vec![4.0, 6.0, 7.0, 9.0, 10.0, 12.0]);
+    }
+
+    #[test]
+    fn test_an_occlusion_query_ignores_hits_behind_the_ray_and_past_the_limit() {
+        let (group, mut ray) = row_of_spheres();
+        ray.origin = tuple::Point::new(0.0, 0.0, 0.0);
+        let mut ts = vec![];
+
+        group.occludes(&ray, 4.5, &mut |intersection| {
+            ts.push(intersection.t);
+            return false;
+        });
+
+        assert_eq!(ts, vec![1.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn test_an_occlusion_query_reports_hits_in_world_space() {
+        let (mut group, ray) = row_of_spheres();
+        group.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 10.0));
+        let mut transforms = vec![];
+
+        group.occludes(&ray, f64::INFINITY, &mut |intersection| {
+            transforms.push(intersection.world_transform);
+            return true;
+        });
+
+        assert_eq!(
+            transforms,
+            vec![matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 10.0)]
+        );
+    }
+}
+
+#[cfg(test)]
+mod instance_tests {
+    use std::sync::Arc;
+
+    use crate::assert_tuple_approx_eq;
+    use crate::color;
+    use crate::intersection;
+    use crate::material;
+    use crate::matrix;
+    use crate::ray;
+    use crate::shape;
+    use crate::transformation::Transform;
+    use crate::tuple;
+
+    // A red unit sphere moved 2 along x, inside a group.
+    fn prototype() -> Arc<shape::Shape> {
+        let mut sphere = shape::Shape::default_sphere();
+        sphere.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(2.0, 0.0, 0.0));
+        sphere.material.color = color::color(1.0, 0.0, 0.0);
+        let mut group = shape::Shape::default_group();
+        group.add_child(sphere);
+        return Arc::new(group);
+    }
+
+    fn blue() -> material::Material {
+        let mut material = material::material();
+        material.color = color::color(0.0, 0.0, 1.0);
+        return material;
+    }
+
+    fn ray_toward(x: f64) -> ray::Ray {
+        return ray::ray(
+            tuple::Point::new(x, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn test_instances_share_their_prototype() {
+        let prototype = prototype();
+
+        let first = shape::Shape::instance(prototype.clone(), None);
+        let second = shape::Shape::instance(prototype.clone(), Some(blue()));
+
+        assert_eq!(Arc::strong_count(&prototype), 3);
+        assert!(std::ptr::eq(
+            &first.children().unwrap()[0],
+            &second.children().unwrap()[0]
+        ));
+    }
+
+    #[test]
+    fn test_an_instance_is_bounded_by_its_transformed_prototype() {
+        let mut instance = shape::Shape::instance(prototype(), None);
+        instance.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 4.0));
+
+        let bounds = instance.parent_space_bounds();
+
+        assert_eq!(bounds.min, tuple::Point::new(1.0, -1.0, 3.0));
+        assert_eq!(bounds.max, tuple::Point::new(3.0, 1.0, 5.0));
+    }
+
+    #[test]
+    fn test_a_ray_hits_the_prototype_under_the_instance_transform() {
+        let prototype = prototype();
+        let mut instance = shape::Shape::instance(prototype.clone(), None);
+        instance.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 4.0));
+
+        let intersections = instance.intersect(&ray_toward(2.0));
+
+        assert_eq!(intersections.len(), 2);
+        assert_eq!(intersections[0].t, 8.0);
+        assert_eq!(intersections[1].t, 10.0);
+        let sphere = &prototype.children().unwrap()[0];
+        assert!(std::ptr::eq(intersections[0].object, sphere));
+        assert_eq!(
+            intersections[0].world_transform,
+            matrix::Matrix4::IDENTITY
+                .translation(2.0, 0.0, 0.0)
+                .translation(0.0, 0.0, 4.0)
+        );
+        assert!(instance.intersect(&ray_toward(0.0)).is_empty());
+    }
+
+    #[test]
+    fn test_an_instance_hit_is_shaded_with_the_override_material() {
+        let prototype = prototype();
+        let plain = shape::Shape::instance(prototype.clone(), None);
+        let overridden = shape::Shape::instance(prototype.clone(), Some(blue()));
+
+        let mut plain_hit = None;
+        plain.intersect_closest(&ray_toward(2.0), &mut plain_hit);
+        let mut overridden_hit = None;
+        overridden.intersect_closest(&ray_toward(2.0), &mut overridden_hit);
+
+        assert_eq!(
+            plain_hit.unwrap().material.color,
+            color::color(1.0, 0.0, 0.0)
+        );
+        let overridden_hit = overridden_hit.unwrap();
+        assert_eq!(overridden_hit.material.color, color::color(0.0, 0.0, 1.0));
+        assert_eq!(
+            overridden_hit.object.material.color,
+            color::color(1.0, 0.0, 0.0)
+        );
+        assert!(
+            overridden.occludes(&ray_toward(2.0), f64::INFINITY, &mut |hit| {
+                return hit.material.color == color::color(0.0, 0.0, 1.0);
+            })
+        );
+    }
+
+    #[test]
+    fn test_overlapping_instances_of_one_glass_prototype_are_one_object() {
+        let prototype = Arc::new(shape::Shape::glass_sphere());
+        let mut first = shape::Shape::instance(prototype.clone(), None);
+        first.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, -0.5));
+        let mut second = shape::Shape::instance(prototype.clone(), None);
+        second.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 0.5));
+        let ray = ray_toward(0.0);
+
+        let mut intersections = first.intersect(&ray);
+        intersections.extend(second.intersect(&ray));
+        intersections.sort_by(|a, b| a.t.total_cmp(&b.t));
+        let xs: Vec<&intersection::Intersection> = intersections.iter().collect();
+
+        assert_eq!(intersections[1].t, 4.5);
+        assert!(std::ptr::eq(
+            intersections[0].object,
+            intersections[1].object
+        ));
+        // Entering the second instance reads as leaving the first.
+        let computations = intersection::prepare_computations(&intersections[1], &ray, &xs);
+        assert_eq!(computations.n1, 1.5);
+        assert_eq!(computations.n2, 1.0);
+    }
+
+    #[test]
+    fn test_the_outermost_instance_material_wins() {
+        let inner = Arc::new(shape::Shape::instance(prototype(), Some(blue())));
+        let mut green = material::material();
+        green.color = color::color(0.0, 1.0, 0.0);
+        let outer = shape::Shape::instance(inner, Some(green));
+
+        let intersections = outer.intersect(&ray_toward(2.0));
+
+        assert_eq!(intersections[0].material.color, color::color(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_the_normal_on_an_instance_accounts_for_its_transform() {
+        let mut instance = shape::Shape::instance(prototype(), None);
+        instance.set_transformation_matrix(matrix::Matrix4::IDENTITY.scaling(1.0, 2.0, 1.0));
+        let mut closest = None;
+        let ray = ray::ray(
+            tuple::Point::new(2.0, 1.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+
+        instance.intersect_closest(&ray, &mut closest);
+        let hit = closest.unwrap();
+
+        // The hit is halfway up the sphere in object space, where the
+        // normal is (0, 0.5, -sqrt(0.75)); stretching y by 2 halves the
+        // normal's y.
+        let point = ray.position(hit.t);
+        assert_tuple_approx_eq!(
+            tuple::normalize(&tuple::Vector::new(0.0, 0.25, -0.75f64.sqrt())),
+            hit.normal_at(point)
+        );
+    }
+}
+
+#[cfg(test)]
+mod closest_hit_tests {
+    use crate::intersection;
+    use crate::matrix;
+    use crate::ray;
+    use crate::shape;
+    use crate::transformation::Transform;
+    use crate::tuple;
+
+    // Three unit spheres in a group at z = 0, 3 and 6, added farthest
+    // first, and a ray from z = -5 through all of them.
+    fn row_of_spheres() -> (shape::Shape, ray::Ray) {
+        let mut group = shape::Shape::default_group();
+        for z in [6.0, 3.0, 0.0] {
+            let mut sphere = shape::Shape::default_sphere();
+            sphere.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, z));
+            group.add_child(sphere);
+        }
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+        return (group, ray);
+    }
+
+    #[test]
+    fn test_the_closest_hit_is_the_nearest_one_in_front_of_the_ray() {
+        let (group, mut ray) = row_of_spheres();
+        let mut closest = None;
+
+        assert!(group.intersect_closest(&ray, &mut closest));
+        assert_eq!(closest.unwrap().t, 4.0);
+
+        // From inside the nearest sphere, its far side is the closest hit.
+        ray.origin = tuple::Point::new(0.0, 0.0, 0.0);
+        let mut closest = None;
+        group.intersect_closest(&ray, &mut closest);
+        assert_eq!(closest.unwrap().t, 1.0);
+    }
+
+    #[test]
+    fn test_the_closest_hit_is_kept_if_the_shape_is_hit_farther_away() {
+        let (group, ray) = row_of_spheres();
+        let nearer = shape::Shape::default_sphere();
+        let mut closest = Some(intersection::intersection(1.0, &nearer));
+
+        assert!(!group.intersect_closest(&ray, &mut closest));
+        assert_eq!(closest.unwrap().t, 1.0);
+    }
+
+    #[test]
+    fn test_the_closest_hit_matches_the_first_of_all_the_hits() {
+        let (group, ray) = row_of_spheres();
+        let intersections = group.intersect(&ray);
+        let mut closest = None;
+
+        group.intersect_closest(&ray, &mut closest);
+
+        assert_eq!(closest.as_ref(), ray::hit(&intersections));
+    }
+
+    #[test]
+    fn test_the_closest_hit_is_reported_in_world_space() {
+        let (mut group, ray) = row_of_spheres();
+        group.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 10.0));
+        let mut closest = None;
+
+        group.intersect_closest(&ray, &mut closest);
+
+        assert_eq!(
+            closest.unwrap().world_transform,
+            matrix::Matrix4::IDENTITY.translation(0.0, 0.0, 10.0)
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Stereo pairs for VR previews: a rig of two cameras a short distance
+//! apart, rendered from one scene and packed into a single canvas.
+
+use crate::camera;
+use crate::canvas;
+use crate::color;
+use crate::transformation::Transform;
+use crate::world;
+
+/// How the two eyes are aimed.
+pub enum Convergence {
+    /// Both eyes look the way the rig's camera looks. Distant objects line
+    /// up in the two images; near ones don't.
+    Parallel,
+    /// Each eye turns inward to look at the point `distance` in front of
+    /// the rig's camera, which lines up in the two images.
+    ToeIn { distance: f64 },
+}
+
+/// How the two eyes' images are packed into one canvas.
+pub enum Layout {
+    /// The left eye on the left and the right eye on the right, in a
+    /// canvas twice as wide as either.
+    SideBySide,
+    /// The left eye above the right eye, in a canvas twice as tall.
+    TopBottom,
+    /// One image for red/cyan glasses: the red channel from the left eye
+    /// and the green and blue channels from the right.
+    Anaglyph,
+}
+
+pub struct StereoRig {
+    camera: camera::Camera,
+    interocular: f64,
+    convergence: Convergence,
+}
+
+impl StereoRig {
+    /// A rig whose eyes sit `interocular` apart along `camera`'s
+    /// horizontal axis, with `camera` midway between them. Each eye renders
+    /// at `camera`'s full size.
+    pub fn new(camera: camera::Camera, interocular: f64, convergence: Convergence) -> StereoRig {
+        return StereoRig {
+            camera,
+            interocular,
+            convergence,
+        };
+    }
+
+    pub fn left_eye(&self) -> camera::Camera {
+        // (camera looks towards -z, so +x is the left)
+        return self.eye(self.interocular / 2.0);
+    }
+
+    pub fn right_eye(&self) -> camera::Camera {
+        return self.eye(-self.interocular / 2.0);
+    }
+
+    // A copy of the rig's camera moved `offset` along its x axis.
+    fn eye(&self, offset: f64) -> camera::Camera {
+        let mut eye = self.camera.clone();
+        // The camera transform maps world space to camera space, so moving
+        // the eye means moving everything it sees the other way.
+        let moved = self
+            .camera
+            .transformation_matrix()
+            .translation(-offset, 0.0, 0.0);
+        eye.set_transformation_matrix(moved);
+        if let Convergence::ToeIn { distance } = self.convergence {
+            // Seen from the eye, the point to converge on lies `offset`
+            // to the side; turn until it is straight ahead.
+            let turned = eye
+                .transformation_matrix()
+                .rotation_y(-(offset / distance).atan());
+            eye.set_transformation_matrix(turned);
+        }
+        return eye;
+    }
+
+    /// Renders both eyes, each on `threads` worker threads, and packs them
+    /// into one canvas.
+    pub fn render(&self, world: &world::World, layout: Layout, threads: usize) -> canvas::Canvas {
+        let left = self.left_eye().render_parallel(world, threads);
+        let right = self.right_eye().render_parallel(world, threads);
+        return compose(&left, &right, layout);
+    }
+}
+
+/// Packs a left and right eye image of the same size into one canvas.
+pub fn compose(left: &canvas::Canvas, right: &canvas::Canvas, layout: Layout) -> canvas::Canvas {
+    let (width, height) = (left.width, left.height);
+    let mut image = match layout {
+        Layout::SideBySide => canvas::canvas(width * 2, height),
+        Layout::TopBottom => canvas::canvas(width, height * 2),
+        Layout::Anaglyph => canvas::canvas(width, height),
+    };
+    for y in 0..height {
+        for x in 0..width {
+            let left_color = *left.pixel_at(x, y);
+            let right_color = *right.pixel_at(x, y);
+            match layout {
+                Layout::SideBySide => {
+                    image.write_pixel(x, y, left_color);
+                    image.write_pixel(x + width, y, right_color);
+                }
+                Layout::TopBottom => {
+                    image.write_pixel(x, y, left_color);
+                    image.write_pixel(x, y + height, right_color);
+                }
+                Layout::Anaglyph => {
+                    let color = color::color(left_color.r, right_color.g, right_color.b);
+                    image.write_pixel(x, y, color);
+                }
+            }
+        }
+    }
+    return image;
+}
+
+#[cfg(test)]
+mod stereo_tests {
+    use crate::assert_tuple_approx_eq;
+    use crate::camera;
+    use crate::canvas;
+    use crate::color;
+    use crate::stereo;
+    use crate::transformation;
+    use crate::tuple;
+    use crate::world;
+
+    fn looking_down_z() -> camera::Camera {
+        let mut camera = camera::Camera::new(11, 11, std::f64::consts::PI / 2.0);
+        camera.set_transformation_matrix(transformation::view_transform(
+            &tuple::Point::new(0.0, 0.0, -5.0),
+            &tuple::Point::new(0.0, 0.0, 0.0),
+            &tuple::Vector::new(0.0, 1.0, 0.0),
+        ));
+        return camera;
+    }
+
+    #[test]
+    fn test_parallel_eyes_sit_either_side_of_the_camera_looking_ahead() {
+        let rig = stereo::StereoRig::new(looking_down_z(), 0.5, stereo::Convergence::Parallel);
+
+        let left = rig.left_eye().ray_for_pixel(5, 5).unwrap();
+        let right = rig.right_eye().ray_for_pixel(5, 5).unwrap();
+
+        // Looking down +z, the left is -x.
+        assert_tuple_approx_eq!(left.origin, tuple::Point::new(-0.25, 0.0, -5.0));
+        assert_tuple_approx_eq!(right.origin, tuple::Point::new(0.25, 0.0, -5.0));
+        assert_tuple_approx_eq!(left.direction, tuple::Vector::new(0.0, 0.0, 1.0));
+        assert_tuple_approx_eq!(right.direction, tuple::Vector::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_toed_in_eyes_look_at_the_convergence_point() {
+        let rig = stereo::StereoRig::new(
+            looking_down_z(),
+            0.5,
+            stereo::Convergence::ToeIn { distance: 4.0 },
+        );
+        let convergence_point = tuple::Point::new(0.0, 0.0, -1.0);
+
+        for eye in [rig.left_eye(), rig.right_eye()] {
+            let ray = eye.ray_for_pixel(5, 5).unwrap();
+            let distance = tuple::magnitude(&(convergence_point - ray.origin));
+
+            assert_tuple_approx_eq!(ray.position(distance), convergence_point);
+        }
+    }
+
+    #[test]
+    fn test_composing_eyes_side_by_side_and_top_to_bottom() {
+        let mut left = canvas::canvas(2, 1);
+        left.write_pixel(1, 0, color::color(1.0, 0.0, 0.0));
+        let mut right = canvas::canvas(2, 1);
+        right.write_pixel(1, 0, color::color(0.0, 0.0, 1.0));
+
+        let side_by_side = stereo::compose(&left, &right, stereo::Layout::SideBySide);
+        let top_bottom = stereo::compose(&left, &right, stereo::Layout::TopBottom);
+
+        assert_eq!((side_by_side.width, side_by_side.height), (4, 1));
+        assert_eq!(*side_by_side.pixel_at(1, 0), color::color(1.0, 0.0, 0.0));
+        assert_eq!(*side_by_side.pixel_at(3, 0), color::color(0.0, 0.0, 1.0));
+        assert_eq!((top_bottom.width, top_bottom.height), (2, 2));
+        assert_eq!(*top_bottom.pixel_at(1, 0), color::color(1.0, 0.0, 0.0));
+        assert_eq!(*top_bottom.pixel_at(1, 1), color::color(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_an_anaglyph_takes_red_from_the_left_eye_and_cyan_from_the_right() {
+        let mut left = canvas::canvas(1, 1);
+        left.write_pixel(0, 0, color::color(0.2, 0.4, 0.6));
+        let mut right = canvas::canvas(1, 1);
+        right.write_pixel(0, 0, color::color(0.3, 0.5, 0.7));
+
+        let anaglyph = stereo::compose(&left, &right, stereo::Layout::Anaglyph);
+
+        assert_eq!((anaglyph.width, anaglyph.height), (1, 1));
+        assert_eq!(*anaglyph.pixel_at(0, 0), color::color(0.2, 0.5, 0.7));
+    }
+
+    #[test]
+    fn test_rendering_a_stereo_pair() {
+        let world = world::default_world();
+        let rig = stereo::StereoRig::new(looking_down_z(), 0.5, stereo::Convergence::Parallel);
+
+        let image = rig.render(&world, stereo::Layout::SideBySide, 2);
+
+        let left = rig.left_eye().render(&world);
+        let right = rig.right_eye().render(&world);
+        assert_eq!(
+            image,
+            stereo::compose(&left, &right, stereo::Layout::SideBySide)
+        );
+        // The eyes see the spheres from different places.
+        assert_ne!(left, right);
+    }
+}
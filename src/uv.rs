@@ -110,6 +110,10 @@ pub enum UvMap {
     Spherical,
     Planar,
     Cylindrical,
+    /// The texture coordinates the surface itself carries, as a mesh's
+    /// faces do from an OBJ file's `vt` lines. A point on a surface without
+    /// any is mapped as `Planar` maps it.
+    Surface,
 }
 
 impl UvMap {
@@ -118,6 +122,7 @@ impl UvMap {
             UvMap::Spherical => spherical_map(point),
             UvMap::Planar => planar_map(point),
             UvMap::Cylindrical => cylindrical_map(point),
+            UvMap::Surface => planar_map(point),
         }
     }
 }
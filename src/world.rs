@@ -1,27 +1,303 @@
+use std::sync::OnceLock;
+
+use crate::bvh;
 use crate::color;
 use crate::intersection;
+use crate::lighting;
 use crate::lights;
 use crate::matrix;
 use crate::ray;
+use crate::sequences;
 use crate::shape;
 use crate::transformation::Transform;
 use crate::tuple;
 
+/// Seed for the default path tracing jitter, so path traced renders are
+/// reproducible.
+const PATH_JITTER_SEED: u64 = 0xB0C3;
+
+/// How many bounces a path makes before Russian roulette may end it.
+const ROULETTE_BOUNCES: usize = 3;
+
+/// Paths longer than this end regardless, so a ray trapped between mirrors
+/// or inside glass still terminates.
+const MAX_BOUNCES: usize = 10;
+
+/// Seed for the default ambient occlusion jitter.
+const OCCLUSION_JITTER_SEED: u64 = 0xA0CC;
+
+/// Darkens the ambient term where nearby surfaces hide part of the sky:
+/// in creases, under objects, and where they meet the floor.
+#[derive(Clone)]
+pub struct AmbientOcclusion {
+    /// Rays cast into the hemisphere around each shaded point.
+    pub samples: usize,
+    /// Surfaces farther than this from the point don't occlude it.
+    pub distance: f64,
+    /// Two numbers per ray pick its direction.
+    pub jitter: sequences::Sequence,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: usize, distance: f64) -> AmbientOcclusion {
+        return AmbientOcclusion {
+            samples,
+            distance,
+            jitter: sequences::Sequence::random(1024, OCCLUSION_JITTER_SEED),
+        };
+    }
+}
+
+/// How a render turns the rays it fires into colors.
+#[derive(Clone)]
+pub enum Integrator {
+    /// Direct light from each light plus perfect mirror reflection and
+    /// refraction: `World::color_at`.
+    Whitted,
+    /// Monte Carlo path tracing: `World::path_traced_color`, drawing its
+    /// random numbers from `jitter`.
+    PathTraced { jitter: sequences::Sequence },
+    /// A grayscale image of how unoccluded each visible point is: white
+    /// where nothing is within `distance`, darker the more the sample rays
+    /// hit. Rays that miss everything are white.
+    AmbientOcclusion(AmbientOcclusion),
+}
+
+impl Integrator {
+    pub fn path_traced() -> Integrator {
+        return Integrator::PathTraced {
+            jitter: sequences::Sequence::random(1 << 16, PATH_JITTER_SEED),
+        };
+    }
+}
+
 pub struct World {
     pub lights: Vec<lights::Light>,
-    pub shapes: Vec<shape::Shape>,
+    // Reached through `shapes_mut`, so that changing them drops the
+    // hierarchy compiled from them.
+    shapes: Vec<shape::Shape>,
+    /// Scales each point's ambient term by how unoccluded it is; `None`
+    /// leaves it flat.
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    // `shapes` compiled for tracing, by the first ray traced through them
+    // or by `build_bvh`.
+    bvh: OnceLock<bvh::Bvh>,
+    // Whether rays go through `bvh` rather than walking `shapes`.
+    compiled: bool,
 }
 
 impl World {
+    pub fn shapes(&self) -> &[shape::Shape] {
+        return &self.shapes;
+    }
+
+    /// The world's shapes, to add to or change. The hierarchy compiled
+    /// from them is dropped, and compiled again when the next ray is
+    /// traced.
+    pub fn shapes_mut(&mut self) -> &mut Vec<shape::Shape> {
+        self.bvh = OnceLock::new();
+        return &mut self.shapes;
+    }
+
+    /// Compiles `shapes` into a flattened bounding volume hierarchy now,
+    /// rather than when the first ray is traced, so the time it takes
+    /// isn't counted against the render.
+    pub fn build_bvh(&mut self) {
+        self.bvh = OnceLock::from(bvh::Bvh::build(&self.shapes));
+    }
+
+    /// Whether `closest_hit` and `occluded` trace rays through the compiled
+    /// hierarchy, as they do unless told otherwise, or walk the shape tree
+    /// shape by shape, which tests and benchmarks compare it against.
+    pub fn set_compiled(&mut self, compiled: bool) {
+        self.compiled = compiled;
+    }
+
+    // The compiled hierarchy, compiling it first if no ray has needed it
+    // yet, or `None` when rays walk the shape tree instead.
+    fn bvh(&self) -> Option<&bvh::Bvh> {
+        if !self.compiled {
+            return None;
+        }
+        return Some(self.bvh.get_or_init(|| bvh::Bvh::build(&self.shapes)));
+    }
+
     pub fn color_at(&self, ray: &ray::Ray, remaining: usize) -> color::Color {
-        let intersections = ray.intersect_world(&self);
-        let hit = ray::hit(&intersections);
-        if hit.is_none() {
-            return color::black();
+        let computations = match self.hit_computations(ray) {
+            Some(computations) => computations,
+            None => return color::black(),
+        };
+        return computations.shade_hit(&self, remaining - 1);
+    }
+
+    /// `Shape::intersect_closest` across the whole world, through the
+    /// compiled hierarchy: the first thing `ray` hits in front of its
+    /// origin, found without collecting and sorting every intersection
+    /// along it.
+    pub fn closest_hit(&self, ray: &ray::Ray) -> Option<intersection::Intersection<'_>> {
+        if let Some(bvh) = self.bvh() {
+            return bvh.closest_hit(&self.shapes, ray);
+        }
+        let mut closest = None;
+        for shape in self.shapes.iter() {
+            shape.intersect_closest(ray, &mut closest);
+        }
+        return closest;
+    }
+
+    // The computations for the first thing `ray` hits, if anything. Only
+    // a transparent hit needs the refractive indices either side of it,
+    // which take every intersection along the ray to work out, so the full
+    // list is only gathered then. Otherwise the hit is treated as the only
+    // surface on the ray.
+    fn hit_computations<'a>(&'a self, ray: &'a ray::Ray) -> Option<intersection::Computation<'a>> {
+        let hit = self.closest_hit(ray)?;
+        if hit.material.transparency == 0.0 {
+            return Some(intersection::prepare_computations(&hit, ray, &vec![&hit]));
         }
+        // The indices are found by spotting the hit among the intersections,
+        // so it has to be taken from the same list: the compiled hierarchy
+        // finds the same surface, but not always with a bit-identical `t`
+        // and transforms.
+        let intersections = ray.intersect_world(self);
+        let hit = intersections.iter().find(|i| i.t.is_sign_positive())?;
         let xs: Vec<&intersection::Intersection> = intersections.iter().collect();
-        let computations = intersection::prepare_computations(&hit.unwrap(), &ray, &xs);
-        return computations.shade_hit(&self, remaining - 1);
+        return Some(intersection::prepare_computations(hit, ray, &xs));
+    }
+
+    /// The color seen along `ray`, path traced: the direct light that
+    /// `color_at` computes without its ambient term, plus the light arriving
+    /// from one diffuse bounce in a random direction (cosine weighted, so
+    /// directions near the normal, which contribute most, are picked most).
+    /// Emissive surfaces light the scene through these bounces: a bounce
+    /// that hits one brings back its emission.
+    /// Mirrors and glass recurse as in `color_at`. After `ROULETTE_BOUNCES`
+    /// bounces Russian roulette ends the path with a probability that
+    /// grows as the surface gets darker, and scales up the paths it lets
+    /// through so the average is unchanged. Averaging many samples per
+    /// pixel turns the noise into soft indirect light and color bleeding.
+    pub fn path_traced_color(
+        &self,
+        ray: &ray::Ray,
+        jitter: &sequences::Sequence,
+        bounces: usize,
+    ) -> color::Color {
+        if bounces > MAX_BOUNCES {
+            return color::black();
+        }
+        let computations = match self.hit_computations(ray) {
+            Some(computations) => computations,
+            None => return color::black(),
+        };
+        let material = &computations.material;
+
+        let color = lighting::surface_color(
+            material,
+            &computations.world_inverse,
+            &computations.point,
+            computations.uv,
+        );
+        let mut surface = material.emission;
+        for light in self.lights.iter() {
+            let intensity = lights::intensity_at(light, &computations.over_point, self);
+            surface = surface
+                + lighting::direct_lighting(
+                    material,
+                    color,
+                    light,
+                    &computations.point,
+                    &computations.eyev,
+                    &computations.normalv,
+                    intensity,
+                );
+        }
+
+        let albedo = color * material.diffuse;
+        let survival = if bounces < ROULETTE_BOUNCES {
+            1.0
+        } else {
+            albedo.r.max(albedo.g).max(albedo.b).clamp(0.05, 1.0)
+        };
+        if albedo != color::black() && (survival == 1.0 || jitter.next() < survival) {
+            let direction =
+                cosine_weighted_direction(&computations.normalv, jitter.next(), jitter.next());
+            let bounce = ray::ray(computations.over_point, direction);
+            let incoming = self.path_traced_color(&bounce, jitter, bounces + 1);
+            surface = surface + albedo * incoming * (1.0 / survival);
+        }
+
+        let reflected = if material.reflective > 0.0 {
+            let reflected_ray = ray::ray(computations.over_point, computations.reflectv);
+            self.path_traced_color(&reflected_ray, jitter, bounces + 1) * material.reflective
+        } else {
+            color::black()
+        };
+        let refracted = match refracted_ray(&computations) {
+            Some(refracted_ray) if material.transparency > 0.0 => {
+                self.path_traced_color(&refracted_ray, jitter, bounces + 1) * material.transparency
+            }
+            _ => color::black(),
+        };
+
+        if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = computations.reflectance();
+            return surface + reflected * reflectance + refracted * (1.0 - reflectance);
+        }
+        return surface + reflected + refracted;
+    }
+
+    /// The fraction of `occlusion.samples` rays, cast from `point` in
+    /// cosine weighted directions around `normal`, that get
+    /// `occlusion.distance` away without hitting anything.
+    pub fn unoccluded_fraction(
+        &self,
+        point: &tuple::Point,
+        normal: &tuple::Vector,
+        occlusion: &AmbientOcclusion,
+    ) -> f64 {
+        if occlusion.samples == 0 {
+            return 1.0;
+        }
+        let mut unoccluded = 0;
+        for _ in 0..occlusion.samples {
+            let direction =
+                cosine_weighted_direction(normal, occlusion.jitter.next(), occlusion.jitter.next());
+            let ray = ray::ray(*point, direction);
+            if !self.occluded(&ray, occlusion.distance, &mut |_| true) {
+                unoccluded += 1;
+            }
+        }
+        return unoccluded as f64 / occlusion.samples as f64;
+    }
+
+    /// `Shape::occludes` across the whole world, through the compiled
+    /// hierarchy: whether `ray` hits anything `blocks` accepts closer than
+    /// `max_t`.
+    pub fn occluded<'a>(
+        &'a self,
+        ray: &ray::Ray,
+        max_t: f64,
+        blocks: &mut dyn FnMut(&intersection::Intersection<'a>) -> bool,
+    ) -> bool {
+        if let Some(bvh) = self.bvh() {
+            return bvh.occluded(&self.shapes, ray, max_t, blocks);
+        }
+        return self
+            .shapes
+            .iter()
+            .any(|shape| shape.occludes(ray, max_t, blocks));
+    }
+
+    /// The gray seen along `ray` in an ambient occlusion render: how
+    /// unoccluded the first point it hits is, or white if it hits nothing.
+    pub fn occlusion_color(&self, ray: &ray::Ray, occlusion: &AmbientOcclusion) -> color::Color {
+        let computations = match self.hit_computations(ray) {
+            Some(computations) => computations,
+            None => return color::white(),
+        };
+        let fraction =
+            self.unoccluded_fraction(&computations.over_point, &computations.normalv, occlusion);
+        return color::white() * fraction;
     }
 
     pub fn reflected_color(
@@ -32,14 +308,14 @@ impl World {
         if remaining == 0 {
             return color::black();
         }
-        if computations.object.material.reflective == 0.0 {
+        if computations.material.reflective == 0.0 {
             return color::black();
         }
 
         let reflected_ray = ray::ray(computations.over_point, computations.reflectv);
         let color = self.color_at(&reflected_ray, remaining);
 
-        return color * computations.object.material.reflective;
+        return color * computations.material.reflective;
     }
 
     pub fn refracted_color(
@@ -50,34 +326,63 @@ impl World {
         if remaining == 0 {
             return color::black();
         }
-        if computations.object.material.transparency == 0.0 {
-            return color::black();
-        }
-        // Ratio of first refraction index to the second
-        let n_ratio = computations.n1 / computations.n2;
-        let cos_i = tuple::dot(&computations.eyev, &computations.normalv);
-        let sin2_t = n_ratio.powf(2.0) * (1.0 - cos_i.powf(2.0));
-
-        if sin2_t > 1.0 {
+        if computations.material.transparency == 0.0 {
             return color::black();
         }
-
-        let cos_t = (1.0 - sin2_t).sqrt();
-        let direction =
-            computations.normalv * (n_ratio * cos_i - cos_t) - computations.eyev * n_ratio;
-
-        // Create a new refracted ray
-        let refract_ray = ray::ray(computations.under_point, direction);
+        let refract_ray = match refracted_ray(computations) {
+            Some(refract_ray) => refract_ray,
+            None => return color::black(),
+        };
 
         // `color_at` decrements `remaining` itself (as it does for
         // `reflected_color` above); decrementing here as well would let it
         // reach zero and underflow.
         let refracted_color =
-            self.color_at(&refract_ray, remaining) * computations.object.material.transparency;
+            self.color_at(&refract_ray, remaining) * computations.material.transparency;
         return refracted_color;
     }
 }
 
+// The ray refracted into the surface at the hit, or `None` on total
+// internal reflection.
+fn refracted_ray(computations: &intersection::Computation) -> Option<ray::Ray> {
+    // Ratio of first refraction index to the second
+    let n_ratio = computations.n1 / computations.n2;
+    let cos_i = tuple::dot(&computations.eyev, &computations.normalv);
+    let sin2_t = n_ratio.powf(2.0) * (1.0 - cos_i.powf(2.0));
+
+    if sin2_t > 1.0 {
+        return None;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let direction = computations.normalv * (n_ratio * cos_i - cos_t) - computations.eyev * n_ratio;
+
+    return Some(ray::ray(computations.under_point, direction));
+}
+
+/// A direction in the hemisphere around `normal`, picked by two numbers in
+/// `[0.0, 1.0)` so that uniform numbers give directions weighted by the
+/// cosine of their angle to the normal.
+pub fn cosine_weighted_direction(normal: &tuple::Vector, u: f64, v: f64) -> tuple::Vector {
+    // A uniform point on the unit disk, projected up onto the hemisphere.
+    let radius = u.sqrt();
+    let angle = 2.0 * std::f64::consts::PI * v;
+    let (x, y) = (radius * angle.cos(), radius * angle.sin());
+    let z = (1.0 - u).max(0.0).sqrt();
+
+    // Any two axes perpendicular to the normal and each other.
+    let helper = if normal.x.abs() > 0.9 {
+        tuple::Vector::new(0.0, 1.0, 0.0)
+    } else {
+        tuple::Vector::new(1.0, 0.0, 0.0)
+    };
+    let tangent = tuple::normalize(&tuple::cross(&helper, normal));
+    let bitangent = tuple::cross(normal, &tangent);
+
+    return tangent * x + bitangent * y + *normal * z;
+}
+
 pub fn default_world() -> World {
     let white_point_light =
         lights::point_light(tuple::Point::new(-10.0, 10.0, -10.0), color::white());
@@ -91,22 +396,69 @@ pub fn default_world() -> World {
     World {
         lights: vec![white_point_light],
         shapes: shapes,
+        ambient_occlusion: None,
+        bvh: OnceLock::new(),
+        compiled: true,
     }
 }
 
-pub fn is_shadowed(world: &World, light_position: &tuple::Point, point: &tuple::Point) -> bool {
+/// How much of the light leaving `light_position` gets through to `point`,
+/// channel by channel: white when nothing is in the way and black when
+/// something opaque is. Each transparent surface the light crosses on the
+/// way lets through its `transparency` of it, tinted by the surface's
+/// color, so glass casts a lighter shadow and colored glass a colored one.
+///
+/// The attenuation is per surface, not per object, as it is for rays
+/// refracted through glass: light through a closed glass ball crosses two
+/// surfaces, so it takes the ball's tint and `transparency` twice.
+pub fn transmittance(
+    world: &World,
+    light_position: &tuple::Point,
+    point: &tuple::Point,
+) -> color::Color {
     let v = *light_position - *point;
     let distance = tuple::magnitude(&v);
-    let direction = tuple::normalize(&v);
+    let ray = ray::ray(*point, tuple::normalize(&v));
+
+    return transmittance_along(world, &ray, distance);
+}
 
-    let ray = ray::ray(*point, direction);
-    let intersections = ray.intersect_world(&world);
+/// `transmittance` from a light infinitely far away in `direction`.
+pub fn transmittance_towards(
+    world: &World,
+    point: &tuple::Point,
+    direction: &tuple::Vector,
+) -> color::Color {
+    let ray = ray::ray(*point, *direction);
 
-    let hit = ray::hit(&intersections);
-    if hit.is_some() && hit.unwrap().t < distance {
-        return true;
+    return transmittance_along(world, &ray, f64::INFINITY);
+}
+
+// The light that gets along `ray` through everything less than `distance`
+// along it.
+fn transmittance_along(world: &World, ray: &ray::Ray, distance: f64) -> color::Color {
+    // The tints multiply in whatever order the surfaces turn up, so the
+    // walk can stop at the first opaque one without sorting anything. Every
+    // crossing counts, the way in and the way out of an object alike.
+    let mut transmitted = color::white();
+    let blocked = world.occluded(ray, distance, &mut |intersection| {
+        let material = intersection.material;
+        if material.transparency == 0.0 {
+            return true;
+        }
+        let tint = lighting::surface_color(
+            material,
+            &intersection.world_inverse,
+            &ray.position(intersection.t),
+            intersection.surface_uv(),
+        );
+        transmitted = transmitted * tint * material.transparency;
+        return false;
+    });
+    if blocked {
+        return color::black();
     }
-    false
+    return transmitted;
 }
 
 pub struct WorldBuilder {
@@ -118,12 +470,15 @@ impl WorldBuilder {
         let world = World {
             lights: Vec::new(),
             shapes: Vec::new(),
+            ambient_occlusion: None,
+            bvh: OnceLock::new(),
+            compiled: true,
         };
         WorldBuilder { world }
     }
 
     pub fn add_shape(&mut self, new_shape: shape::Shape) -> &Self {
-        self.world.shapes.push(new_shape);
+        self.world.shapes_mut().push(new_shape);
         return self;
     }
 
@@ -135,17 +490,22 @@ impl WorldBuilder {
 
 #[cfg(test)]
 mod world_tests {
-    use crate::assert_color_approx_eq;
     use crate::color;
     use crate::intersection;
     use crate::lights;
+    use crate::material;
     use crate::matrix;
+    use crate::mesh;
     use crate::patterns;
     use crate::ray;
+    use crate::sequences;
     use crate::shape;
     use crate::transformation::Transform;
     use crate::tuple;
+    use crate::uv;
     use crate::world;
+    use crate::{assert_color_approx_eq, assert_tuple_approx_eq};
+    use assert_approx_eq::assert_approx_eq;
 
     #[test]
     fn empty_world() {
@@ -204,6 +564,170 @@ mod world_tests {
         assert_eq!(intersections[3].t, 6.0_f64);
     }
 
+    #[test]
+    fn the_closest_hit_in_the_default_world() {
+        let world = world::default_world();
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+
+        let hit = world.closest_hit(&ray).unwrap();
+
+        assert_eq!(hit.t, 4.0);
+        assert!(std::ptr::eq(hit.object, &world.shapes[0]));
+
+        // From the center, the inner sphere's far side is nearest.
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, 0.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+        let hit = world.closest_hit(&ray).unwrap();
+        assert_eq!(hit.t, 0.5);
+        assert!(std::ptr::eq(hit.object, &world.shapes[1]));
+    }
+
+    #[test]
+    fn test_the_compiled_hierarchy_shades_the_world_as_the_shape_tree_does() {
+        let mut world = world::default_world();
+        world.set_compiled(false);
+        let rays: Vec<ray::Ray> = (0..5)
+            .map(|i| {
+                ray::ray(
+                    tuple::Point::new(-1.0 + i as f64 * 0.5, 0.3, -5.0),
+                    tuple::Vector::new(0.0, 0.0, 1.0),
+                )
+            })
+            .collect();
+        let expected: Vec<color::Color> = rays.iter().map(|ray| world.color_at(ray, 5)).collect();
+
+        world.set_compiled(true);
+
+        for (ray, expected) in rays.iter().zip(expected) {
+            assert_color_approx_eq!(world.color_at(ray, 5), expected);
+        }
+    }
+
+    #[test]
+    fn test_the_compiled_hierarchy_finds_the_refractive_indices_the_shape_tree_does() {
+        // A glass sphere two transformed groups down, with a glass floor
+        // below: the indices either side of each hit depend on matching the
+        // hit up with its place among all the ray's intersections.
+        let mut builder = world::WorldBuilder::new();
+        let mut floor = shape::Shape::default_plane();
+        floor.material = material::glass();
+        floor.material.refractive_index = 1.2;
+        floor.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, -1.0, 0.0));
+        builder.add_shape(floor);
+        let mut sphere = shape::Shape::glass_sphere();
+        sphere.set_transformation_matrix(matrix::Matrix4::IDENTITY.rotation_y(0.3));
+        let mut inner = shape::Shape::default_group();
+        inner.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .scaling(0.7, 1.3, 0.9)
+                .rotation_z(0.4),
+        );
+        inner.add_child(sphere);
+        let mut outer = shape::Shape::default_group();
+        outer.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .rotation_x(0.2)
+                .translation(0.1, 0.3, 0.2),
+        );
+        outer.add_child(inner);
+        builder.add_shape(outer);
+        let mut world = builder.world;
+        world.set_compiled(false);
+        let rays = [
+            ray::ray(
+                tuple::Point::new(0.05, 0.25, -5.0),
+                tuple::normalize(&tuple::Vector::new(0.01, -0.02, 1.0)),
+            ),
+            ray::ray(
+                tuple::Point::new(0.1, 0.3, 0.2),
+                tuple::normalize(&tuple::Vector::new(0.3, -1.0, 0.2)),
+            ),
+        ];
+        let indices = |world: &world::World, ray: &ray::Ray| {
+            let computations = world.hit_computations(ray).unwrap();
+            return (computations.n1, computations.n2);
+        };
+        let expected: Vec<(f64, f64)> = rays.iter().map(|ray| indices(&world, ray)).collect();
+        assert_eq!(expected, [(1.0, 1.5), (1.5, 1.0)]);
+
+        world.set_compiled(true);
+
+        for (ray, expected) in rays.iter().zip(expected) {
+            assert_eq!(indices(&world, ray), expected);
+        }
+    }
+
+    #[test]
+    fn test_a_world_built_in_code_is_traced_through_its_compiled_hierarchy() {
+        let mut builder = world::WorldBuilder::new();
+        let mut group = shape::Shape::default_group();
+        group.set_transformation_matrix(matrix::Matrix4::IDENTITY.rotation_y(0.5));
+        for x in -3..=3 {
+            let mut sphere = shape::Shape::default_sphere();
+            sphere.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(
+                x as f64 * 1.5,
+                0.0,
+                0.0,
+            ));
+            group.add_child(sphere);
+        }
+        builder.add_shape(group);
+        let world = builder.world;
+
+        let rays: Vec<ray::Ray> = (0..40)
+            .map(|i| {
+                ray::ray(
+                    tuple::Point::new(-6.0 + i as f64 * 0.3, 0.2, -10.0),
+                    tuple::Vector::new(0.0, 0.0, 1.0),
+                )
+            })
+            .collect();
+        let mut hits = 0;
+        for ray in rays.iter() {
+            let expected = world.shapes.iter().fold(None, |mut closest, shape| {
+                shape.intersect_closest(ray, &mut closest);
+                return closest;
+            });
+            let actual = world.closest_hit(ray);
+            assert!(world.bvh.get().is_some());
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(expected), Some(actual)) => {
+                    hits += 1;
+                    assert!(std::ptr::eq(expected.object, actual.object));
+                    assert_approx_eq!(expected.t, actual.t, 1e-9);
+                }
+                (expected, actual) => panic!("expected {:?}, found {:?}", expected, actual),
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn test_changing_the_shapes_compiles_the_hierarchy_again() {
+        let mut world = world::default_world();
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+        assert_eq!(world.closest_hit(&ray).unwrap().t, 4.0);
+
+        let mut wall = shape::Shape::default_plane();
+        wall.set_transformation_matrix(
+            matrix::Matrix4::IDENTITY
+                .rotation_x(std::f64::consts::PI / 2.0)
+                .translation(0.0, 0.0, -3.0),
+        );
+        world.shapes_mut().push(wall);
+
+        assert_approx_eq!(world.closest_hit(&ray).unwrap().t, 2.0);
+    }
+
     #[test]
     fn color_at_when_a_ray_misses() {
         let world = world::default_world();
@@ -250,14 +774,33 @@ mod world_tests {
         assert_color_approx_eq!(color, inner.as_ref().unwrap().material.color);
     }
 
+    #[test]
+    fn occluded_looks_for_a_blocking_hit_before_the_limit() {
+        let world = world::default_world();
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+
+        // The outer sphere is hit first at t = 4, the inner one at 4.5.
+        assert!(!world.occluded(&ray, 3.9, &mut |_| true));
+        assert!(world.occluded(&ray, 4.1, &mut |_| true));
+        assert!(world.occluded(&ray, 4.6, &mut |intersection| {
+            intersection.object == &world.shapes[1]
+        }));
+        assert!(!world.occluded(&ray, 4.4, &mut |intersection| {
+            intersection.object == &world.shapes[1]
+        }));
+    }
+
     #[test]
     fn there_is_no_shadow_when_nothing_is_collinear_with_point_and_light() {
         let world = world::default_world();
         let point = tuple::Point::new(0.0, 10.0, 0.0);
 
         assert_eq!(
-            world::is_shadowed(&world, &world.lights[0].position, &point),
-            false
+            world::transmittance(&world, &world.lights[0].position, &point),
+            color::white()
         );
     }
 
@@ -267,8 +810,8 @@ mod world_tests {
         let point = tuple::Point::new(10.0, -10.0, 10.0);
 
         assert_eq!(
-            world::is_shadowed(&world, &world.lights[0].position, &point),
-            true
+            world::transmittance(&world, &world.lights[0].position, &point),
+            color::black()
         );
     }
 
@@ -278,8 +821,8 @@ mod world_tests {
         let point = tuple::Point::new(-20.0, 20.0, -20.0);
 
         assert_eq!(
-            world::is_shadowed(&world, &world.lights[0].position, &point),
-            false
+            world::transmittance(&world, &world.lights[0].position, &point),
+            color::white()
         );
     }
 
@@ -289,8 +832,74 @@ mod world_tests {
         let point = tuple::Point::new(-2.0, 2.0, -2.0);
 
         assert_eq!(
-            world::is_shadowed(&world, &world.lights[0].position, &point),
-            false
+            world::transmittance(&world, &world.lights[0].position, &point),
+            color::white()
+        );
+    }
+
+    // A light at (0, 0, -10) behind a sphere at the origin made of
+    // `material`.
+    fn light_behind(material: material::Material) -> world::World {
+        let mut builder = world::WorldBuilder::new();
+        builder.add_light_source(lights::point_light(
+            tuple::Point::new(0.0, 0.0, -10.0),
+            color::white(),
+        ));
+        let mut sphere = shape::Shape::default_sphere();
+        sphere.material = material;
+        builder.add_shape(sphere);
+        return builder.world;
+    }
+
+    #[test]
+    fn light_passes_through_clear_glass() {
+        let world = light_behind(material::glass());
+        let point = tuple::Point::new(0.0, 0.0, 10.0);
+
+        assert_eq!(
+            world::transmittance(&world, &world.lights[0].position, &point),
+            color::white()
+        );
+    }
+
+    #[test]
+    fn tinted_glass_casts_a_colored_shadow() {
+        let mut glass = material::glass();
+        glass.color = color::color(1.0, 0.5, 0.25);
+        glass.transparency = 0.8;
+        let world = light_behind(glass);
+        let point = tuple::Point::new(0.0, 0.0, 10.0);
+
+        let transmitted = world::transmittance(&world, &world.lights[0].position, &point);
+
+        // The light crosses the sphere's surface twice, going in and out.
+        let once = color::color(0.8, 0.4, 0.2);
+        assert_color_approx_eq!(transmitted, once * once);
+    }
+
+    #[test]
+    fn a_point_behind_one_tinted_sphere_gets_its_tint_once_per_surface() {
+        let mut glass = material::glass();
+        glass.color = color::color(1.0, 0.5, 0.25);
+        glass.transparency = 0.8;
+        let world = light_behind(glass);
+        let point = tuple::Point::new(0.0, 0.0, 10.0);
+
+        let intensity = lights::intensity_at(&world.lights[0], &point, &world);
+
+        assert_color_approx_eq!(intensity, color::color(0.64, 0.16, 0.04));
+    }
+
+    #[test]
+    fn transparent_surfaces_past_the_light_cast_no_shadow() {
+        let mut glass = material::glass();
+        glass.color = color::color(1.0, 0.5, 0.25);
+        let world = light_behind(glass);
+        let point = tuple::Point::new(0.0, 0.0, -20.0);
+
+        assert_eq!(
+            world::transmittance(&world, &world.lights[0].position, &point),
+            color::white()
         );
     }
 
@@ -503,7 +1112,7 @@ mod world_tests {
             tuple::Point::new(0.0, 0.0, -5.0),
             tuple::Vector::new(0.0, 0.0, 1.0),
         );
-        let intersections = vec![
+        let intersections = [
             intersection::intersection(4.0, &world.shapes[0]),
             intersection::intersection(6.0, &world.shapes[0]),
         ];
@@ -535,7 +1144,7 @@ mod world_tests {
             tuple::Point::new(0.0, 0.0, -5.0),
             tuple::Vector::new(0.0, 0.0, 1.0),
         );
-        let intersections = vec![
+        let intersections = [
             intersection::intersection(4.0, &world.shapes[0]),
             intersection::intersection(6.0, &world.shapes[0]),
         ];
@@ -567,7 +1176,7 @@ mod world_tests {
             tuple::Point::new(0.0, 0.0, 2.0_f64.sqrt() / 2.0),
             tuple::Vector::new(0.0, 1.0, 0.0),
         );
-        let intersections = vec![
+        let intersections = [
             intersection::intersection(-2.0_f64.sqrt() / 2.0, &world.shapes[0]),
             intersection::intersection(2.0_f64.sqrt() / 2.0, &world.shapes[0]),
         ];
@@ -608,7 +1217,7 @@ mod world_tests {
             tuple::Point::new(0.0, 0.0, 0.1),
             tuple::Vector::new(0.0, 1.0, 0.0),
         );
-        let intersections = vec![
+        let intersections = [
             intersection::intersection(-0.9899, &world.shapes[0]),
             intersection::intersection(-0.4899, &world.shapes[1]),
             intersection::intersection(0.4899, &world.shapes[1]),
@@ -672,7 +1281,9 @@ mod world_tests {
 
         let color = computations.shade_hit(&world, 5);
 
-        let expected_color = color::color(0.93642, 0.68642, 0.68642);
+        // Brighter in red than the book's (0.93642, 0.68642, 0.68642): half
+        // the light now reaches the red ball through the transparent floor.
+        let expected_color = color::color(1.12547, 0.68642, 0.68642);
         assert_color_approx_eq!(color, expected_color);
     }
 
@@ -726,7 +1337,9 @@ mod world_tests {
 
         let color = computations.shade_hit(&world, 5);
 
-        let expected_color = color::color(0.93391, 0.69643, 0.69243);
+        // The book's (0.93391, 0.69643, 0.69243), but with the red ball lit
+        // through the transparent floor.
+        let expected_color = color::color(1.11500, 0.69643, 0.69243);
         assert_color_approx_eq!(color, expected_color);
     }
 
@@ -762,4 +1375,215 @@ mod world_tests {
 
         assert_color_approx_eq!(color, color::white());
     }
+
+    #[test]
+    fn a_mesh_is_textured_by_its_own_coordinates() {
+        // One face across the square from (-1, -1) to (1, 1), its texture
+        // coordinates running from (0, 0) to (1, 1) over the same square.
+        let mut builder = world::WorldBuilder::new();
+        builder.add_light_source(lights::point_light(
+            tuple::Point::new(0.0, 0.0, -10.0),
+            color::white(),
+        ));
+        let mut mesh = shape::Shape::mesh(mesh::Mesh::new(
+            vec![
+                tuple::Point::new(-1.0, -1.0, 0.0),
+                tuple::Point::new(1.0, -1.0, 0.0),
+                tuple::Point::new(1.0, 1.0, 0.0),
+            ],
+            vec![],
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            vec![mesh::Face {
+                vertices: [0, 1, 2],
+                normals: None,
+                uvs: Some([0, 1, 2]),
+            }],
+        ));
+        let checkers = uv::UvPattern::checkers(2, 2, color::black(), color::white());
+        mesh.material.pattern = Some(patterns::Pattern::texture_map(checkers, uv::UvMap::Surface));
+        mesh.material.ambient = 1.0;
+        mesh.material.diffuse = 0.0;
+        mesh.material.specular = 0.0;
+        builder.add_shape(mesh);
+        let world = builder.world;
+        let color_at = |x: f64, y: f64| {
+            let ray = ray::ray(
+                tuple::Point::new(x, y, -5.0),
+                tuple::Vector::new(0.0, 0.0, 1.0),
+            );
+            return world.color_at(&ray, 10);
+        };
+
+        assert_color_approx_eq!(color_at(0.5, -0.5), color::white());
+        assert_color_approx_eq!(color_at(0.8, 0.3), color::black());
+    }
+
+    #[test]
+    fn cosine_weighted_directions_stay_in_the_hemisphere_around_the_normal() {
+        let normal = tuple::normalize(&tuple::Vector::new(1.0, -2.0, 0.5));
+
+        assert_tuple_approx_eq!(world::cosine_weighted_direction(&normal, 0.0, 0.3), normal);
+        for (u, v) in [(0.2, 0.1), (0.5, 0.5), (0.99, 0.75), (0.7, 0.95)] {
+            let direction = world::cosine_weighted_direction(&normal, u, v);
+            assert_approx_eq!(tuple::magnitude(&direction), 1.0);
+            // The cosine to the normal is the height above the disk.
+            assert_approx_eq!(tuple::dot(&direction, &normal), (1.0 - u).sqrt());
+        }
+    }
+
+    #[test]
+    fn path_tracing_replaces_the_ambient_term_with_bounced_light() {
+        // Bounces off the outer sphere escape into the empty sky, so the
+        // only light is the direct light, without the ambient term.
+        let world = world::default_world();
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+        let jitter = sequences::Sequence::random(64, 1);
+
+        let color = world.path_traced_color(&ray, &jitter, 0);
+
+        let ambient = color::color(0.8, 1.0, 0.6) * 0.1;
+        assert_color_approx_eq!(color, world.color_at(&ray, 10) - ambient);
+    }
+
+    #[test]
+    fn path_tracing_picks_up_light_bounced_off_other_surfaces() {
+        // Every bounce off the floor hits the ceiling, lit from below.
+        let mut builder = world::WorldBuilder::new();
+        builder.add_light_source(lights::point_light(
+            tuple::Point::new(0.0, 1.0, 0.0),
+            color::white(),
+        ));
+        builder.add_shape(shape::Shape::default_plane());
+        let mut ceiling = shape::Shape::default_plane();
+        ceiling.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 2.0, 0.0));
+        builder.add_shape(ceiling);
+        let world = builder.world;
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 1.0, -1.0),
+            tuple::normalize(&tuple::Vector::new(0.0, -1.0, 1.0)),
+        );
+        let jitter = sequences::Sequence::random(64, 1);
+
+        let color = world.path_traced_color(&ray, &jitter, 0);
+
+        let direct = world.color_at(&ray, 1) - color::color(0.1, 0.1, 0.1);
+        assert!(color.r > direct.r + 0.01, "{:?} vs {:?}", color, direct);
+        assert_color_approx_eq!(color::color(color.r, color.r, color.r), color);
+    }
+
+    #[test]
+    fn path_tracing_a_ray_that_misses_is_black() {
+        let world = world::default_world();
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 1.0, 0.0),
+        );
+        let jitter = sequences::Sequence::random(64, 1);
+
+        assert_eq!(world.path_traced_color(&ray, &jitter, 0), color::black());
+    }
+
+    #[test]
+    fn an_emissive_surface_glows_without_any_lights() {
+        let mut builder = world::WorldBuilder::new();
+        let mut sphere = shape::Shape::default_sphere();
+        sphere.material.emission = color::color(1.5, 1.0, 0.5);
+        builder.add_shape(sphere);
+        let world = builder.world;
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.0, -5.0),
+            tuple::Vector::new(0.0, 0.0, 1.0),
+        );
+
+        assert_eq!(world.color_at(&ray, 10), color::color(1.5, 1.0, 0.5));
+    }
+
+    #[test]
+    fn path_tracing_lights_a_surface_with_an_emissive_ceiling() {
+        // Every bounce off the floor hits the glowing ceiling, which is
+        // the only light in the scene.
+        let mut builder = world::WorldBuilder::new();
+        let mut floor = shape::Shape::default_plane();
+        floor.material.specular = 0.0;
+        builder.add_shape(floor);
+        let mut ceiling = shape::Shape::default_plane();
+        ceiling.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 1.0, 0.0));
+        ceiling.material.diffuse = 0.0;
+        ceiling.material.emission = color::color(1.0, 0.5, 0.25);
+        builder.add_shape(ceiling);
+        let world = builder.world;
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.5, -1.0),
+            tuple::normalize(&tuple::Vector::new(0.0, -0.5, 1.0)),
+        );
+        let jitter = sequences::Sequence::random(64, 1);
+
+        let color = world.path_traced_color(&ray, &jitter, 0);
+
+        assert_color_approx_eq!(color, color::color(1.0, 0.5, 0.25) * 0.9);
+    }
+
+    // A floor with a ceiling one unit above it.
+    fn floor_and_ceiling() -> world::World {
+        let mut builder = world::WorldBuilder::new();
+        builder.add_light_source(lights::point_light(
+            tuple::Point::new(0.0, 0.5, 0.0),
+            color::white(),
+        ));
+        builder.add_shape(shape::Shape::default_plane());
+        let mut ceiling = shape::Shape::default_plane();
+        ceiling.set_transformation_matrix(matrix::Matrix4::IDENTITY.translation(0.0, 1.0, 0.0));
+        builder.add_shape(ceiling);
+        return builder.world;
+    }
+
+    #[test]
+    fn a_point_is_occluded_by_surfaces_within_the_occlusion_distance() {
+        let world = floor_and_ceiling();
+        let point = tuple::Point::new(0.0, 0.0001, 0.0);
+        let up = tuple::Vector::new(0.0, 1.0, 0.0);
+
+        let near = world::AmbientOcclusion::new(8, 0.5);
+        let far = world::AmbientOcclusion::new(8, 100.0);
+
+        assert_eq!(world.unoccluded_fraction(&point, &up, &near), 1.0);
+        assert_eq!(world.unoccluded_fraction(&point, &up, &far), 0.0);
+    }
+
+    #[test]
+    fn ambient_occlusion_scales_only_the_ambient_term() {
+        let mut world = floor_and_ceiling();
+        let ray = ray::ray(
+            tuple::Point::new(0.0, 0.5, -1.0),
+            tuple::normalize(&tuple::Vector::new(0.0, -0.5, 1.0)),
+        );
+        let plain = world.color_at(&ray, 1);
+
+        world.ambient_occlusion = Some(world::AmbientOcclusion::new(8, 100.0));
+        let occluded = world.color_at(&ray, 1);
+
+        // Fully occluded: the floor's ambient 0.1 is gone, its direct light
+        // is not.
+        assert_color_approx_eq!(occluded, plain - color::color(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn an_occlusion_render_is_white_where_rays_miss_and_gray_where_they_hit() {
+        let world = floor_and_ceiling();
+        let occlusion = world::AmbientOcclusion::new(8, 100.0);
+        let miss = ray::ray(
+            tuple::Point::new(0.0, 0.5, 0.0),
+            tuple::Vector::new(1.0, 0.0, 0.0),
+        );
+        let hit = ray::ray(
+            tuple::Point::new(0.0, 0.5, 0.0),
+            tuple::Vector::new(0.0, -1.0, 0.0),
+        );
+
+        assert_eq!(world.occlusion_color(&miss, &occlusion), color::white());
+        assert_eq!(world.occlusion_color(&hit, &occlusion), color::black());
+    }
 }
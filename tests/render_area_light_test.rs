@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -79,11 +81,11 @@ fn test_area_light() -> Result<(), std::io::Error> {
     builder.add_light_source(light);
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 2.5, -7.0),
         &tuple::Point::new(0.0, 1.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -136,11 +138,11 @@ fn test_soft_shadow_teapot() -> Result<(), std::io::Error> {
     builder.add_light_source(light);
 
     let mut camera = camera::Camera::new(100 * SCALE, 75 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.5, 2.5, -5.0),
         &tuple::Point::new(0.5, 0.6, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -59,11 +61,11 @@ fn test_simple_cube() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 2.0, -4.0),
         &tuple::Point::new(0.0, 0.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -92,11 +94,11 @@ fn test_simple_cylinder() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 3.0, -4.5),
         &tuple::Point::new(0.0, 1.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -126,11 +128,11 @@ fn test_open_cylinder() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 3.5, -4.0),
         &tuple::Point::new(0.0, 1.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -162,11 +164,11 @@ fn test_open_cone() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 1.6, -2.5),
         &tuple::Point::new(0.0, 0.5, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -197,11 +199,11 @@ fn test_simple_cone() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 1.5, -4.0),
         &tuple::Point::new(0.0, 0.5, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -231,11 +233,11 @@ fn test_simple_plane() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 1.5, -5.0),
         &tuple::Point::new(0.0, 0.0, 5.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
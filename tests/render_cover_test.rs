@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -137,11 +139,11 @@ fn test_cover_scene() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, 0.785);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(-6.0, 6.0, -10.0),
         &tuple::Point::new(6.0, 0.0, 6.0),
         &tuple::Vector::new(-0.45, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
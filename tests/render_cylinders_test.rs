@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -105,11 +107,11 @@ fn test_cylinder_scene() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(150 * SCALE, 100 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 2.5, -5.5),
         &tuple::Point::new(0.0, 1.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
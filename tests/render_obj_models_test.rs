@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -42,6 +44,11 @@ fn load_model(path: &str) -> Result<shape::Shape, std::io::Error> {
     return Ok(obj_file::parse_obj(&source).into_group());
 }
 
+fn load_mesh(path: &str) -> Result<shape::Shape, std::io::Error> {
+    let source = std::fs::read_to_string(path)?;
+    return Ok(obj_file::parse_obj(&source).into_mesh());
+}
+
 // Every model stands on the same matte floor, lit from the upper left, with
 // the camera looking slightly down at it from -z.
 fn render_model(model: shape::Shape, from: tuple::Point, to: tuple::Point) -> canvas::Canvas {
@@ -66,8 +73,11 @@ fn render_model(model: shape::Shape, from: tuple::Point, to: tuple::Point) -> ca
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 75 * SCALE, std::f64::consts::PI / 3.0);
-    camera.transform =
-        transformation::view_transform(&from, &to, &tuple::Vector::new(0.0, 1.0, 0.0));
+    camera.set_transformation_matrix(transformation::view_transform(
+        &from,
+        &to,
+        &tuple::Vector::new(0.0, 1.0, 0.0),
+    ));
 
     return camera.render(&builder.world);
 }
@@ -168,3 +178,73 @@ fn test_teddy() -> Result<(), std::io::Error> {
     assert_matches_fixture(&canvas, "teddy");
     return Ok(());
 }
+
+// The high resolution teapot again, loaded as one mesh sharing the file's
+// vertices and normals instead of a group of separate triangles. It must
+// render identically.
+#[test]
+fn test_high_poly_teapot_as_a_mesh() -> Result<(), std::io::Error> {
+    let teapot = shape::ShapeBuilder::from(load_mesh("object_files/teapot.obj")?)
+        .set_transform(
+            matrix::Matrix4::IDENTITY
+                .rotation_x(-std::f64::consts::PI / 2.0)
+                .scaling(0.1, 0.1, 0.1),
+        )
+        .build();
+
+    let canvas = render_model(
+        teapot,
+        tuple::Point::new(0.0, 2.0, -4.0),
+        tuple::Point::new(0.0, 0.7, 0.0),
+    );
+
+    assert_matches_fixture(&canvas, "teapot_high");
+    return Ok(());
+}
+
+// Three low-poly teapots sharing one mesh, each an instance with its own
+// transform; the middle one keeps the mesh's white material, the others
+// override it.
+#[test]
+fn test_instanced_teapots() -> Result<(), std::io::Error> {
+    let teapot = std::sync::Arc::new(
+        shape::ShapeBuilder::from(load_mesh("object_files/teapot-low.obj")?)
+            .set_transform(
+                matrix::Matrix4::IDENTITY
+                    .rotation_x(-std::f64::consts::PI / 2.0)
+                    .scaling(0.05, 0.05, 0.05),
+            )
+            .build(),
+    );
+    let colored = |color| {
+        let mut material = material::material();
+        material.color = color;
+        return Some(material);
+    };
+
+    let mut group = shape::Shape::default_group();
+    for (x, turn, material) in [
+        (-1.7, 0.8, colored(color::color(0.9, 0.3, 0.2))),
+        (0.0, 0.0, None),
+        (1.7, -0.8, colored(color::color(0.2, 0.5, 0.9))),
+    ] {
+        group.add_child(
+            shape::ShapeBuilder::instance(teapot.clone(), material)
+                .set_transform(
+                    matrix::Matrix4::IDENTITY
+                        .rotation_y(turn)
+                        .translation(x, 0.0, 0.0),
+                )
+                .build(),
+        );
+    }
+
+    let canvas = render_model(
+        group,
+        tuple::Point::new(0.0, 2.0, -4.0),
+        tuple::Point::new(0.0, 0.5, 0.0),
+    );
+
+    assert_matches_fixture(&canvas, "instanced_teapots");
+    return Ok(());
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -42,7 +44,7 @@ fn test_rendering_a_scene_file() -> Result<(), std::io::Error> {
     let scene = scene_file::SceneFile::parse(&source).unwrap();
 
     let world = scene.build_world().unwrap();
-    let camera = scene.build_camera(SCALE);
+    let camera = scene.build_camera(SCALE).unwrap();
     let canvas = camera.render(&world);
 
     assert_matches_fixture(&canvas, "multiple_lights");
@@ -57,7 +59,7 @@ fn test_rendering_a_scene_file_with_an_area_light() -> Result<(), std::io::Error
     let scene = scene_file::SceneFile::parse(&source).unwrap();
 
     let world = scene.build_world().unwrap();
-    let camera = scene.build_camera(SCALE);
+    let camera = scene.build_camera(SCALE).unwrap();
     let canvas = camera.render(&world);
 
     assert_matches_fixture(&canvas, "area_light");
@@ -74,10 +76,186 @@ fn test_rendering_animation_frames() -> Result<(), std::io::Error> {
     assert_eq!(animation.frame_count(), 3);
     for frame_index in 0..animation.frame_count() {
         let world = animation.build_world(frame_index).unwrap();
-        let camera = animation.build_camera(frame_index, SCALE);
+        let camera = animation.build_camera(frame_index, SCALE).unwrap();
         let canvas = camera.render(&world);
 
         assert_matches_fixture(&canvas, &format!("sphere_moves_{}", frame_index));
     }
     return Ok(());
 }
+
+// Shading tiles on several threads must not change a single pixel, even
+// with the area light's shared jitter sequence in play.
+#[test]
+fn test_rendering_a_scene_file_in_parallel() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/area_light.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render_parallel(&world, 4);
+
+    assert_matches_fixture(&canvas, "area_light");
+    return Ok(());
+}
+
+// A thin-lens camera focused on the teapot, so the spheres in front of and
+// behind it blur.
+#[test]
+fn test_rendering_a_scene_file_with_depth_of_field() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/depth_of_field_teapot.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "depth_of_field_teapot");
+    return Ok(());
+}
+
+// One teapot mesh placed seven times: the scene file's instances share it,
+// each with its own transform, and two with their own material.
+#[test]
+fn test_rendering_a_scene_file_with_instances() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/teapot_forest.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "teapot_forest");
+    return Ok(());
+}
+
+// An orthographic camera: the two balls, at different distances from the
+// camera, come out the same size.
+#[test]
+fn test_rendering_a_scene_file_with_an_orthographic_camera() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/orthographic_blocks.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "orthographic_blocks");
+    return Ok(());
+}
+
+// An equirectangular panorama: every direction around the camera, with
+// the ball behind it split across the image's left and right edges.
+#[test]
+fn test_rendering_a_scene_file_with_an_equirectangular_camera() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/panorama.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "panorama");
+    return Ok(());
+}
+
+// Path traced: the red and green walls bleed their colors onto the ball
+// and the floor beside them.
+#[test]
+fn test_rendering_a_path_traced_scene_file() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/color_bleeding.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "color_bleeding");
+    return Ok(());
+}
+
+// Ambient occlusion only: the floor darkens where it meets the teapot.
+#[test]
+fn test_rendering_an_ambient_occlusion_scene_file() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/occlusion_teapot.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "occlusion_teapot");
+    return Ok(());
+}
+
+// A low sun: the pillars' shadows run parallel, and soften towards their
+// ends.
+#[test]
+fn test_rendering_a_scene_file_with_a_directional_light() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/sunlight.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "sunlight");
+    return Ok(());
+}
+
+// A ball of light and a disk of light: soft shadows with rounded edges.
+#[test]
+fn test_rendering_a_scene_file_with_round_lights() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/round_lights.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "round_lights");
+    return Ok(());
+}
+
+// A lamp with inverse square falloff: each ball is dimmer the farther it
+// is from the lamp.
+#[test]
+fn test_rendering_a_scene_file_with_light_falloff() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/falloff.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "falloff");
+    return Ok(());
+}
+
+// A projector casting an image: the map wraps over the ball, which
+// shadows the map on the wall.
+#[test]
+fn test_rendering_a_scene_file_with_a_projector() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/projector.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "projector");
+    return Ok(());
+}
+
+#[test]
+fn test_rendering_a_scene_file_with_colored_shadows() -> Result<(), std::io::Error> {
+    let source = std::fs::read_to_string("scenes/colored_shadows.toml")?;
+    let scene = scene_file::SceneFile::parse(&source).unwrap();
+
+    let world = scene.build_world().unwrap();
+    let camera = scene.build_camera(SCALE).unwrap();
+    let canvas = camera.render(&world);
+
+    assert_matches_fixture(&canvas, "colored_shadows");
+    return Ok(());
+}
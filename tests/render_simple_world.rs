@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -12,21 +14,21 @@ mod test_helpers {
 
     pub fn create_rectangular_camera() -> camera::Camera {
         let mut camera = camera::Camera::new(100 * SCALE, 50 * SCALE, std::f64::consts::PI / 3.0);
-        camera.transform = transformation::view_transform(
+        camera.set_transformation_matrix(transformation::view_transform(
             &tuple::Point::new(0.0, 1.5, -5.0),
             &tuple::Point::new(0.0, 1.0, 0.0),
             &tuple::Vector::new(0.0, 1.0, 0.0),
-        );
+        ));
         return camera;
     }
 
     pub fn create_square_camera() -> camera::Camera {
         let mut camera = camera::Camera::new(50 * SCALE, 50 * SCALE, std::f64::consts::PI / 3.0);
-        camera.transform = transformation::view_transform(
+        camera.set_transformation_matrix(transformation::view_transform(
             &tuple::Point::new(0.0, 1.5, -5.0),
             &tuple::Point::new(0.0, 1.0, 0.0),
             &tuple::Vector::new(0.0, 1.0, 0.0),
-        );
+        ));
         return camera;
     }
 
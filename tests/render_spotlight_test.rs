@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -40,7 +42,7 @@ fn assert_scene_matches_fixture(name: &str) -> Result<(), std::io::Error> {
     let scene = scene_file::SceneFile::parse(&source).unwrap();
 
     let world = scene.build_world().unwrap();
-    let camera = scene.build_camera(SCALE);
+    let camera = scene.build_camera(SCALE).unwrap();
     let canvas = camera.render(&world);
 
     assert_matches_fixture(&canvas, name);
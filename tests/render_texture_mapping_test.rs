@@ -1,3 +1,5 @@
+#![allow(clippy::style, clippy::approx_constant)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -41,11 +43,11 @@ fn test_uv_checkered_sphere() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, 0.5);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 0.0, -5.0),
         &tuple::Point::new(0.0, 0.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -93,11 +95,11 @@ fn test_uv_checkered_plane() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, 0.5);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(1.0, 2.0, -5.0),
         &tuple::Point::new(0.0, 0.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -151,11 +153,11 @@ fn test_uv_checkered_cylinder() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, 0.5);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 0.0, -10.0),
         &tuple::Point::new(0.0, 0.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -203,11 +205,11 @@ fn test_align_check_plane() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(100 * SCALE, 100 * SCALE, 0.5);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(1.0, 2.0, -5.0),
         &tuple::Point::new(0.0, 0.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -286,11 +288,11 @@ fn test_uv_mapped_cube() -> Result<(), std::io::Error> {
     }
 
     let mut camera = camera::Camera::new(200 * SCALE, 100 * SCALE, 0.8);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 0.0, -20.0),
         &tuple::Point::new(0.0, 0.0, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -370,11 +372,11 @@ fn test_earth() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(200 * SCALE, 100 * SCALE, 0.8);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(1.0, 2.0, -10.0),
         &tuple::Point::new(0.0, 1.1, 0.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
@@ -447,11 +449,11 @@ fn test_skybox() -> Result<(), std::io::Error> {
     ));
 
     let mut camera = camera::Camera::new(200 * SCALE, 100 * SCALE, 1.2);
-    camera.transform = transformation::view_transform(
+    camera.set_transformation_matrix(transformation::view_transform(
         &tuple::Point::new(0.0, 0.0, 0.0),
         &tuple::Point::new(0.0, 0.0, 5.0),
         &tuple::Vector::new(0.0, 1.0, 0.0),
-    );
+    ));
 
     let canvas = camera.render(&builder.world);
 
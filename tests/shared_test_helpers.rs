@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 use std::path::Path;
 
 use ray_tracer::canvas;
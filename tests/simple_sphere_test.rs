@@ -1,3 +1,5 @@
+#![allow(clippy::style)]
+
 extern crate ray_tracer;
 
 mod shared_test_helpers;
@@ -48,7 +50,7 @@ fn test_simple_sphere_test() -> Result<(), std::io::Error> {
                         &point,
                         &camera,
                         &normal,
-                        1.0,
+                        color::white(),
                     );
                     canvas.write_pixel(x, y, color);
                 }
@@ -118,7 +120,7 @@ fn test_translated_sphere_test() -> Result<(), std::io::Error> {
                         &point,
                         &camera,
                         &normal,
-                        1.0,
+                        color::white(),
                     );
                     canvas.write_pixel(x, y, color);
                 }